@@ -13,7 +13,6 @@ use syn::{Data, DeriveInput, Fields, FieldsNamed, FieldsUnnamed, Index};
 /// #[derive(CompositeType)]
 /// #[amqp(name = "my:teststruct", code = 123)]
 /// struct TestStruct { }
-
 pub(crate) fn derive_for_struct(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     let name = &input.ident;
     if let Data::Struct(ref st) = input.data {
@@ -69,7 +69,7 @@ fn parse_descriptor(span: Span, attrs: &Vec<Attribute>) -> syn::Result<Descripto
     for attr in attrs {
         if attr.path().is_ident("amqp") {
             if let Meta::List(meta_list) = &attr.meta {
-                return Ok(syn::parse2(meta_list.clone().tokens)?)
+                return syn::parse2(meta_list.clone().tokens)
             }
         }
     }
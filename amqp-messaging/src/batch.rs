@@ -0,0 +1,249 @@
+use crate::message::{Body, Message};
+use amqp_type::composite::messaging::annotations::Annotations;
+use amqp_type::composite::messaging::section::Section;
+use amqp_type::error::amqp_error::AmqpError;
+use amqp_type::error::AppError;
+use amqp_type::primitive::variable_width::binary::Binary;
+use amqp_type::restricted::message_format::MessageFormat;
+
+/// The message format of a single message as defined by the AMQP 1.0 specification.
+pub const STANDARD_MESSAGE_FORMAT: MessageFormat = 0;
+/// The message format of a batch, as used by Azure Event Hubs and Service Bus.
+/// The body of a batch is a sequence of data sections, each holding one encoded message.
+pub const BATCH_MESSAGE_FORMAT: MessageFormat = 0x8001_3700;
+
+/// # Message Batch
+/// Packs several messages into a single transfer with message format [`BATCH_MESSAGE_FORMAT`].
+///
+/// Small messages sent one by one are dominated by per-transfer framing. A batch encodes each
+/// message into a data section of one envelope message, so only the envelope pays that overhead.
+/// The message annotations of the first message are copied to the envelope, so brokers can
+/// route the batch as a whole (for example by partition key).
+///
+/// ```
+///# use amqp_messaging::batch::MessageBatch;
+///# use amqp_messaging::message::Message;
+/// let mut batch = MessageBatch::new(1024);
+/// assert!(batch.try_add(&Message::from_value("first")));
+/// assert!(batch.try_add(&Message::from_value("second")));
+/// let format = batch.message_format();
+/// let messages = MessageBatch::unpack(format, batch.encode().unwrap()).unwrap();
+/// assert_eq!(messages.len(), 2);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageBatch {
+    annotations: Option<Annotations>,
+    data: Vec<Binary>,
+    size: usize,
+    max_size: usize,
+}
+
+impl MessageBatch {
+    /// Creates an empty batch whose encoded envelope will not exceed `max_size` bytes.
+    pub fn new(max_size: usize) -> Self {
+        MessageBatch {
+            annotations: None,
+            data: Vec::new(),
+            size: 0,
+            max_size,
+        }
+    }
+
+    /// Further limits the batch to the `max-message-size` negotiated on the link.
+    /// A missing or zero value means the peer imposes no limit.
+    pub fn with_max_message_size(mut self, max_message_size: Option<u64>) -> Self {
+        if let Some(limit) = max_message_size.filter(|&limit| limit > 0) {
+            self.max_size = self
+                .max_size
+                .min(usize::try_from(limit).unwrap_or(usize::MAX));
+        }
+        self
+    }
+
+    /// Adds a message to the batch. Returns `false` and leaves the batch unchanged
+    /// if the message would push the batch over its size limit.
+    pub fn try_add(&mut self, message: &Message) -> bool {
+        let annotations = match self.data.is_empty() {
+            true => message.message_annotations().cloned(),
+            false => None,
+        };
+        let annotations_size = annotations
+            .clone()
+            .map_or(0, |a| Section::MessageAnnotations(a).encode().len());
+        let data = Binary::from(message.clone().encode());
+        let data_size = Section::Data(data.clone()).encode().len();
+        if self.size + annotations_size + data_size > self.max_size {
+            return false;
+        }
+
+        if annotations.is_some() {
+            self.annotations = annotations;
+        }
+        self.data.push(data);
+        self.size += annotations_size + data_size;
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// The encoded size of the batch in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// The message format the transfer carrying this batch must be sent with.
+    pub fn message_format(&self) -> MessageFormat {
+        BATCH_MESSAGE_FORMAT
+    }
+
+    /// The envelope message carrying the batch.
+    /// Fails for an empty batch, as an envelope without data sections is not a valid message.
+    pub fn into_message(self) -> Result<Message, AppError> {
+        if self.data.is_empty() {
+            Err(AmqpError::NotAllowed)?
+        }
+        let envelope = Message::new(Body::Data(self.data));
+        Ok(match self.annotations {
+            Some(annotations) => envelope.with_message_annotations(annotations),
+            None => envelope,
+        })
+    }
+
+    pub fn encode(self) -> Result<Vec<u8>, AppError> {
+        Ok(self.into_message()?.encode())
+    }
+
+    /// Decodes the payload of a transfer into the messages it carries, according to its message format.
+    pub fn unpack(format: MessageFormat, payload: Vec<u8>) -> Result<Vec<Message>, AppError> {
+        let message = Message::try_decode(&mut payload.into_iter())?;
+        match format {
+            STANDARD_MESSAGE_FORMAT => Ok(vec![message]),
+            BATCH_MESSAGE_FORMAT => match message.body() {
+                Body::Data(sections) => sections
+                    .iter()
+                    .map(|data| Message::try_decode(&mut Vec::from(data.clone()).into_iter()))
+                    .collect(),
+                _ => Err(AmqpError::DecodeError)?,
+            },
+            _ => Err(AmqpError::NotImplemented)?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amqp_type::composite::messaging::properties::Properties;
+    use amqp_type::primitive::variable_width::symbol::Symbol;
+    use amqp_type::primitive::Primitive;
+
+    fn event(i: u32) -> Message {
+        Message::from_value(i).with_properties(Properties::default().with_message_id(i as u64))
+    }
+
+    #[test]
+    fn test_pack_unpack_round_trip() {
+        let mut batch = MessageBatch::new(4096);
+        (0..10).for_each(|i| assert!(batch.try_add(&event(i))));
+        assert_eq!(batch.len(), 10);
+        let messages = MessageBatch::unpack(BATCH_MESSAGE_FORMAT, batch.encode().unwrap()).unwrap();
+        assert_eq!(messages, (0..10).map(event).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_empty_batch_cannot_be_encoded() {
+        let batch = MessageBatch::new(4096);
+        assert!(batch.is_empty());
+        assert!(matches!(
+            batch.clone().encode(),
+            Err(AppError::Amqp(AmqpError::NotAllowed))
+        ));
+        assert!(batch.into_message().is_err());
+    }
+
+    #[test]
+    fn test_size_matches_encoding() {
+        let mut batch = MessageBatch::new(4096);
+        let mut first = event(0);
+        first
+            .message_annotations_mut()
+            .insert(Symbol::with_ascii("x-opt-partition-key"), "orders");
+        assert!(batch.try_add(&first));
+        assert!(batch.try_add(&event(1)));
+        let size = batch.size();
+        assert_eq!(batch.encode().unwrap().len(), size);
+    }
+
+    #[test]
+    fn test_batch_rejects_message_exceeding_limit() {
+        let mut probe = MessageBatch::new(usize::MAX);
+        (0..3).for_each(|i| assert!(probe.try_add(&event(i))));
+
+        let mut batch = MessageBatch::new(probe.size());
+        (0..3).for_each(|i| assert!(batch.try_add(&event(i))));
+        assert!(!batch.try_add(&event(3)));
+        assert_eq!(batch.len(), 3);
+        assert!(batch.size() <= batch.max_size());
+    }
+
+    #[test]
+    fn test_max_message_size_limits_batch() {
+        let batch = MessageBatch::new(4096).with_max_message_size(Some(256));
+        assert_eq!(batch.max_size(), 256);
+        let batch = MessageBatch::new(4096).with_max_message_size(Some(0));
+        assert_eq!(batch.max_size(), 4096);
+        let batch = MessageBatch::new(128).with_max_message_size(None);
+        assert_eq!(batch.max_size(), 128);
+    }
+
+    #[test]
+    fn test_envelope_carries_annotations_of_first_message() {
+        let mut first = event(0);
+        first
+            .message_annotations_mut()
+            .insert(Symbol::with_ascii("x-opt-partition-key"), "orders");
+        let mut batch = MessageBatch::new(4096);
+        assert!(batch.try_add(&first));
+        assert!(batch.try_add(&event(1)));
+        let envelope = batch.into_message().unwrap();
+        assert_eq!(
+            envelope
+                .message_annotations()
+                .unwrap()
+                .get("x-opt-partition-key"),
+            Some(&Primitive::String("orders".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_unpack_standard_format_yields_single_message() {
+        let messages = MessageBatch::unpack(STANDARD_MESSAGE_FORMAT, event(7).encode()).unwrap();
+        assert_eq!(messages, vec![event(7)]);
+    }
+
+    #[test]
+    fn test_unpack_unknown_format_fails() {
+        assert!(matches!(
+            MessageBatch::unpack(0x1234_5600, event(7).encode()),
+            Err(AppError::Amqp(AmqpError::NotImplemented))
+        ));
+    }
+
+    #[test]
+    fn test_unpack_batch_without_data_body_fails() {
+        assert!(matches!(
+            MessageBatch::unpack(BATCH_MESSAGE_FORMAT, event(7).encode()),
+            Err(AppError::Amqp(AmqpError::DecodeError))
+        ));
+    }
+}
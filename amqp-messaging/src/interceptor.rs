@@ -0,0 +1,111 @@
+use crate::message::Message;
+
+/// # Interceptor
+/// Hook into the send and receive paths of a link.
+///
+/// Interceptors are opt-in: they only run if they are registered on the connection or link.
+/// `on_send` runs just before a message is encoded for transfer, `on_receive` right after a
+/// delivery has been decoded and before it is handed to the application.
+pub trait Interceptor: Send + Sync {
+    fn on_send(&self, _message: &mut Message) {}
+
+    fn on_receive(&self, _message: &mut Message) {}
+}
+
+/// An ordered chain of interceptors.
+/// Outgoing messages pass the interceptors in registration order, incoming messages in reverse.
+#[derive(Default)]
+pub struct Interceptors(Vec<Box<dyn Interceptor>>);
+
+impl Interceptors {
+    pub fn new() -> Self {
+        Interceptors(Vec::new())
+    }
+
+    pub fn push<I: Interceptor + 'static>(&mut self, interceptor: I) {
+        self.0.push(Box::new(interceptor));
+    }
+
+    pub fn with<I: Interceptor + 'static>(mut self, interceptor: I) -> Self {
+        self.push(interceptor);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn on_send(&self, message: &mut Message) {
+        self.0.iter().for_each(|i| i.on_send(message));
+    }
+
+    pub fn on_receive(&self, message: &mut Message) {
+        self.0.iter().rev().for_each(|i| i.on_receive(message));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amqp_type::primitive::Primitive;
+    use std::sync::{Arc, Mutex};
+
+    struct Recorder {
+        name: &'static str,
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Interceptor for Recorder {
+        fn on_send(&self, message: &mut Message) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("send:{}", self.name));
+            message
+                .application_properties_mut()
+                .insert(self.name, true)
+                .unwrap();
+        }
+
+        fn on_receive(&self, _message: &mut Message) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("receive:{}", self.name));
+        }
+    }
+
+    #[test]
+    fn test_interceptors_run_in_order() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let interceptors = Interceptors::new()
+            .with(Recorder {
+                name: "a",
+                calls: calls.clone(),
+            })
+            .with(Recorder {
+                name: "b",
+                calls: calls.clone(),
+            });
+        let mut message = Message::from_value("hello");
+        interceptors.on_send(&mut message);
+        interceptors.on_receive(&mut message);
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec!["send:a", "send:b", "receive:b", "receive:a"]
+        );
+        assert_eq!(
+            message.application_properties().unwrap().get("b"),
+            Some(&Primitive::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn test_empty_chain_leaves_message_untouched() {
+        let interceptors = Interceptors::default();
+        let mut message = Message::from_value("hello");
+        interceptors.on_send(&mut message);
+        assert!(interceptors.is_empty());
+        assert_eq!(message, Message::from_value("hello"));
+    }
+}
@@ -0,0 +1,385 @@
+use amqp_type::composite::messaging::annotations::Annotations;
+use amqp_type::composite::messaging::application_properties::ApplicationProperties;
+use amqp_type::composite::messaging::header::Header;
+use amqp_type::composite::messaging::properties::Properties;
+use amqp_type::composite::messaging::section::Section;
+use amqp_type::error::amqp_error::AmqpError;
+use amqp_type::error::AppError;
+use amqp_type::primitive::compound::list::List;
+use amqp_type::primitive::variable_width::binary::Binary;
+use amqp_type::primitive::Primitive;
+use std::vec::IntoIter;
+
+/// # Body
+/// The application data of a message.
+///
+/// The body consists of either one or more data sections, one or more amqp-sequence sections,
+/// or a single amqp-value section.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Body {
+    Data(Vec<Binary>),
+    Sequence(Vec<List>),
+    Value(Primitive),
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Body::Value(Primitive::Null)
+    }
+}
+
+impl Body {
+    fn into_sections(self) -> Vec<Section> {
+        match self {
+            Body::Data(data) => data.into_iter().map(Section::Data).collect(),
+            Body::Sequence(sequences) => sequences.into_iter().map(Section::AmqpSequence).collect(),
+            Body::Value(value) => vec![Section::AmqpValue(value)],
+        }
+    }
+
+    /// Encodes the body sections exactly as they appear within a message.
+    pub fn encode(self) -> Vec<u8> {
+        encode_sections(self.into_sections())
+    }
+
+    /// Decodes body sections until the stream is exhausted.
+    pub fn try_decode(stream: &mut IntoIter<u8>) -> Result<Self, AppError> {
+        let mut body = None;
+        while !stream.as_slice().is_empty() {
+            Body::push(&mut body, Section::try_decode(stream)?)?;
+        }
+        Ok(body.ok_or(AmqpError::DecodeError)?)
+    }
+
+    /// Appends a body section to this body. Fails if the section is not a body section,
+    /// or does not match the kind of body that was already started.
+    fn push(body: &mut Option<Body>, section: Section) -> Result<(), AppError> {
+        match (body.as_mut(), section) {
+            (None, Section::Data(x)) => *body = Some(Body::Data(vec![x])),
+            (None, Section::AmqpSequence(x)) => *body = Some(Body::Sequence(vec![x])),
+            (None, Section::AmqpValue(x)) => *body = Some(Body::Value(x)),
+            (Some(Body::Data(data)), Section::Data(x)) => data.push(x),
+            (Some(Body::Sequence(sequences)), Section::AmqpSequence(x)) => sequences.push(x),
+            _ => Err(AmqpError::DecodeError)?,
+        }
+        Ok(())
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(value: Vec<u8>) -> Self {
+        Body::Data(vec![Binary::from(value)])
+    }
+}
+
+/// # Message
+/// An annotated AMQP message.
+///
+/// The bare message consists of the properties, application-properties and body sections and is
+/// immutable once sent. The header, annotations and footer may be changed by intermediaries.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Message {
+    header: Option<Header>,
+    delivery_annotations: Option<Annotations>,
+    message_annotations: Option<Annotations>,
+    properties: Option<Properties>,
+    application_properties: Option<ApplicationProperties>,
+    body: Body,
+    footer: Option<Annotations>,
+}
+
+impl Message {
+    pub fn new(body: Body) -> Self {
+        Message {
+            body,
+            ..Default::default()
+        }
+    }
+
+    pub fn from_data(data: Vec<u8>) -> Self {
+        Message::new(Body::from(data))
+    }
+
+    pub fn from_value<T: Into<Primitive>>(value: T) -> Self {
+        Message::new(Body::Value(value.into()))
+    }
+
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
+    pub fn delivery_annotations(&self) -> Option<&Annotations> {
+        self.delivery_annotations.as_ref()
+    }
+
+    pub fn message_annotations(&self) -> Option<&Annotations> {
+        self.message_annotations.as_ref()
+    }
+
+    pub fn properties(&self) -> Option<&Properties> {
+        self.properties.as_ref()
+    }
+
+    pub fn application_properties(&self) -> Option<&ApplicationProperties> {
+        self.application_properties.as_ref()
+    }
+
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
+
+    pub fn footer(&self) -> Option<&Annotations> {
+        self.footer.as_ref()
+    }
+
+    pub fn with_header(mut self, header: Header) -> Self {
+        self.header = Some(header);
+        self
+    }
+
+    pub fn with_delivery_annotations(mut self, annotations: Annotations) -> Self {
+        self.delivery_annotations = Some(annotations);
+        self
+    }
+
+    pub fn with_message_annotations(mut self, annotations: Annotations) -> Self {
+        self.message_annotations = Some(annotations);
+        self
+    }
+
+    pub fn with_properties(mut self, properties: Properties) -> Self {
+        self.properties = Some(properties);
+        self
+    }
+
+    pub fn with_application_properties(mut self, properties: ApplicationProperties) -> Self {
+        self.application_properties = Some(properties);
+        self
+    }
+
+    pub fn with_footer(mut self, footer: Annotations) -> Self {
+        self.footer = Some(footer);
+        self
+    }
+
+    /// Returns the message annotations, creating an empty section if there is none yet.
+    pub fn message_annotations_mut(&mut self) -> &mut Annotations {
+        self.message_annotations
+            .get_or_insert_with(Annotations::new)
+    }
+
+    /// Returns the application properties, creating an empty section if there is none yet.
+    pub fn application_properties_mut(&mut self) -> &mut ApplicationProperties {
+        self.application_properties
+            .get_or_insert_with(ApplicationProperties::new)
+    }
+
+    /// Returns the footer, creating an empty section if there is none yet.
+    pub fn footer_mut(&mut self) -> &mut Annotations {
+        self.footer.get_or_insert_with(Annotations::new)
+    }
+
+    pub fn set_body(&mut self, body: Body) {
+        self.body = body;
+    }
+
+    /// All sections of the message in the order they appear on the wire.
+    pub fn into_sections(self) -> Vec<Section> {
+        let mut sections = Vec::new();
+        sections.extend(self.header.map(Section::Header));
+        sections.extend(self.delivery_annotations.map(Section::DeliveryAnnotations));
+        sections.extend(self.message_annotations.map(Section::MessageAnnotations));
+        sections.extend(bare_sections(
+            self.properties,
+            self.application_properties,
+            self.body,
+        ));
+        sections.extend(self.footer.map(Section::Footer));
+        sections
+    }
+
+    pub fn encode(self) -> Vec<u8> {
+        encode_sections(self.into_sections())
+    }
+
+    /// Encodes only the bare message, which are the properties, application-properties and body
+    /// sections. These are the exact bytes a sender puts on the wire for them, so they can be used
+    /// to compute hashes and signatures that a receiver can verify after decoding the message.
+    pub fn encode_bare(&self) -> Vec<u8> {
+        encode_sections(bare_sections(
+            self.properties.clone(),
+            self.application_properties.clone(),
+            self.body.clone(),
+        ))
+    }
+
+    /// Decodes a message from the stream. The whole remaining stream is consumed as message sections.
+    pub fn try_decode(stream: &mut IntoIter<u8>) -> Result<Self, AppError> {
+        let mut message = Message::default();
+        let mut body = None;
+        let mut last_code = 0;
+        while !stream.as_slice().is_empty() {
+            let section = Section::try_decode(stream)?;
+            let code = section.code();
+            // sections must appear in the order defined by the specification
+            // and only body sections may be repeated.
+            if code < last_code || (code == last_code && !is_repeatable(&section)) {
+                Err(AmqpError::DecodeError)?
+            }
+            if body.is_some() && !is_body(&section) && !matches!(section, Section::Footer(_)) {
+                Err(AmqpError::DecodeError)?
+            }
+            last_code = code;
+            match section {
+                Section::Header(x) => message.header = Some(x),
+                Section::DeliveryAnnotations(x) => message.delivery_annotations = Some(x),
+                Section::MessageAnnotations(x) => message.message_annotations = Some(x),
+                Section::Properties(x) => message.properties = Some(x),
+                Section::ApplicationProperties(x) => message.application_properties = Some(x),
+                Section::Footer(x) => message.footer = Some(x),
+                body_section => Body::push(&mut body, body_section)?,
+            }
+        }
+        message.body = body.ok_or(AmqpError::DecodeError)?;
+        Ok(message)
+    }
+}
+
+fn bare_sections(
+    properties: Option<Properties>,
+    application_properties: Option<ApplicationProperties>,
+    body: Body,
+) -> Vec<Section> {
+    let mut sections = Vec::new();
+    sections.extend(properties.map(Section::Properties));
+    sections.extend(application_properties.map(Section::ApplicationProperties));
+    sections.extend(body.into_sections());
+    sections
+}
+
+fn is_body(section: &Section) -> bool {
+    matches!(
+        section,
+        Section::Data(_) | Section::AmqpSequence(_) | Section::AmqpValue(_)
+    )
+}
+
+fn is_repeatable(section: &Section) -> bool {
+    matches!(section, Section::Data(_) | Section::AmqpSequence(_))
+}
+
+fn encode_sections(sections: Vec<Section>) -> Vec<u8> {
+    let mut res = Vec::new();
+    for section in sections {
+        res.append(&mut section.encode());
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amqp_type::primitive::variable_width::symbol::Symbol;
+
+    fn full_message() -> Message {
+        let mut annotations = Annotations::new();
+        annotations.insert(Symbol::with_ascii("x-opt-partition"), 1);
+        let mut application_properties = ApplicationProperties::new();
+        application_properties.insert("tenant", "acme").unwrap();
+        Message::new(Body::Data(vec![
+            Binary::from(vec![1, 2]),
+            Binary::from(vec![3]),
+        ]))
+        .with_header(Header::default().with_durable(true))
+        .with_delivery_annotations(annotations.clone())
+        .with_message_annotations(annotations.clone())
+        .with_properties(Properties::default().with_message_id(1_u64))
+        .with_application_properties(application_properties)
+        .with_footer(annotations)
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip_full_message() {
+        let initial = full_message();
+        let encoded = initial.clone().encode();
+        let decoded = Message::try_decode(&mut encoded.into_iter()).unwrap();
+        assert_eq!(decoded, initial);
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip_value_body() {
+        let initial = Message::from_value("hello");
+        let encoded = initial.clone().encode();
+        let decoded = Message::try_decode(&mut encoded.into_iter()).unwrap();
+        assert_eq!(decoded, initial);
+    }
+
+    #[test]
+    fn test_encode_bare_excludes_annotated_sections() {
+        let message = full_message();
+        let expected = encode_sections(vec![
+            Section::Properties(message.properties().unwrap().clone()),
+            Section::ApplicationProperties(message.application_properties().unwrap().clone()),
+            Section::Data(Binary::from(vec![1, 2])),
+            Section::Data(Binary::from(vec![3])),
+        ]);
+        assert_eq!(message.encode_bare(), expected);
+    }
+
+    #[test]
+    fn test_encode_bare_is_stable_across_decoding() {
+        let message = full_message();
+        let encoded = message.clone().encode();
+        let decoded = Message::try_decode(&mut encoded.into_iter()).unwrap();
+        assert_eq!(decoded.encode_bare(), message.encode_bare());
+    }
+
+    #[test]
+    fn test_body_encode_decode_round_trip() {
+        let initial = Body::Sequence(vec![List::from(vec![1, 2]), List::from(vec![3])]);
+        let encoded = initial.clone().encode();
+        assert_eq!(Body::try_decode(&mut encoded.into_iter()).unwrap(), initial);
+    }
+
+    #[test]
+    fn test_body_decode_rejects_non_body_sections() {
+        let encoded = encode_sections(vec![Section::Header(Header::default())]);
+        assert!(matches!(
+            Body::try_decode(&mut encoded.into_iter()),
+            Err(AppError::Amqp(AmqpError::DecodeError))
+        ));
+    }
+
+    #[test]
+    fn test_decode_rejects_sections_out_of_order() {
+        let encoded = encode_sections(vec![
+            Section::AmqpValue(Primitive::Null),
+            Section::Header(Header::default()),
+        ]);
+        assert!(matches!(
+            Message::try_decode(&mut encoded.into_iter()),
+            Err(AppError::Amqp(AmqpError::DecodeError))
+        ));
+    }
+
+    #[test]
+    fn test_decode_rejects_mixed_body_sections() {
+        let encoded = encode_sections(vec![
+            Section::Data(Binary::from(vec![1])),
+            Section::AmqpValue(Primitive::Null),
+        ]);
+        assert!(matches!(
+            Message::try_decode(&mut encoded.into_iter()),
+            Err(AppError::Amqp(AmqpError::DecodeError))
+        ));
+    }
+
+    #[test]
+    fn test_decode_rejects_message_without_body() {
+        let encoded = encode_sections(vec![Section::Header(Header::default())]);
+        assert!(matches!(
+            Message::try_decode(&mut encoded.into_iter()),
+            Err(AppError::Amqp(AmqpError::DecodeError))
+        ));
+    }
+}
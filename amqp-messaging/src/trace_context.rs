@@ -0,0 +1,382 @@
+use crate::interceptor::Interceptor;
+use crate::message::Message;
+use amqp_type::primitive::variable_width::symbol::Symbol;
+use amqp_type::primitive::Primitive;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Key of the `traceparent` field, as defined by the W3C Trace Context AMQP protocol draft.
+pub const TRACEPARENT: &str = "traceparent";
+/// Key of the `tracestate` field, as defined by the W3C Trace Context AMQP protocol draft.
+pub const TRACESTATE: &str = "tracestate";
+
+const SUPPORTED_VERSION: u8 = 0;
+const INVALID_VERSION: u8 = 0xff;
+
+/// # Trace Parent
+/// The `traceparent` of the W3C Trace Context, identifying the span that sent a message.
+///
+/// Serialized as `{version}-{trace-id}-{parent-id}-{trace-flags}` in lowercase hex, for example
+/// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceParent {
+    trace_id: [u8; 16],
+    parent_id: [u8; 8],
+    flags: u8,
+}
+
+impl TraceParent {
+    /// Returns `None` if the trace id or parent id is all zeroes, which the specification forbids.
+    pub fn new(trace_id: [u8; 16], parent_id: [u8; 8], flags: u8) -> Option<Self> {
+        if trace_id == [0; 16] || parent_id == [0; 8] {
+            return None;
+        }
+        Some(TraceParent {
+            trace_id,
+            parent_id,
+            flags,
+        })
+    }
+
+    pub fn trace_id(&self) -> [u8; 16] {
+        self.trace_id
+    }
+
+    pub fn parent_id(&self) -> [u8; 8] {
+        self.parent_id
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    pub fn sampled(&self) -> bool {
+        self.flags & 0x01 == 0x01
+    }
+}
+
+impl Display for TraceParent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02x}-{}-{}-{:02x}",
+            SUPPORTED_VERSION,
+            hex(&self.trace_id),
+            hex(&self.parent_id),
+            self.flags
+        )
+    }
+}
+
+impl FromStr for TraceParent {
+    type Err = ();
+
+    /// Parses a `traceparent` value. Versions above 00 are accepted as long as they start with
+    /// the fields known from version 00, as the specification requires for forward compatibility.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split('-');
+        let version = fields.next().map(parse_hex::<1>).ok_or(())??[0];
+        let trace_id = fields.next().map(parse_hex::<16>).ok_or(())??;
+        let parent_id = fields.next().map(parse_hex::<8>).ok_or(())??;
+        let flags = fields.next().map(parse_hex::<1>).ok_or(())??[0];
+        let has_more_fields = fields.next().is_some();
+        match version {
+            INVALID_VERSION => Err(()),
+            SUPPORTED_VERSION if has_more_fields => Err(()),
+            _ => TraceParent::new(trace_id, parent_id, flags).ok_or(()),
+        }
+    }
+}
+
+/// # Trace Context
+/// The W3C trace context propagated with a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    traceparent: TraceParent,
+    tracestate: Option<String>,
+}
+
+/// Where the trace context is stored in the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Carrier {
+    /// Message annotations may be modified by intermediaries, so a broker can add its own span.
+    #[default]
+    MessageAnnotations,
+    /// Application properties are part of the bare message and pass all intermediaries unchanged.
+    ApplicationProperties,
+}
+
+impl TraceContext {
+    pub fn new(traceparent: TraceParent) -> Self {
+        TraceContext {
+            traceparent,
+            tracestate: None,
+        }
+    }
+
+    pub fn traceparent(&self) -> &TraceParent {
+        &self.traceparent
+    }
+
+    pub fn tracestate(&self) -> Option<&str> {
+        self.tracestate.as_deref()
+    }
+
+    pub fn with_tracestate(mut self, tracestate: String) -> Self {
+        self.tracestate = Some(tracestate);
+        self
+    }
+
+    /// Writes the trace context into the message, replacing any previous context in the carrier.
+    pub fn inject(&self, message: &mut Message, carrier: Carrier) {
+        match carrier {
+            Carrier::MessageAnnotations => {
+                let annotations = message.message_annotations_mut();
+                annotations.insert(
+                    Symbol::with_ascii(TRACEPARENT),
+                    self.traceparent.to_string(),
+                );
+                match &self.tracestate {
+                    Some(state) => {
+                        annotations.insert(Symbol::with_ascii(TRACESTATE), state.as_str())
+                    }
+                    None => annotations.remove(TRACESTATE),
+                };
+            }
+            Carrier::ApplicationProperties => {
+                let properties = message.application_properties_mut();
+                properties
+                    .insert(TRACEPARENT, self.traceparent.to_string())
+                    .expect("strings are valid application property values");
+                match &self.tracestate {
+                    Some(state) => properties
+                        .insert(TRACESTATE, state.as_str())
+                        .expect("strings are valid application property values"),
+                    None => properties.remove(TRACESTATE),
+                };
+            }
+        }
+    }
+
+    /// Reads the trace context from the message annotations, falling back to the application properties.
+    /// A malformed `traceparent` is treated as absent, and so is the `tracestate` that came with it.
+    pub fn extract(message: &Message) -> Option<Self> {
+        let from_annotations = message
+            .message_annotations()
+            .and_then(|a| Self::from_fields(a.get(TRACEPARENT), a.get(TRACESTATE)));
+        from_annotations.or_else(|| {
+            message
+                .application_properties()
+                .and_then(|p| Self::from_fields(p.get(TRACEPARENT), p.get(TRACESTATE)))
+        })
+    }
+
+    fn from_fields(
+        traceparent: Option<&Primitive>,
+        tracestate: Option<&Primitive>,
+    ) -> Option<Self> {
+        let traceparent = match traceparent {
+            Some(Primitive::String(value)) => value.parse().ok()?,
+            _ => return None,
+        };
+        let tracestate = match tracestate {
+            Some(Primitive::String(value)) if !value.is_empty() => Some(value.clone()),
+            _ => None,
+        };
+        Some(TraceContext {
+            traceparent,
+            tracestate,
+        })
+    }
+}
+
+type CurrentContext = Box<dyn Fn() -> Option<TraceContext> + Send + Sync>;
+type ContextHandler = Box<dyn Fn(&Message, TraceContext) + Send + Sync>;
+
+/// # Trace Context Interceptor
+/// Propagates the trace context across a link without code at each send and receive.
+///
+/// On send, the context of the active span is injected into the outgoing message. On receive,
+/// the extracted context is handed to a callback, typically to start a consumer span linked to
+/// the producer. Both hooks are closures so the interceptor works with any tracing library.
+pub struct TraceContextInterceptor {
+    carrier: Carrier,
+    current: CurrentContext,
+    on_extract: ContextHandler,
+}
+
+impl TraceContextInterceptor {
+    pub fn new<C, H>(current: C, on_extract: H) -> Self
+    where
+        C: Fn() -> Option<TraceContext> + Send + Sync + 'static,
+        H: Fn(&Message, TraceContext) + Send + Sync + 'static,
+    {
+        TraceContextInterceptor {
+            carrier: Carrier::default(),
+            current: Box::new(current),
+            on_extract: Box::new(on_extract),
+        }
+    }
+
+    pub fn with_carrier(mut self, carrier: Carrier) -> Self {
+        self.carrier = carrier;
+        self
+    }
+}
+
+impl Interceptor for TraceContextInterceptor {
+    fn on_send(&self, message: &mut Message) {
+        if let Some(context) = (self.current)() {
+            context.inject(message, self.carrier);
+        }
+    }
+
+    fn on_receive(&self, message: &mut Message) {
+        if let Some(context) = TraceContext::extract(message) {
+            (self.on_extract)(message, context);
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_hex<const N: usize>(field: &str) -> Result<[u8; N], ()> {
+    let valid = |c: u8| c.is_ascii_digit() || (b'a'..=b'f').contains(&c);
+    if field.len() != N * 2 || !field.bytes().all(valid) {
+        return Err(());
+    }
+    let mut result = [0; N];
+    for (i, byte) in result.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&field[i * 2..i * 2 + 2], 16).map_err(|_| ())?;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interceptor::Interceptors;
+    use std::sync::{Arc, Mutex};
+
+    const EXAMPLE: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    fn context() -> TraceContext {
+        TraceContext::new(EXAMPLE.parse().unwrap()).with_tracestate("congo=t61rcWkgMzE".to_string())
+    }
+
+    fn transmit(message: Message) -> Message {
+        let encoded = message.encode();
+        Message::try_decode(&mut encoded.into_iter()).unwrap()
+    }
+
+    #[test]
+    fn test_traceparent_round_trip() {
+        let traceparent: TraceParent = EXAMPLE.parse().unwrap();
+        assert_eq!(
+            traceparent.parent_id(),
+            [0x00, 0xf0, 0x67, 0xaa, 0x0b, 0xa9, 0x02, 0xb7]
+        );
+        assert!(traceparent.sampled());
+        assert_eq!(traceparent.to_string(), EXAMPLE);
+    }
+
+    #[test]
+    fn test_traceparent_rejects_invalid_values() {
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+        ] {
+            assert!(invalid.parse::<TraceParent>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_traceparent_accepts_future_versions() {
+        let future =
+            "cc-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-what-the-future-holds";
+        assert!(future.parse::<TraceParent>().is_ok());
+    }
+
+    #[test]
+    fn test_inject_extract_message_annotations() {
+        let mut message = Message::from_value("hello");
+        context().inject(&mut message, Carrier::MessageAnnotations);
+        let received = transmit(message);
+        assert!(received.application_properties().is_none());
+        assert_eq!(TraceContext::extract(&received), Some(context()));
+    }
+
+    #[test]
+    fn test_inject_extract_application_properties() {
+        let mut message = Message::from_value("hello");
+        context().inject(&mut message, Carrier::ApplicationProperties);
+        let received = transmit(message);
+        assert!(received.message_annotations().is_none());
+        assert_eq!(TraceContext::extract(&received), Some(context()));
+    }
+
+    #[test]
+    fn test_extract_prefers_message_annotations() {
+        let mut message = Message::from_value("hello");
+        context().inject(&mut message, Carrier::ApplicationProperties);
+        let broker_span = TraceContext::new(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-b7ad6b7169203331-00"
+                .parse()
+                .unwrap(),
+        );
+        broker_span.inject(&mut message, Carrier::MessageAnnotations);
+        assert_eq!(TraceContext::extract(&message), Some(broker_span));
+    }
+
+    #[test]
+    fn test_extract_ignores_malformed_traceparent() {
+        let mut message = Message::from_value("hello");
+        let annotations = message.message_annotations_mut();
+        annotations.insert(Symbol::with_ascii(TRACEPARENT), "not-a-traceparent");
+        annotations.insert(Symbol::with_ascii(TRACESTATE), "congo=t61rcWkgMzE");
+        assert_eq!(TraceContext::extract(&message), None);
+    }
+
+    #[test]
+    fn test_inject_replaces_stale_tracestate() {
+        let mut message = Message::from_value("hello");
+        context().inject(&mut message, Carrier::MessageAnnotations);
+        TraceContext::new(EXAMPLE.parse().unwrap())
+            .inject(&mut message, Carrier::MessageAnnotations);
+        assert_eq!(TraceContext::extract(&message).unwrap().tracestate(), None);
+    }
+
+    #[test]
+    fn test_interceptor_links_producer_and_consumer() {
+        let extracted = Arc::new(Mutex::new(None));
+        let sink = extracted.clone();
+        let interceptors = Interceptors::new().with(TraceContextInterceptor::new(
+            || Some(context()),
+            move |_, context| *sink.lock().unwrap() = Some(context),
+        ));
+        let mut message = Message::from_value("hello");
+        interceptors.on_send(&mut message);
+        let mut received = transmit(message);
+        interceptors.on_receive(&mut received);
+        assert_eq!(*extracted.lock().unwrap(), Some(context()));
+    }
+
+    #[test]
+    fn test_interceptor_without_active_span_sends_plain_message() {
+        let interceptor =
+            TraceContextInterceptor::new(|| None, |_, _| panic!("no context expected"))
+                .with_carrier(Carrier::ApplicationProperties);
+        let mut message = Message::from_value("hello");
+        interceptor.on_send(&mut message);
+        interceptor.on_receive(&mut message);
+        assert_eq!(message, Message::from_value("hello"));
+    }
+}
@@ -0,0 +1,367 @@
+use aes_gcm::aead::{Aead, AeadCore, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use amqp_messaging::message::{Body, Message};
+use amqp_type::error::AppError;
+use amqp_type::primitive::variable_width::binary::Binary;
+use amqp_type::primitive::variable_width::symbol::Symbol;
+use amqp_type::primitive::Primitive;
+use std::fmt::{Display, Formatter};
+
+/// Message annotation holding the id of the key the body was encrypted with.
+pub const KEY_ID_ANNOTATION: &str = "x-opt-encryption-key-id";
+/// Message annotation holding the algorithm the body was encrypted with.
+pub const ALGORITHM_ANNOTATION: &str = "x-opt-encryption-algorithm";
+/// AES-256 in Galois/Counter Mode, named as in RFC 7518.
+pub const AES_256_GCM: &str = "A256GCM";
+
+const NONCE_SIZE: usize = 12;
+
+#[derive(Debug)]
+pub enum EncryptionError {
+    /// The key provider has no key for encrypting outgoing messages.
+    NoEncryptionKey,
+    /// The key provider does not know the key the message was encrypted with.
+    UnknownKey(String),
+    /// The key has the wrong length for the algorithm.
+    InvalidKey,
+    /// The message does not carry the annotations of an encrypted message.
+    NotEncrypted,
+    /// The message was encrypted with an algorithm that is not supported.
+    UnsupportedAlgorithm(String),
+    /// The body could not be encrypted, for example because it is too large for the algorithm.
+    EncryptionFailed,
+    /// The ciphertext could not be authenticated with the key.
+    DecryptionFailed,
+    /// The decrypted payload is not a valid message body.
+    Decode(AppError),
+}
+
+impl Display for EncryptionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncryptionError::NoEncryptionKey => write!(f, "no key available for encryption"),
+            EncryptionError::UnknownKey(id) => write!(f, "unknown encryption key: {}", id),
+            EncryptionError::InvalidKey => write!(f, "invalid encryption key length"),
+            EncryptionError::NotEncrypted => write!(f, "message body is not encrypted"),
+            EncryptionError::UnsupportedAlgorithm(alg) => {
+                write!(f, "unsupported encryption algorithm: {}", alg)
+            }
+            EncryptionError::EncryptionFailed => write!(f, "message body could not be encrypted"),
+            EncryptionError::DecryptionFailed => write!(f, "message body could not be decrypted"),
+            EncryptionError::Decode(e) => write!(f, "decrypted body could not be decoded: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for EncryptionError {}
+
+/// A symmetric key together with the id peers use to look it up.
+#[derive(Clone)]
+pub struct EncryptionKey {
+    id: String,
+    material: Vec<u8>,
+}
+
+impl EncryptionKey {
+    pub fn new(id: String, material: Vec<u8>) -> Self {
+        EncryptionKey { id, material }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// Supplies the keys used to encrypt and decrypt message bodies.
+/// Implemented by the application, for example on top of a key management service.
+pub trait KeyProvider {
+    /// The key new messages are encrypted with.
+    fn current_key(&self) -> Option<EncryptionKey>;
+
+    /// Looks up a key by the id recorded in a received message.
+    fn key(&self, id: &str) -> Option<EncryptionKey>;
+}
+
+/// # Message Encryptor
+/// Envelope encryption of message bodies with AES-256-GCM.
+///
+/// Only the body sections are encrypted; they are replaced by a single data section holding
+/// the nonce followed by the ciphertext. The header, properties and application-properties
+/// stay readable so brokers can still route the message. The key id and algorithm are recorded
+/// in the message annotations and authenticated together with the ciphertext.
+pub struct MessageEncryptor<P: KeyProvider> {
+    provider: P,
+}
+
+impl<P: KeyProvider> MessageEncryptor<P> {
+    pub fn new(provider: P) -> Self {
+        MessageEncryptor { provider }
+    }
+
+    pub fn encrypt(&self, message: &mut Message) -> Result<(), EncryptionError> {
+        let key = self
+            .provider
+            .current_key()
+            .ok_or(EncryptionError::NoEncryptionKey)?;
+        let cipher = cipher(&key)?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let plaintext = message.body().clone().encode();
+        let aad = associated_data(key.id(), AES_256_GCM);
+        let mut ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &plaintext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| EncryptionError::EncryptionFailed)?;
+
+        let mut payload = nonce.to_vec();
+        payload.append(&mut ciphertext);
+        message.set_body(Body::Data(vec![Binary::from(payload)]));
+        let annotations = message.message_annotations_mut();
+        annotations.insert(Symbol::with_ascii(KEY_ID_ANNOTATION), key.id());
+        annotations.insert(
+            Symbol::with_ascii(ALGORITHM_ANNOTATION),
+            Symbol::with_ascii(AES_256_GCM),
+        );
+        Ok(())
+    }
+
+    /// Restores the original body sections of an encrypted message and removes the encryption annotations.
+    pub fn decrypt(&self, message: &mut Message) -> Result<(), EncryptionError> {
+        let (key_id, algorithm) = encryption_annotations(message)?;
+        if algorithm != AES_256_GCM {
+            Err(EncryptionError::UnsupportedAlgorithm(algorithm.clone()))?
+        }
+        let key = self
+            .provider
+            .key(&key_id)
+            .ok_or(EncryptionError::UnknownKey(key_id.clone()))?;
+        let payload = match message.body() {
+            Body::Data(data) if data.len() == 1 => Vec::from(data[0].clone()),
+            _ => Err(EncryptionError::NotEncrypted)?,
+        };
+        if payload.len() < NONCE_SIZE {
+            Err(EncryptionError::DecryptionFailed)?
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_SIZE);
+        let aad = associated_data(&key_id, &algorithm);
+        let plaintext = cipher(&key)?
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| EncryptionError::DecryptionFailed)?;
+        let body = Body::try_decode(&mut plaintext.into_iter()).map_err(EncryptionError::Decode)?;
+
+        message.set_body(body);
+        let annotations = message.message_annotations_mut();
+        annotations.remove(KEY_ID_ANNOTATION);
+        annotations.remove(ALGORITHM_ANNOTATION);
+        Ok(())
+    }
+}
+
+/// Whether the message carries the annotations of an encrypted body.
+pub fn is_encrypted(message: &Message) -> bool {
+    encryption_annotations(message).is_ok()
+}
+
+fn cipher(key: &EncryptionKey) -> Result<Aes256Gcm, EncryptionError> {
+    Aes256Gcm::new_from_slice(&key.material).map_err(|_| EncryptionError::InvalidKey)
+}
+
+fn associated_data(key_id: &str, algorithm: &str) -> Vec<u8> {
+    format!("{}:{}", algorithm, key_id).into_bytes()
+}
+
+fn encryption_annotations(message: &Message) -> Result<(String, String), EncryptionError> {
+    let annotations = message
+        .message_annotations()
+        .ok_or(EncryptionError::NotEncrypted)?;
+    match (
+        annotations.get(KEY_ID_ANNOTATION),
+        annotations.get(ALGORITHM_ANNOTATION),
+    ) {
+        (Some(Primitive::String(key_id)), Some(Primitive::Symbol(algorithm))) => {
+            Ok((key_id.clone(), algorithm.inner().to_string()))
+        }
+        _ => Err(EncryptionError::NotEncrypted),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amqp_type::composite::messaging::header::Header;
+    use amqp_type::composite::messaging::properties::Properties;
+    use amqp_type::primitive::compound::list::List;
+    use std::collections::HashMap;
+
+    struct StaticKeys {
+        current: &'static str,
+        keys: HashMap<&'static str, Vec<u8>>,
+    }
+
+    impl StaticKeys {
+        fn new() -> Self {
+            let mut keys = HashMap::new();
+            keys.insert("key-1", vec![1; 32]);
+            keys.insert("key-2", vec![2; 32]);
+            StaticKeys {
+                current: "key-1",
+                keys,
+            }
+        }
+    }
+
+    impl KeyProvider for StaticKeys {
+        fn current_key(&self) -> Option<EncryptionKey> {
+            self.key(self.current)
+        }
+
+        fn key(&self, id: &str) -> Option<EncryptionKey> {
+            self.keys
+                .get(id)
+                .map(|material| EncryptionKey::new(id.to_string(), material.clone()))
+        }
+    }
+
+    fn transmit(message: Message) -> Message {
+        let encoded = message.encode();
+        Message::try_decode(&mut encoded.into_iter()).unwrap()
+    }
+
+    #[test]
+    fn test_data_body_round_trip() {
+        let encryptor = MessageEncryptor::new(StaticKeys::new());
+        let original = Message::new(Body::Data(vec![
+            Binary::from(b"secret".to_vec()),
+            Binary::from(vec![1, 2]),
+        ]));
+        let mut message = original.clone();
+        encryptor.encrypt(&mut message).unwrap();
+        assert_ne!(message.body(), original.body());
+        assert!(is_encrypted(&message));
+
+        let mut received = transmit(message);
+        encryptor.decrypt(&mut received).unwrap();
+        assert_eq!(received.body(), original.body());
+        assert!(!is_encrypted(&received));
+    }
+
+    #[test]
+    fn test_value_and_sequence_bodies_round_trip() {
+        let encryptor = MessageEncryptor::new(StaticKeys::new());
+        for body in [
+            Body::Value(Primitive::String("secret".to_string())),
+            Body::Sequence(vec![List::from(vec![1, 2, 3])]),
+        ] {
+            let mut message = Message::new(body.clone());
+            encryptor.encrypt(&mut message).unwrap();
+            let mut received = transmit(message);
+            encryptor.decrypt(&mut received).unwrap();
+            assert_eq!(received.body(), &body);
+        }
+    }
+
+    #[test]
+    fn test_routing_sections_stay_readable() {
+        let encryptor = MessageEncryptor::new(StaticKeys::new());
+        let properties = Properties::default().with_to("queue://orders".to_string());
+        let mut message = Message::from_value("secret")
+            .with_header(Header::default().with_durable(true))
+            .with_properties(properties.clone());
+        message
+            .application_properties_mut()
+            .insert("region", "eu")
+            .unwrap();
+        encryptor.encrypt(&mut message).unwrap();
+
+        let received = transmit(message);
+        assert_eq!(received.properties(), Some(&properties));
+        assert!(received.header().unwrap().durable());
+        assert_eq!(
+            received.application_properties().unwrap().get("region"),
+            Some(&Primitive::String("eu".to_string()))
+        );
+        assert_eq!(
+            received
+                .message_annotations()
+                .unwrap()
+                .get(KEY_ID_ANNOTATION),
+            Some(&Primitive::String("key-1".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_decrypt_uses_recorded_key_after_rotation() {
+        let mut keys = StaticKeys::new();
+        let mut message = Message::from_value("secret");
+        MessageEncryptor::new(StaticKeys::new())
+            .encrypt(&mut message)
+            .unwrap();
+        keys.current = "key-2";
+        MessageEncryptor::new(keys).decrypt(&mut message).unwrap();
+        assert_eq!(
+            message.body(),
+            &Body::Value(Primitive::String("secret".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_decrypt_with_unknown_key_fails() {
+        let mut message = Message::from_value("secret");
+        MessageEncryptor::new(StaticKeys::new())
+            .encrypt(&mut message)
+            .unwrap();
+        let mut keys = StaticKeys::new();
+        keys.keys.remove("key-1");
+        assert!(matches!(
+            MessageEncryptor::new(keys).decrypt(&mut message),
+            Err(EncryptionError::UnknownKey(id)) if id == "key-1"
+        ));
+    }
+
+    #[test]
+    fn test_tampered_ciphertext_fails() {
+        let encryptor = MessageEncryptor::new(StaticKeys::new());
+        let mut message = Message::from_value("secret");
+        encryptor.encrypt(&mut message).unwrap();
+        let mut payload = match message.body() {
+            Body::Data(data) => Vec::from(data[0].clone()),
+            _ => unreachable!(),
+        };
+        let last = payload.len() - 1;
+        payload[last] ^= 1;
+        message.set_body(Body::Data(vec![Binary::from(payload)]));
+        assert!(matches!(
+            encryptor.decrypt(&mut message),
+            Err(EncryptionError::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    fn test_decrypt_plain_message_fails() {
+        let encryptor = MessageEncryptor::new(StaticKeys::new());
+        assert!(matches!(
+            encryptor.decrypt(&mut Message::from_value("plain")),
+            Err(EncryptionError::NotEncrypted)
+        ));
+    }
+
+    #[test]
+    fn test_invalid_key_length_fails() {
+        let mut keys = StaticKeys::new();
+        keys.keys.insert("key-1", vec![1; 16]);
+        assert!(matches!(
+            MessageEncryptor::new(keys).encrypt(&mut Message::from_value("secret")),
+            Err(EncryptionError::InvalidKey)
+        ));
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::BTreeSet;
+
+/// # Number Allocator
+/// Hands out the lowest free number up to a maximum, as used for the channels of a connection
+/// (bounded by the negotiated `channel-max`) and the handles of a session (bounded by the
+/// negotiated `handle-max`).
+///
+/// A number stays in use until it is released, which the connection and session do only once
+/// both ends have ended the session or detached the link. A number is therefore never reused
+/// while the end or detach of its previous user is still pending.
+///
+/// ```
+///# use amqp_transport::allocator::Allocator;
+/// let mut channels = Allocator::new(1);
+/// assert_eq!(channels.allocate(), Some(0));
+/// assert_eq!(channels.allocate(), Some(1));
+/// assert_eq!(channels.allocate(), None);
+/// channels.release(0);
+/// assert_eq!(channels.allocate(), Some(0));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Allocator {
+    max: u32,
+    in_use: BTreeSet<u32>,
+}
+
+impl Allocator {
+    pub fn new(max: u32) -> Self {
+        Allocator {
+            max,
+            in_use: BTreeSet::new(),
+        }
+    }
+
+    pub fn max(&self) -> u32 {
+        self.max
+    }
+
+    /// Lowers or raises the maximum, e.g. once the peer's limit is known.
+    /// Numbers already in use stay in use.
+    pub fn set_max(&mut self, max: u32) {
+        self.max = max;
+    }
+
+    pub fn is_in_use(&self, number: u32) -> bool {
+        self.in_use.contains(&number)
+    }
+
+    pub fn len(&self) -> usize {
+        self.in_use.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.in_use.is_empty()
+    }
+
+    /// Takes the lowest free number, or `None` if every number up to the maximum is in use.
+    pub fn allocate(&mut self) -> Option<u32> {
+        let mut candidate = 0;
+        for number in &self.in_use {
+            if *number != candidate {
+                break;
+            }
+            candidate = candidate.checked_add(1)?;
+        }
+        if candidate > self.max {
+            return None;
+        }
+        self.in_use.insert(candidate);
+        Some(candidate)
+    }
+
+    pub fn release(&mut self, number: u32) {
+        self.in_use.remove(&number);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocates_lowest_free_number() {
+        let mut allocator = Allocator::new(10);
+        assert_eq!(allocator.allocate(), Some(0));
+        assert_eq!(allocator.allocate(), Some(1));
+        assert_eq!(allocator.allocate(), Some(2));
+        allocator.release(1);
+        assert!(!allocator.is_in_use(1));
+        assert_eq!(allocator.allocate(), Some(1));
+        assert_eq!(allocator.allocate(), Some(3));
+        assert_eq!(allocator.len(), 4);
+    }
+
+    #[test]
+    fn test_respects_max() {
+        let mut allocator = Allocator::new(0);
+        assert_eq!(allocator.allocate(), Some(0));
+        assert_eq!(allocator.allocate(), None);
+        allocator.set_max(1);
+        assert_eq!(allocator.allocate(), Some(1));
+    }
+
+    #[test]
+    fn test_lowered_max_keeps_numbers_in_use() {
+        let mut allocator = Allocator::new(5);
+        (0..4).for_each(|_| {
+            allocator.allocate();
+        });
+        allocator.set_max(1);
+        assert!(allocator.is_in_use(3));
+        allocator.release(0);
+        assert_eq!(allocator.allocate(), Some(0));
+        assert_eq!(allocator.allocate(), None);
+    }
+
+    #[test]
+    fn test_full_range() {
+        let mut allocator = Allocator::new(u32::MAX);
+        allocator.allocate();
+        assert_eq!(allocator.allocate(), Some(1));
+    }
+}
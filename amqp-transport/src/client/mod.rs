@@ -0,0 +1,499 @@
+pub mod receiver;
+pub mod sender;
+pub mod session;
+
+use crate::client::session::Session;
+use crate::connection::ConnectionEndpoint;
+use crate::driver::{Command, Driver, Reply};
+use crate::error::TransportError;
+use crate::session::DEFAULT_SESSION_WINDOW;
+use crate::tls::ClientTls;
+use crate::websocket;
+use amqp_messaging::interceptor::{Interceptor, Interceptors};
+use amqp_type::composite::transport::frame::performatives::begin::Begin;
+use amqp_type::composite::transport::frame::performatives::open::Open;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::sync::{mpsc, oneshot};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::error::{Error as WebSocketError, UrlError};
+
+/// The largest frame a client accepts unless configured otherwise.
+pub const DEFAULT_MAX_FRAME_SIZE: u32 = 64 * 1024;
+
+/// # Connection Options
+/// The fields of the open frame a client sends, and the interceptors that run on every link
+/// of the connection.
+pub struct ConnectionOptions {
+    open: Open,
+    interceptors: Interceptors,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        ConnectionOptions::new(uuid::Uuid::new_v4().to_string())
+    }
+}
+
+impl ConnectionOptions {
+    pub fn new(container_id: impl Into<String>) -> Self {
+        ConnectionOptions {
+            open: Open::new(container_id.into()).with_max_frame_size(DEFAULT_MAX_FRAME_SIZE),
+            interceptors: Interceptors::new(),
+        }
+    }
+
+    pub fn with_host_name(mut self, host_name: impl Into<String>) -> Self {
+        self.open = self.open.with_host_name(host_name.into());
+        self
+    }
+
+    pub fn with_max_frame_size(mut self, max_frame_size: u32) -> Self {
+        self.open = self.open.with_max_frame_size(max_frame_size);
+        self
+    }
+
+    pub fn with_channel_max(mut self, channel_max: u16) -> Self {
+        self.open = self.open.with_channel_max(channel_max);
+        self
+    }
+
+    /// Closes the connection if the peer sends nothing for this long.
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.open = self.open.with_idle_timeout(idle_timeout.as_millis() as u32);
+        self
+    }
+
+    pub fn with_interceptor<I: Interceptor + 'static>(mut self, interceptor: I) -> Self {
+        self.interceptors.push(interceptor);
+        self
+    }
+
+    pub fn open(&self) -> &Open {
+        &self.open
+    }
+
+    pub(crate) fn into_parts(self) -> (Open, Interceptors) {
+        (self.open, self.interceptors)
+    }
+}
+
+/// # Connection
+/// A client connection. A single task owns the socket and runs the connection, session and
+/// link state machines; the connection and the [`Session`]s, senders and receivers created from
+/// it are handles that send their requests to that task.
+///
+/// ```no_run
+///# use amqp_transport::client::{Connection, ConnectionOptions};
+///# use amqp_messaging::message::Message;
+///# async fn example() -> Result<(), amqp_transport::error::TransportError> {
+/// let connection = Connection::open("localhost:5672", ConnectionOptions::default()).await?;
+/// let session = connection.begin_session().await?;
+/// let sender = session.attach_sender("queue").await?;
+/// let outcome = sender.send(Message::from_value("hello")).await?;
+/// connection.close().await
+///# }
+/// ```
+///
+/// The connection is closed once [`Connection::close`] is called or all handles are dropped.
+pub struct Connection {
+    commands: mpsc::UnboundedSender<Command>,
+    interceptors: Arc<Interceptors>,
+    remote_open: Open,
+}
+
+impl Connection {
+    /// Connects to `addr` over TCP and opens the connection.
+    pub async fn open(
+        addr: impl ToSocketAddrs,
+        options: ConnectionOptions,
+    ) -> Result<Self, TransportError> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        Connection::open_stream(stream, options).await
+    }
+
+    /// Connects to `addr` over TCP, starts TLS as configured by `tls` and opens the connection.
+    /// The server must present a certificate for `server_name`.
+    pub async fn open_tls(
+        addr: impl ToSocketAddrs,
+        server_name: &str,
+        tls: &ClientTls,
+        options: ConnectionOptions,
+    ) -> Result<Self, TransportError> {
+        let stream = TcpStream::connect(addr).await?;
+        stream.set_nodelay(true)?;
+        let stream = tls.connect(server_name, stream).await?;
+        Connection::open_stream(stream, options).await
+    }
+
+    /// Connects over TCP to the WebSocket endpoint at `url` (`ws://host:port/path`) and opens
+    /// the connection over it. For a secure WebSocket, start TLS with [`ClientTls::connect`]
+    /// and hand the stream to [`websocket::connect`].
+    pub async fn open_websocket(
+        url: &str,
+        options: ConnectionOptions,
+    ) -> Result<Self, TransportError> {
+        let request = url.into_client_request()?;
+        let uri = request.uri();
+        if uri.scheme_str() != Some("ws") {
+            Err(WebSocketError::Url(UrlError::UnsupportedUrlScheme))?
+        }
+        let host = uri
+            .host()
+            .ok_or(WebSocketError::Url(UrlError::NoHostName))?;
+        let stream = TcpStream::connect((host, uri.port_u16().unwrap_or(80))).await?;
+        stream.set_nodelay(true)?;
+        let stream = websocket::connect(url, stream).await?;
+        Connection::open_stream(stream, options).await
+    }
+
+    /// Opens the connection over a stream that is already connected to the peer.
+    pub async fn open_stream<S>(
+        stream: S,
+        options: ConnectionOptions,
+    ) -> Result<Self, TransportError>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (commands, receiver) = mpsc::unbounded_channel();
+        let (opened, remote_open) = oneshot::channel();
+        let (open, interceptors) = options.into_parts();
+        tokio::spawn(
+            Driver::new(stream, ConnectionEndpoint::new(open), receiver).run_client(opened),
+        );
+        let remote_open = remote_open
+            .await
+            .map_err(|_| TransportError::Closed(None))??;
+        Ok(Connection {
+            commands,
+            interceptors: Arc::new(interceptors),
+            remote_open,
+        })
+    }
+
+    /// The open frame the peer answered with.
+    pub fn remote_open(&self) -> &Open {
+        &self.remote_open
+    }
+
+    pub async fn begin_session(&self) -> Result<Session, TransportError> {
+        let begin = Begin::new(0.into(), DEFAULT_SESSION_WINDOW, DEFAULT_SESSION_WINDOW);
+        let channel = request(&self.commands, |reply| Command::Begin { begin, reply }).await?;
+        Ok(Session::new(
+            channel,
+            self.commands.clone(),
+            self.interceptors.clone(),
+        ))
+    }
+
+    /// Closes the connection and waits for the peer to close its side.
+    pub async fn close(self) -> Result<(), TransportError> {
+        request(&self.commands, |reply| Command::Close { reply }).await
+    }
+}
+
+/// Sends a command to the driver and waits for its reply.
+pub(crate) async fn request<T>(
+    commands: &mpsc::UnboundedSender<Command>,
+    command: impl FnOnce(Reply<T>) -> Command,
+) -> Result<T, TransportError> {
+    let (reply, result) = oneshot::channel();
+    commands
+        .send(command(reply))
+        .map_err(|_| TransportError::Closed(None))?;
+    result.await.map_err(|_| TransportError::Closed(None))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::{ConnectionEvent, ConnectionState, Transmit};
+    use crate::frame::codec::FrameCodec;
+    use crate::link::LinkEvent;
+    use crate::memory::MemoryTransport;
+    use crate::protocol_header::{NegotiationError, ProtocolHeader};
+    use crate::session::SessionEvent;
+    use amqp_messaging::message::Message;
+    use amqp_type::composite::messaging::delivery_state::accepted::Accepted;
+    use amqp_type::composite::messaging::delivery_state::rejected::Rejected;
+    use amqp_type::composite::messaging::delivery_state::released::Released;
+    use amqp_type::composite::messaging::delivery_state::DeliveryState;
+    use amqp_type::composite::transport::frame::performatives::transfer::Transfer;
+    use amqp_type::restricted::delivery_tag::DeliveryTag;
+    use amqp_type::restricted::role::Role;
+    use bytes::BytesMut;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_stream::StreamExt;
+    use tokio_util::codec::{Encoder, FramedRead};
+
+    /// What the peer got from the client: the messages it received and the outcomes of the
+    /// messages it sent.
+    #[derive(Debug, Default)]
+    struct Served {
+        received: Vec<Message>,
+        outcomes: Vec<Option<DeliveryState>>,
+    }
+
+    /// A peer that goes along with whatever the client starts. It grants credit to the client's
+    /// senders and accepts their deliveries, and sends `outgoing` to the client's receivers.
+    async fn serve<S: AsyncRead + AsyncWrite>(stream: S, mut outgoing: Vec<Message>) -> Served {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut frames = FramedRead::new(
+            reader,
+            FrameCodec::new().with_max_frame_size(DEFAULT_MAX_FRAME_SIZE),
+        );
+        let mut codec = FrameCodec::new().with_max_frame_size(DEFAULT_MAX_FRAME_SIZE);
+        let open = Open::new("peer".to_string()).with_max_frame_size(DEFAULT_MAX_FRAME_SIZE);
+        let mut endpoint = ConnectionEndpoint::new(open);
+        let mut header = [0; ProtocolHeader::SIZE];
+        frames.get_mut().read_exact(&mut header).await.unwrap();
+        endpoint.on_header().unwrap();
+        endpoint.send_header().unwrap();
+
+        let mut served = Served::default();
+        let mut tags = 0_u8..;
+        loop {
+            while let Some(event) = endpoint.poll_event() {
+                match event {
+                    ConnectionEvent::Opened(_) => endpoint.send_open().unwrap(),
+                    ConnectionEvent::Closed(_) => endpoint.send_close(None).unwrap(),
+                    ConnectionEvent::Session(channel, event) => {
+                        let session = endpoint.session_mut(channel).unwrap();
+                        let (handle, event) = match event {
+                            SessionEvent::Begun(_) => {
+                                session.send_begin().unwrap();
+                                continue;
+                            }
+                            SessionEvent::Ended(_) => {
+                                session.send_end(None).unwrap();
+                                continue;
+                            }
+                            SessionEvent::Link(handle, event) => (handle, event),
+                        };
+                        let Some(link) = session.link_mut(handle) else {
+                            continue;
+                        };
+                        match event {
+                            LinkEvent::Attached(_) => {
+                                link.send_attach().unwrap();
+                                if link.role() == Role::Receiver {
+                                    link.grant_credit(10).unwrap();
+                                }
+                            }
+                            LinkEvent::Flow(_) if link.role() == Role::Sender => {
+                                while link.link_credit() > 0 && !outgoing.is_empty() {
+                                    let tag = DeliveryTag::new(vec![tags.next().unwrap()]).unwrap();
+                                    let transfer = Transfer::new(handle)
+                                        .with_delivery_tag(tag)
+                                        .with_message_format(0);
+                                    link.send_message(transfer, outgoing.remove(0).encode())
+                                        .unwrap();
+                                }
+                            }
+                            LinkEvent::Delivery(transfer, payload) => {
+                                served
+                                    .received
+                                    .push(Message::try_decode(&mut payload.into_iter()).unwrap());
+                                let tag = transfer.delivery_tag().unwrap();
+                                link.dispose(tag, DeliveryState::Accepted(Accepted {}))
+                                    .unwrap();
+                            }
+                            LinkEvent::Settled { state, .. } => served.outcomes.push(state),
+                            LinkEvent::Detached { closed, .. } => {
+                                link.send_detach(closed, None).unwrap()
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            let mut buffer = BytesMut::new();
+            while let Some(transmit) = endpoint.poll_transmit() {
+                match transmit {
+                    Transmit::Header(header) => buffer.extend_from_slice(&header.encode()),
+                    Transmit::Frame(frame) => codec.encode(frame, &mut buffer).unwrap(),
+                }
+            }
+            writer.write_all(&buffer).await.unwrap();
+            if endpoint.state() == ConnectionState::End {
+                return served;
+            }
+            match frames.next().await {
+                Some(frame) => endpoint.on_frame(frame.unwrap()).unwrap(),
+                None => return served,
+            }
+        }
+    }
+
+    fn accepted() -> Option<DeliveryState> {
+        Some(DeliveryState::Accepted(Accepted {}))
+    }
+
+    #[derive(Clone, Default)]
+    struct Counter(Arc<AtomicUsize>);
+
+    impl Interceptor for Counter {
+        fn on_send(&self, _message: &mut Message) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_resolves_with_outcome() {
+        let (client, server) = MemoryTransport::new().with_chunk_size(5).pair();
+        let peer = tokio::spawn(serve(server, vec![]));
+
+        let counter = Counter::default();
+        let options = ConnectionOptions::new("client").with_interceptor(counter.clone());
+        let connection = Connection::open_stream(client, options).await.unwrap();
+        assert_eq!(connection.remote_open().container_id(), "peer");
+        let session = connection.begin_session().await.unwrap();
+        let sender = session.attach_sender("queue").await.unwrap();
+        let (first, second) = tokio::join!(
+            sender.send(Message::from_value("first")),
+            sender.send(Message::from_value("second")),
+        );
+        assert_eq!(first.unwrap(), accepted());
+        assert_eq!(second.unwrap(), accepted());
+        assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+
+        sender.close().await.unwrap();
+        session.end().await.unwrap();
+        connection.close().await.unwrap();
+        let served = peer.await.unwrap();
+        assert_eq!(served.received.len(), 2);
+        assert!(served.received.contains(&Message::from_value("first")));
+    }
+
+    #[tokio::test]
+    async fn test_received_deliveries_report_outcomes() {
+        let (client, server) = MemoryTransport::new().pair();
+        let messages = vec![
+            Message::from_value("accept"),
+            Message::from_value("reject"),
+            Message::from_value("release"),
+        ];
+        let peer = tokio::spawn(serve(server, messages));
+
+        let connection = Connection::open_stream(client, ConnectionOptions::default())
+            .await
+            .unwrap();
+        let session = connection.begin_session().await.unwrap();
+        let mut receiver = session.attach_receiver("queue").await.unwrap();
+        let delivery = receiver.recv().await.unwrap();
+        assert_eq!(delivery.message(), &Message::from_value("accept"));
+        assert!(!delivery.settled());
+        delivery.accept().await.unwrap();
+        receiver.recv().await.unwrap().reject().await.unwrap();
+        receiver.recv().await.unwrap().release().await.unwrap();
+
+        receiver.close().await.unwrap();
+        connection.close().await.unwrap();
+        let served = peer.await.unwrap();
+        assert_eq!(
+            served.outcomes,
+            vec![
+                accepted(),
+                Some(DeliveryState::Rejected(Rejected {})),
+                Some(DeliveryState::Released(Released {})),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_closed_connection_fails_handles() {
+        let (client, server) = MemoryTransport::new().pair();
+        let peer = tokio::spawn(serve(server, vec![]));
+        let connection = Connection::open_stream(client, ConnectionOptions::default())
+            .await
+            .unwrap();
+        let session = connection.begin_session().await.unwrap();
+        let mut receiver = session.attach_receiver("queue").await.unwrap();
+        connection.close().await.unwrap();
+        peer.await.unwrap();
+
+        assert!(matches!(
+            receiver.recv().await,
+            Err(TransportError::Closed(None))
+        ));
+        assert!(matches!(
+            session.attach_sender("queue").await,
+            Err(TransportError::Closed(None))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_peer_speaking_another_protocol_fails_open() {
+        let (client, mut server) = MemoryTransport::new().pair();
+        tokio::spawn(async move {
+            server
+                .write_all(b"HTTP/1.1 400 Bad Request\r\n")
+                .await
+                .unwrap();
+            let mut sink = Vec::new();
+            let _ = server.read_to_end(&mut sink).await;
+        });
+        let result = Connection::open_stream(client, ConnectionOptions::default()).await;
+        assert!(matches!(result, Err(TransportError::Negotiation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_peer_speaking_another_version_fails_open() {
+        let (client, mut server) = MemoryTransport::new().pair();
+        tokio::spawn(async move {
+            let mut header = [0; ProtocolHeader::SIZE];
+            server.read_exact(&mut header).await.unwrap();
+            server.write_all(b"AMQP\x00\x00\x09\x01").await.unwrap();
+            let mut sink = Vec::new();
+            let _ = server.read_to_end(&mut sink).await;
+        });
+        let result = Connection::open_stream(client, ConnectionOptions::default()).await;
+        let Err(TransportError::Negotiation(NegotiationError::Rejected { supported, .. })) = result
+        else {
+            panic!("expected the version mismatch to fail the open");
+        };
+        assert_eq!(supported.version(), (0, 9, 1));
+    }
+
+    /// Hands out the numbers up to `count` on any sending link.
+    struct Counting {
+        next: u32,
+        count: u32,
+    }
+
+    impl crate::server::Handler for Counting {
+        fn on_credit(&mut self, _link: &crate::server::Link, credit: u32) -> Vec<Message> {
+            let end = self.count.min(self.next + credit);
+            let messages = (self.next..end).map(Message::from_value).collect();
+            self.next = end;
+            messages
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_outlasts_its_window() {
+        let count = 2 * crate::session::DEFAULT_SESSION_WINDOW + 100;
+        let handler = Counting { next: 0, count };
+        let (connection, served) = MemoryTransport::new()
+            .connect(
+                ConnectionOptions::default(),
+                ConnectionOptions::new("server"),
+                handler,
+            )
+            .await
+            .unwrap();
+        let session = connection.begin_session().await.unwrap();
+        let mut receiver = session.attach_receiver("numbers").await.unwrap();
+        for i in 0..count {
+            let delivery = receiver.recv().await.unwrap();
+            assert_eq!(delivery.message(), &Message::from_value(i));
+            delivery.accept().await.unwrap();
+        }
+        connection.close().await.unwrap();
+        served.closed().await.unwrap();
+    }
+}
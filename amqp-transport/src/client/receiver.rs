@@ -0,0 +1,142 @@
+use crate::client::request;
+use crate::driver::Command;
+use crate::error::TransportError;
+use amqp_messaging::interceptor::Interceptors;
+use amqp_messaging::message::Message;
+use amqp_type::composite::messaging::delivery_state::accepted::Accepted;
+use amqp_type::composite::messaging::delivery_state::modified::Modified;
+use amqp_type::composite::messaging::delivery_state::rejected::Rejected;
+use amqp_type::composite::messaging::delivery_state::released::Released;
+use amqp_type::composite::messaging::delivery_state::DeliveryState;
+use amqp_type::composite::transport::frame::performatives::transfer::Transfer;
+use amqp_type::error::amqp_error::AmqpError;
+use amqp_type::restricted::delivery_tag::DeliveryTag;
+use amqp_type::restricted::handle::Handle;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// # Receiver
+/// A handle to a link receiving messages from a node.
+///
+/// The receiver keeps the sender's credit topped up: every delivery taken with
+/// [`Receiver::recv`] lets the sender send one more.
+pub struct Receiver {
+    link: (u16, Handle),
+    deliveries: mpsc::UnboundedReceiver<Result<(Transfer, Vec<u8>), TransportError>>,
+    commands: mpsc::UnboundedSender<Command>,
+    interceptors: Arc<Interceptors>,
+}
+
+impl Receiver {
+    pub(crate) fn new(
+        link: (u16, Handle),
+        deliveries: mpsc::UnboundedReceiver<Result<(Transfer, Vec<u8>), TransportError>>,
+        commands: mpsc::UnboundedSender<Command>,
+        interceptors: Arc<Interceptors>,
+    ) -> Self {
+        Receiver {
+            link,
+            deliveries,
+            commands,
+            interceptors,
+        }
+    }
+
+    pub fn handle(&self) -> Handle {
+        self.link.1
+    }
+
+    /// Waits for the next delivery. Fails with [`TransportError::Closed`] once the link is gone.
+    pub async fn recv(&mut self) -> Result<Delivery, TransportError> {
+        let (transfer, payload) = self
+            .deliveries
+            .recv()
+            .await
+            .ok_or(TransportError::Closed(None))??;
+        let _ = self.commands.send(Command::Credit {
+            link: self.link,
+            credit: 1,
+        });
+        let mut message = Message::try_decode(&mut payload.into_iter())?;
+        self.interceptors.on_receive(&mut message);
+        Ok(Delivery {
+            link: self.link,
+            tag: transfer
+                .delivery_tag()
+                .cloned()
+                .ok_or(AmqpError::DecodeError)?,
+            settled: transfer.settled() == Some(true),
+            message,
+            commands: self.commands.clone(),
+        })
+    }
+
+    /// Closes the link and waits for the peer to close its side.
+    pub async fn close(self) -> Result<(), TransportError> {
+        let link = self.link;
+        request(&self.commands, |reply| Command::Detach { link, reply }).await
+    }
+}
+
+/// # Delivery
+/// A message that arrived on a [`Receiver`], to be accepted, rejected, released or modified.
+/// A delivery the sender already settled needs no outcome; reporting one does nothing.
+pub struct Delivery {
+    link: (u16, Handle),
+    tag: DeliveryTag,
+    settled: bool,
+    message: Message,
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+impl Delivery {
+    pub fn tag(&self) -> &DeliveryTag {
+        &self.tag
+    }
+
+    pub fn settled(&self) -> bool {
+        self.settled
+    }
+
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    pub fn into_message(self) -> Message {
+        self.message
+    }
+
+    /// The message was processed successfully.
+    pub async fn accept(&self) -> Result<(), TransportError> {
+        self.dispose(DeliveryState::Accepted(Accepted {})).await
+    }
+
+    /// The message is invalid and cannot be processed.
+    pub async fn reject(&self) -> Result<(), TransportError> {
+        self.dispose(DeliveryState::Rejected(Rejected {})).await
+    }
+
+    /// The message was not processed and may be delivered again.
+    pub async fn release(&self) -> Result<(), TransportError> {
+        self.dispose(DeliveryState::Released(Released {})).await
+    }
+
+    /// The message was not processed, and the sender should deliver it elsewhere or later.
+    pub async fn modify(&self) -> Result<(), TransportError> {
+        self.dispose(DeliveryState::Modified(Modified {})).await
+    }
+
+    async fn dispose(&self, state: DeliveryState) -> Result<(), TransportError> {
+        if self.settled {
+            return Ok(());
+        }
+        let (link, tag) = (self.link, self.tag.clone());
+        request(&self.commands, |reply| Command::Dispose {
+            link,
+            tag,
+            state,
+            reply,
+        })
+        .await
+    }
+}
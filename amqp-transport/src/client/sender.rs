@@ -0,0 +1,72 @@
+use crate::client::request;
+use crate::driver::Command;
+use crate::error::TransportError;
+use amqp_messaging::interceptor::Interceptors;
+use amqp_messaging::message::Message;
+use amqp_type::composite::messaging::delivery_state::DeliveryState;
+use amqp_type::composite::transport::frame::performatives::transfer::Transfer;
+use amqp_type::restricted::delivery_tag::DeliveryTag;
+use amqp_type::restricted::handle::Handle;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// # Sender
+/// A handle to a link sending messages to a node.
+///
+/// Messages wait for credit from the receiver before they go out. Several sends may be in
+/// flight at once; each resolves with the outcome of its delivery.
+pub struct Sender {
+    link: (u16, Handle),
+    commands: mpsc::UnboundedSender<Command>,
+    interceptors: Arc<Interceptors>,
+    next_tag: AtomicU64,
+}
+
+impl Sender {
+    pub(crate) fn new(
+        link: (u16, Handle),
+        commands: mpsc::UnboundedSender<Command>,
+        interceptors: Arc<Interceptors>,
+    ) -> Self {
+        Sender {
+            link,
+            commands,
+            interceptors,
+            next_tag: AtomicU64::new(0),
+        }
+    }
+
+    pub fn handle(&self) -> Handle {
+        self.link.1
+    }
+
+    /// Sends a message unsettled and waits for the receiver to settle it. Resolves with the
+    /// outcome the receiver reported, if any.
+    pub async fn send(
+        &self,
+        mut message: Message,
+    ) -> Result<Option<DeliveryState>, TransportError> {
+        self.interceptors.on_send(&mut message);
+        let tag = self.next_tag.fetch_add(1, Ordering::Relaxed);
+        let transfer = Transfer::new(self.link.1)
+            .with_delivery_tag(DeliveryTag::new(tag.to_be_bytes().to_vec())?)
+            .with_message_format(0)
+            .with_settled(false);
+        let (link, payload) = (self.link, message.encode());
+        let outcome = request(&self.commands, |reply| Command::Send {
+            link,
+            transfer,
+            payload,
+            reply,
+        })
+        .await?;
+        Ok(outcome.await?)
+    }
+
+    /// Closes the link and waits for the peer to close its side.
+    pub async fn close(self) -> Result<(), TransportError> {
+        let link = self.link;
+        request(&self.commands, |reply| Command::Detach { link, reply }).await
+    }
+}
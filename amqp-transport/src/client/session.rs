@@ -0,0 +1,104 @@
+use crate::client::receiver::Receiver;
+use crate::client::request;
+use crate::client::sender::Sender;
+use crate::driver::Command;
+use crate::error::TransportError;
+use amqp_messaging::interceptor::Interceptors;
+use amqp_type::composite::transport::frame::performatives::attach::Attach;
+use amqp_type::composite::transport::transport::source::Source;
+use amqp_type::composite::transport::transport::target::Target;
+use amqp_type::restricted::role::Role;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// How many deliveries a receiver lets the sender send ahead of the application.
+pub const DEFAULT_LINK_CREDIT: u32 = 100;
+
+/// # Session
+/// A handle to a session of a client [`Connection`](crate::client::Connection), on which
+/// senders and receivers are attached.
+pub struct Session {
+    channel: u16,
+    commands: mpsc::UnboundedSender<Command>,
+    interceptors: Arc<Interceptors>,
+}
+
+impl Session {
+    pub(crate) fn new(
+        channel: u16,
+        commands: mpsc::UnboundedSender<Command>,
+        interceptors: Arc<Interceptors>,
+    ) -> Self {
+        Session {
+            channel,
+            commands,
+            interceptors,
+        }
+    }
+
+    pub fn channel(&self) -> u16 {
+        self.channel
+    }
+
+    /// Attaches a link sending to the node at `address`.
+    pub async fn attach_sender(
+        &self,
+        address: impl Into<String>,
+    ) -> Result<Sender, TransportError> {
+        let attach = Attach::new(link_name("sender"), 0, Role::Sender)
+            .with_source(Source::default())
+            .with_target(Target::new(address));
+        let (channel, attach) = (self.channel, attach);
+        let handle = request(&self.commands, |reply| Command::Attach {
+            channel,
+            attach,
+            credit: 0,
+            deliveries: None,
+            reply,
+        })
+        .await?;
+        Ok(Sender::new(
+            (channel, handle),
+            self.commands.clone(),
+            self.interceptors.clone(),
+        ))
+    }
+
+    /// Attaches a link receiving from the node at `address`, granting the sender
+    /// [`DEFAULT_LINK_CREDIT`] deliveries.
+    pub async fn attach_receiver(
+        &self,
+        address: impl Into<String>,
+    ) -> Result<Receiver, TransportError> {
+        let attach = Attach::new(link_name("receiver"), 0, Role::Receiver)
+            .with_source(Source::new(address))
+            .with_target(Target::default());
+        let channel = self.channel;
+        let (deliveries, incoming) = mpsc::unbounded_channel();
+        let handle = request(&self.commands, |reply| Command::Attach {
+            channel,
+            attach,
+            credit: DEFAULT_LINK_CREDIT,
+            deliveries: Some(deliveries),
+            reply,
+        })
+        .await?;
+        Ok(Receiver::new(
+            (channel, handle),
+            incoming,
+            self.commands.clone(),
+            self.interceptors.clone(),
+        ))
+    }
+
+    /// Ends the session and waits for the peer to end its side.
+    pub async fn end(self) -> Result<(), TransportError> {
+        let channel = self.channel;
+        request(&self.commands, |reply| Command::End { channel, reply }).await
+    }
+}
+
+/// Link names must be unique between two containers.
+fn link_name(role: &str) -> String {
+    format!("{}-{}", role, uuid::Uuid::new_v4())
+}
@@ -0,0 +1,45 @@
+use std::fmt::Debug;
+use std::time::Instant;
+
+/// # Clock
+/// The source of time for the idle timeout of a [`ConnectionEndpoint`](super::ConnectionEndpoint).
+///
+/// The endpoint never sleeps: it reads the clock when frames pass through it and reports the
+/// next deadline through [`ConnectionEndpoint::poll_timeout`](super::ConnectionEndpoint::poll_timeout).
+/// Tests swap in a clock they advance by hand.
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The clock of the operating system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when a test advances it.
+#[cfg(test)]
+#[derive(Debug, Clone)]
+pub(crate) struct ManualClock(std::sync::Arc<std::sync::Mutex<Instant>>);
+
+#[cfg(test)]
+impl ManualClock {
+    pub(crate) fn new() -> Self {
+        ManualClock(std::sync::Arc::new(std::sync::Mutex::new(Instant::now())))
+    }
+
+    pub(crate) fn advance(&self, millis: u64) {
+        *self.0.lock().unwrap() += std::time::Duration::from_millis(millis);
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.0.lock().unwrap()
+    }
+}
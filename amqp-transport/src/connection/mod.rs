@@ -1 +1,1293 @@
+pub mod clock;
 
+use crate::allocator::Allocator;
+use crate::connection::clock::{Clock, SystemClock};
+use crate::frame::amqp_frame::AmqpFrame;
+use crate::frame::Frame;
+use crate::protocol_header::{ProtocolHeader, ProtocolId};
+use crate::session::{SessionEndpoint, SessionEvent, SessionState, DEFAULT_SESSION_WINDOW};
+use amqp_type::composite::transport::frame::performative::Performative;
+use amqp_type::composite::transport::frame::performatives::begin::Begin;
+use amqp_type::composite::transport::frame::performatives::close::Close;
+use amqp_type::composite::transport::frame::performatives::open::Open;
+use amqp_type::composite::transport::transport::error::Error;
+use amqp_type::error::amqp_error::AmqpError;
+use amqp_type::error::connection_error::ConnectionError;
+use amqp_type::error::AppError;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// # Connection State
+/// The states of a connection endpoint as defined in spec section 2.4.6.
+/// ```text
+/// START       --S:HDR-->   HDR_SENT     START       --R:HDR-->   HDR_RCVD
+/// HDR_RCVD    --S:HDR-->   HDR_EXCH     HDR_SENT    --R:HDR-->   HDR_EXCH
+/// HDR_SENT    --S:OPEN-->  OPEN_PIPE    OPEN_PIPE   --R:HDR-->   OPEN_SENT
+/// OPEN_PIPE   --S:CLOSE--> OC_PIPE      OC_PIPE     --R:HDR-->   CLOSE_PIPE
+/// HDR_EXCH    --S:OPEN-->  OPEN_SENT    HDR_EXCH    --R:OPEN-->  OPEN_RCVD
+/// OPEN_SENT   --R:OPEN-->  OPENED       OPEN_RCVD   --S:OPEN-->  OPENED
+/// OPEN_SENT   --S:CLOSE--> CLOSE_PIPE   CLOSE_PIPE  --R:OPEN-->  CLOSE_SENT
+/// OPENED      --S:CLOSE--> CLOSE_SENT*  OPENED      --R:CLOSE--> CLOSE_RCVD
+/// CLOSE_SENT* --R:CLOSE--> END          CLOSE_RCVD  --S:CLOSE--> END
+/// ```
+/// `CLOSE_SENT*` is `DISCARDING` if the close was sent because of an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Start,
+    HdrRcvd,
+    HdrSent,
+    HdrExch,
+    OpenPipe,
+    OcPipe,
+    OpenRcvd,
+    OpenSent,
+    ClosePipe,
+    Opened,
+    CloseRcvd,
+    CloseSent,
+    Discarding,
+    End,
+}
+
+/// Bytes the endpoint wants to send to its peer, in order.
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum Transmit {
+    Header(ProtocolHeader),
+    Frame(Frame),
+}
+
+/// Something that happened on the connection that the layers above need to know about.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionEvent {
+    /// The peer opened its side of the connection.
+    Opened(Open),
+    /// The peer closed its side of the connection, possibly because of an error.
+    Closed(Option<Error>),
+    /// Something happened on the session with the given local channel.
+    Session(u16, SessionEvent),
+}
+
+/// # Connection Endpoint
+/// The state machine of one end of a connection, without any I/O.
+///
+/// The endpoint is driven by passing it the protocol headers and frames read from the peer,
+/// and by local commands. It queues the headers and frames that must be written to the peer
+/// ([`ConnectionEndpoint::poll_transmit`]) and the events for the layers above
+/// ([`ConnectionEndpoint::poll_event`]). Anything the current state does not allow fails with
+/// `amqp:illegal-state` and leaves the state unchanged; the caller is expected to close the
+/// connection with that error.
+///
+/// The endpoint owns the sessions on the connection. Frames from the peer are routed to the
+/// session mapped to their channel, and a begin for a new session creates a
+/// [`SessionEndpoint`] that is reported with [`SessionEvent::Begun`] and must be answered with
+/// [`SessionEndpoint::send_begin`]. Sessions are dropped once both ends have ended them.
+///
+/// Local channels are the lowest free ones up to the negotiated `channel-max`, and are only
+/// reused once the session is dropped. A frame on a channel beyond our `channel-max`, or on a
+/// channel no session is mapped to, is a `amqp:connection:framing-error`.
+///
+/// Once the peer's open advertised an idle timeout, the endpoint sends an empty frame whenever
+/// it has sent nothing for half of it. If our open advertised an idle timeout and nothing
+/// arrives from the peer for that long, the endpoint closes the connection with
+/// `amqp:resource-limit-exceeded`. Time is read from a [`Clock`]; the caller arms a timer
+/// for [`ConnectionEndpoint::poll_timeout`] and calls [`ConnectionEndpoint::handle_timeout`]
+/// when it fires.
+#[derive(Debug)]
+pub struct ConnectionEndpoint {
+    state: ConnectionState,
+    local_open: Open,
+    remote_open: Option<Open>,
+    sessions: BTreeMap<u16, SessionEndpoint>,
+    channels: Allocator,
+    remote_channels: HashMap<u16, u16>,
+    transmit: VecDeque<Transmit>,
+    events: VecDeque<ConnectionEvent>,
+    clock: Arc<dyn Clock>,
+    last_received: Instant,
+    last_sent: Instant,
+}
+
+impl ConnectionEndpoint {
+    pub fn new(local_open: Open) -> Self {
+        ConnectionEndpoint {
+            state: ConnectionState::Start,
+            channels: Allocator::new(local_open.channel_max() as u32),
+            local_open,
+            remote_open: None,
+            sessions: BTreeMap::new(),
+            remote_channels: HashMap::new(),
+            transmit: VecDeque::new(),
+            events: VecDeque::new(),
+            clock: Arc::new(SystemClock),
+            last_received: Instant::now(),
+            last_sent: Instant::now(),
+        }
+    }
+
+    /// Replaces the system clock, e.g. with one a test advances by hand.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.last_received = clock.now();
+        self.last_sent = clock.now();
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    pub fn local_open(&self) -> &Open {
+        &self.local_open
+    }
+
+    pub fn remote_open(&self) -> Option<&Open> {
+        self.remote_open.as_ref()
+    }
+
+    /// The largest frame the peer accepts. Until the peer's open has been received, this is the
+    /// minimum every peer must accept.
+    pub fn remote_max_frame_size(&self) -> u32 {
+        self.remote_open
+            .as_ref()
+            .map_or(crate::constants::MIN_MAX_FRAME_SIZE, |open| {
+                open.max_frame_size()
+            })
+    }
+
+    /// The highest channel number both peers can use.
+    pub fn channel_max(&self) -> u16 {
+        let local = self.local_open.channel_max();
+        self.remote_open
+            .as_ref()
+            .map_or(local, |open| local.min(open.channel_max()))
+    }
+
+    pub fn session(&self, channel: u16) -> Option<&SessionEndpoint> {
+        self.sessions.get(&channel)
+    }
+
+    pub fn session_mut(&mut self, channel: u16) -> Option<&mut SessionEndpoint> {
+        self.sessions.get_mut(&channel)
+    }
+
+    /// Polls the frames to send, the connection's own before those of its sessions.
+    /// Session frames are only sent while the connection is open on our side.
+    pub fn poll_transmit(&mut self) -> Option<Transmit> {
+        let transmit = match self.transmit.pop_front() {
+            Some(transmit) => Some(transmit),
+            None if self.carries_session_frames() => {
+                let frame = self
+                    .sessions
+                    .values_mut()
+                    .find_map(|session| session.poll_transmit());
+                self.remove_ended_sessions();
+                frame.map(|frame| Transmit::Frame(frame.into()))
+            }
+            None => None,
+        };
+        if transmit.is_some() {
+            self.last_sent = self.clock.now();
+        }
+        transmit
+    }
+
+    /// Our idle timeout: how long the peer may stay silent before we close the connection.
+    pub fn local_idle_timeout(&self) -> Option<Duration> {
+        idle_timeout(&self.local_open)
+    }
+
+    /// How often we must send something for the peer not to time out: half its idle timeout.
+    pub fn heartbeat_interval(&self) -> Option<Duration> {
+        self.remote_open
+            .as_ref()
+            .and_then(idle_timeout)
+            .map(|timeout| timeout / 2)
+    }
+
+    /// The instant [`ConnectionEndpoint::handle_timeout`] must be called next, if any.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        [self.expiry(), self.next_heartbeat()]
+            .into_iter()
+            .chain(self.sessions.values().map(SessionEndpoint::poll_timeout))
+            .flatten()
+            .min()
+    }
+
+    /// Acts on the idle timeouts that are due: closes the connection if the peer was silent
+    /// for longer than our idle timeout, or sends an empty frame to keep the peer from timing out.
+    /// Sessions send the dispositions they batched for long enough.
+    pub fn handle_timeout(&mut self) {
+        let now = self.clock.now();
+        for session in self.sessions.values_mut() {
+            session.handle_timeout();
+        }
+        if self.expiry().is_some_and(|expiry| now >= expiry) {
+            let error = Error::from(AppError::from(AmqpError::ResourceLimitExceeded))
+                .with_description("The idle timeout expired.".to_string());
+            // The peer gets another idle timeout to answer the close before the connection ends.
+            match self.send_close(Some(error)) {
+                Ok(()) => self.last_received = now,
+                Err(_) => self.state = ConnectionState::End,
+            }
+        } else if self
+            .next_heartbeat()
+            .is_some_and(|heartbeat| now >= heartbeat)
+        {
+            self.transmit.push_back(Transmit::Frame(Frame::heartbeat()));
+        }
+    }
+
+    fn expiry(&self) -> Option<Instant> {
+        let waiting = !matches!(
+            self.state,
+            ConnectionState::Start
+                | ConnectionState::HdrRcvd
+                | ConnectionState::HdrSent
+                | ConnectionState::HdrExch
+                | ConnectionState::OpenRcvd
+                | ConnectionState::End
+        );
+        match waiting {
+            true => self
+                .local_idle_timeout()
+                .map(|timeout| self.last_received + timeout),
+            false => None,
+        }
+    }
+
+    fn next_heartbeat(&self) -> Option<Instant> {
+        match self.state {
+            ConnectionState::Opened => self
+                .heartbeat_interval()
+                .map(|interval| self.last_sent + interval),
+            _ => None,
+        }
+    }
+
+    pub fn poll_event(&mut self) -> Option<ConnectionEvent> {
+        self.events.pop_front()
+    }
+
+    /// Sends the AMQP protocol header.
+    pub fn send_header(&mut self) -> Result<(), AppError> {
+        self.state = match self.state {
+            ConnectionState::Start => ConnectionState::HdrSent,
+            ConnectionState::HdrRcvd => ConnectionState::HdrExch,
+            _ => Err(AmqpError::IllegalState)?,
+        };
+        self.transmit.push_back(Transmit::Header(amqp_header()));
+        Ok(())
+    }
+
+    /// Sends the open frame. It may be pipelined right after the protocol header.
+    pub fn send_open(&mut self) -> Result<(), AppError> {
+        self.state = match self.state {
+            ConnectionState::HdrSent => ConnectionState::OpenPipe,
+            ConnectionState::HdrExch => ConnectionState::OpenSent,
+            ConnectionState::OpenRcvd => ConnectionState::Opened,
+            _ => Err(AmqpError::IllegalState)?,
+        };
+        self.send_performative(Performative::Open(self.local_open.clone()));
+        Ok(())
+    }
+
+    /// Sends the close frame. Closing because of an error moves an open connection to
+    /// `DISCARDING`, in which frames from the peer are ignored until its close arrives.
+    pub fn send_close(&mut self, error: Option<Error>) -> Result<(), AppError> {
+        self.state = match (self.state, &error) {
+            (ConnectionState::OpenPipe, _) => ConnectionState::OcPipe,
+            (ConnectionState::OpenSent, _) => ConnectionState::ClosePipe,
+            (ConnectionState::Opened, None) => ConnectionState::CloseSent,
+            (ConnectionState::Opened, Some(_)) => ConnectionState::Discarding,
+            (ConnectionState::CloseRcvd, _) => ConnectionState::End,
+            _ => Err(AmqpError::IllegalState)?,
+        };
+        self.send_performative(Performative::Close(Close::new(error)));
+        Ok(())
+    }
+
+    /// Begins a session on the lowest free channel, which is returned. The begin may be
+    /// pipelined right after the open frame.
+    pub fn begin_session(&mut self, begin: Begin) -> Result<u16, AppError> {
+        if !self.carries_session_frames() {
+            Err(AmqpError::IllegalState)?
+        }
+        let channel = self.free_channel()?;
+        let mut session = SessionEndpoint::new(channel, begin);
+        session.set_clock(self.clock.clone());
+        session.set_max_frame_size(self.remote_max_frame_size())?;
+        session.send_begin()?;
+        self.sessions.insert(channel, session);
+        Ok(channel)
+    }
+
+    /// Sends a frame for a session. Only an opened connection carries session frames.
+    pub fn send_frame(&mut self, frame: AmqpFrame) -> Result<(), AppError> {
+        if self.state != ConnectionState::Opened {
+            Err(AmqpError::IllegalState)?
+        }
+        self.transmit.push_back(Transmit::Frame(frame.into()));
+        Ok(())
+    }
+
+    /// Handles the protocol header received from the peer, once
+    /// [`ProtocolNegotiation`](crate::protocol_header::ProtocolNegotiation) has agreed on it.
+    /// The negotiation answers and refuses any other header, so the endpoint does not look at it.
+    pub fn on_header(&mut self) -> Result<(), AppError> {
+        self.last_received = self.clock.now();
+        self.state = match self.state {
+            ConnectionState::Start => ConnectionState::HdrRcvd,
+            ConnectionState::HdrSent => ConnectionState::HdrExch,
+            ConnectionState::OpenPipe => ConnectionState::OpenSent,
+            ConnectionState::OcPipe => ConnectionState::ClosePipe,
+            _ => Err(AmqpError::IllegalState)?,
+        };
+        Ok(())
+    }
+
+    /// Handles a frame received from the peer.
+    pub fn on_frame(&mut self, frame: Frame) -> Result<(), AppError> {
+        self.last_received = self.clock.now();
+        let frame = match frame {
+            Frame::AmqpFrame(frame) => frame,
+            Frame::Empty { .. } if self.has_received_header() => return Ok(()),
+            _ => Err(AmqpError::IllegalState)?,
+        };
+        match frame.performative() {
+            Performative::Close(close) => self.on_close(close.error().cloned()),
+            _ if self.state == ConnectionState::Discarding => Ok(()),
+            Performative::Open(open) => self.on_open(open.clone()),
+            _ => self.on_session_frame(frame),
+        }
+    }
+
+    fn on_open(&mut self, open: Open) -> Result<(), AppError> {
+        if open.max_frame_size() < crate::constants::MIN_MAX_FRAME_SIZE {
+            Err(AmqpError::FrameSizeTooSmall)?
+        }
+        self.state = match self.state {
+            ConnectionState::HdrExch => ConnectionState::OpenRcvd,
+            ConnectionState::OpenSent => ConnectionState::Opened,
+            ConnectionState::ClosePipe => ConnectionState::CloseSent,
+            _ => Err(AmqpError::IllegalState)?,
+        };
+        self.remote_open = Some(open.clone());
+        self.channels.set_max(self.channel_max() as u32);
+        for session in self.sessions.values_mut() {
+            session.set_max_frame_size(open.max_frame_size())?;
+        }
+        self.events.push_back(ConnectionEvent::Opened(open));
+        Ok(())
+    }
+
+    fn on_close(&mut self, error: Option<Error>) -> Result<(), AppError> {
+        self.state = match self.state {
+            ConnectionState::Opened => ConnectionState::CloseRcvd,
+            ConnectionState::CloseSent | ConnectionState::Discarding => ConnectionState::End,
+            _ => Err(AmqpError::IllegalState)?,
+        };
+        self.events.push_back(ConnectionEvent::Closed(error));
+        Ok(())
+    }
+
+    fn on_session_frame(&mut self, frame: AmqpFrame) -> Result<(), AppError> {
+        match self.state {
+            ConnectionState::Opened | ConnectionState::CloseSent => {}
+            _ => Err(AmqpError::IllegalState)?,
+        }
+        if frame.channel() > self.local_open.channel_max() {
+            Err(ConnectionError::FramingError)?
+        }
+        let channel = match frame.performative() {
+            Performative::Begin(begin) => self.map_remote_channel(frame.channel(), begin)?,
+            _ => match self.remote_channels.get(&frame.channel()) {
+                Some(channel) => *channel,
+                None => Err(ConnectionError::FramingError)?,
+            },
+        };
+        let session = self
+            .sessions
+            .get_mut(&channel)
+            .ok_or(AmqpError::InternalError)?;
+        session.on_frame(frame)?;
+        while let Some(event) = session.poll_event() {
+            self.events
+                .push_back(ConnectionEvent::Session(channel, event));
+        }
+        self.remove_ended_sessions();
+        Ok(())
+    }
+
+    /// Finds the local channel of the session a begin is meant for. A begin without a remote
+    /// channel starts a new session, a begin with one answers ours.
+    fn map_remote_channel(&mut self, remote_channel: u16, begin: &Begin) -> Result<u16, AppError> {
+        if self.remote_channels.contains_key(&remote_channel) {
+            Err(AmqpError::IllegalState)?
+        }
+        let channel = match begin.remote_channel() {
+            Some(channel) => match self.sessions.get(&channel) {
+                // The session may have been ended while its begin was on the way.
+                Some(session)
+                    if session.remote_channel().is_none()
+                        && matches!(
+                            session.state(),
+                            SessionState::BeginSent | SessionState::EndSent
+                        ) =>
+                {
+                    channel
+                }
+                _ => Err(AmqpError::IllegalState)?,
+            },
+            None => {
+                let channel = self.free_channel()?;
+                let begin = Begin::new(0.into(), DEFAULT_SESSION_WINDOW, DEFAULT_SESSION_WINDOW);
+                let mut session = SessionEndpoint::new(channel, begin);
+                session.set_clock(self.clock.clone());
+                session.set_max_frame_size(self.remote_max_frame_size())?;
+                self.sessions.insert(channel, session);
+                channel
+            }
+        };
+        self.remote_channels.insert(remote_channel, channel);
+        Ok(channel)
+    }
+
+    fn free_channel(&mut self) -> Result<u16, AppError> {
+        match self.channels.allocate() {
+            Some(channel) => Ok(channel as u16),
+            None => Err(AmqpError::ResourceLimitExceeded)?,
+        }
+    }
+
+    fn remove_ended_sessions(&mut self) {
+        let ended: Vec<u16> = self
+            .sessions
+            .iter()
+            .filter(|(_, session)| {
+                session.state() == SessionState::Unmapped && !session.has_transmit()
+            })
+            .map(|(channel, _)| *channel)
+            .collect();
+        for channel in ended {
+            self.sessions.remove(&channel);
+            self.channels.release(channel as u32);
+            self.remote_channels.retain(|_, local| *local != channel);
+        }
+    }
+
+    fn carries_session_frames(&self) -> bool {
+        matches!(
+            self.state,
+            ConnectionState::OpenPipe
+                | ConnectionState::OpenSent
+                | ConnectionState::Opened
+                | ConnectionState::CloseRcvd
+        )
+    }
+
+    fn send_performative(&mut self, performative: Performative) {
+        let frame = AmqpFrame::new(0, performative);
+        self.transmit.push_back(Transmit::Frame(frame.into()));
+    }
+
+    fn has_received_header(&self) -> bool {
+        !matches!(
+            self.state,
+            ConnectionState::Start
+                | ConnectionState::HdrSent
+                | ConnectionState::OpenPipe
+                | ConnectionState::OcPipe
+        )
+    }
+}
+
+fn idle_timeout(open: &Open) -> Option<Duration> {
+    open.idle_timeout()
+        .filter(|timeout| *timeout > 0)
+        .map(|timeout| Duration::from_millis(timeout as u64))
+}
+
+fn amqp_header() -> ProtocolHeader {
+    ProtocolHeader::new(ProtocolId::Amqp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::clock::ManualClock;
+    use crate::link::LinkEvent;
+    use amqp_type::composite::messaging::delivery_state::accepted::Accepted;
+    use amqp_type::composite::messaging::delivery_state::DeliveryState;
+    use amqp_type::composite::transport::frame::performatives::attach::Attach;
+    use amqp_type::composite::transport::frame::performatives::disposition::Disposition;
+    use amqp_type::composite::transport::frame::performatives::transfer::Transfer;
+    use amqp_type::primitive::variable_width::symbol::Symbol;
+    use amqp_type::restricted::delivery_tag::DeliveryTag;
+    use amqp_type::restricted::role::Role;
+    use futures::FutureExt;
+    use ConnectionState::*;
+
+    #[derive(Debug, Clone, Copy)]
+    enum Step {
+        SendHeader,
+        SendOpen,
+        SendClose,
+        SendCloseWithError,
+        ReceiveHeader,
+        ReceiveOpen,
+        ReceiveClose,
+    }
+    use Step::*;
+
+    fn open_frame() -> Frame {
+        AmqpFrame::new(0, Performative::Open(Open::new("remote".to_string()))).into()
+    }
+
+    fn close_frame() -> Frame {
+        AmqpFrame::new(0, Performative::Close(Close::default())).into()
+    }
+
+    fn remote_begin_frame() -> Frame {
+        AmqpFrame::new(1, Performative::Begin(Begin::new(0.into(), 10, 10))).into()
+    }
+
+    fn attach_frame() -> Frame {
+        AmqpFrame::new(
+            1,
+            Performative::Attach(Attach::new("link".to_string(), 0, Role::Sender)),
+        )
+        .into()
+    }
+
+    fn apply(endpoint: &mut ConnectionEndpoint, step: Step) -> Result<(), AppError> {
+        match step {
+            SendHeader => endpoint.send_header(),
+            SendOpen => endpoint.send_open(),
+            SendClose => endpoint.send_close(None),
+            SendCloseWithError => {
+                endpoint.send_close(Some(Error::new(Symbol::with_ascii("amqp:internal-error"))))
+            }
+            ReceiveHeader => endpoint.on_header(),
+            ReceiveOpen => endpoint.on_frame(open_frame()),
+            ReceiveClose => endpoint.on_frame(close_frame()),
+        }
+    }
+
+    fn run(steps: &[Step]) -> ConnectionEndpoint {
+        let mut endpoint = ConnectionEndpoint::new(Open::new("local".to_string()));
+        for step in steps {
+            apply(&mut endpoint, *step)
+                .unwrap_or_else(|e| panic!("{:?} failed in {:?}: {}", step, endpoint.state(), e));
+        }
+        endpoint
+    }
+
+    #[test]
+    fn test_legal_transitions() {
+        let paths: &[(&[Step], ConnectionState)] = &[
+            (&[], Start),
+            (&[ReceiveHeader], HdrRcvd),
+            (&[SendHeader], HdrSent),
+            (&[SendHeader, ReceiveHeader], HdrExch),
+            (&[ReceiveHeader, SendHeader], HdrExch),
+            (&[SendHeader, SendOpen], OpenPipe),
+            (&[SendHeader, SendOpen, SendClose], OcPipe),
+            (&[SendHeader, SendOpen, ReceiveHeader], OpenSent),
+            (&[SendHeader, ReceiveHeader, ReceiveOpen], OpenRcvd),
+            (&[SendHeader, ReceiveHeader, SendOpen], OpenSent),
+            (&[SendHeader, SendOpen, SendClose, ReceiveHeader], ClosePipe),
+            (&[SendHeader, ReceiveHeader, SendOpen, SendClose], ClosePipe),
+            (
+                &[SendHeader, ReceiveHeader, SendOpen, SendClose, ReceiveOpen],
+                CloseSent,
+            ),
+            (&[SendHeader, ReceiveHeader, SendOpen, ReceiveOpen], Opened),
+            (&[SendHeader, ReceiveHeader, ReceiveOpen, SendOpen], Opened),
+            (
+                &[
+                    SendHeader,
+                    ReceiveHeader,
+                    SendOpen,
+                    ReceiveOpen,
+                    ReceiveClose,
+                ],
+                CloseRcvd,
+            ),
+            (
+                &[SendHeader, ReceiveHeader, SendOpen, ReceiveOpen, SendClose],
+                CloseSent,
+            ),
+            (
+                &[
+                    SendHeader,
+                    ReceiveHeader,
+                    SendOpen,
+                    ReceiveOpen,
+                    SendCloseWithError,
+                ],
+                Discarding,
+            ),
+            (
+                &[
+                    SendHeader,
+                    ReceiveHeader,
+                    SendOpen,
+                    ReceiveOpen,
+                    ReceiveClose,
+                    SendClose,
+                ],
+                End,
+            ),
+            (
+                &[
+                    SendHeader,
+                    ReceiveHeader,
+                    SendOpen,
+                    ReceiveOpen,
+                    SendClose,
+                    ReceiveClose,
+                ],
+                End,
+            ),
+            (
+                &[
+                    SendHeader,
+                    ReceiveHeader,
+                    SendOpen,
+                    ReceiveOpen,
+                    SendCloseWithError,
+                    ReceiveClose,
+                ],
+                End,
+            ),
+        ];
+        for (steps, expected) in paths {
+            assert_eq!(run(steps).state(), *expected, "{:?}", steps);
+        }
+    }
+
+    #[test]
+    fn test_illegal_transitions() {
+        let paths: &[(&[Step], Step)] = &[
+            (&[], SendOpen),
+            (&[], SendClose),
+            (&[], ReceiveOpen),
+            (&[SendHeader], SendHeader),
+            (&[ReceiveHeader], ReceiveHeader),
+            (&[ReceiveHeader], SendOpen),
+            (&[SendHeader, ReceiveHeader], ReceiveClose),
+            (&[SendHeader, ReceiveHeader, ReceiveOpen], SendClose),
+            (&[SendHeader, ReceiveHeader, SendOpen], ReceiveClose),
+            (
+                &[SendHeader, ReceiveHeader, SendOpen, ReceiveOpen],
+                SendOpen,
+            ),
+            (
+                &[SendHeader, ReceiveHeader, SendOpen, ReceiveOpen],
+                ReceiveOpen,
+            ),
+            (
+                &[SendHeader, ReceiveHeader, SendOpen, ReceiveOpen, SendClose],
+                SendClose,
+            ),
+            (
+                &[
+                    SendHeader,
+                    ReceiveHeader,
+                    SendOpen,
+                    ReceiveOpen,
+                    ReceiveClose,
+                    SendClose,
+                ],
+                ReceiveClose,
+            ),
+        ];
+        for (steps, step) in paths {
+            let mut endpoint = run(steps);
+            let before = endpoint.state();
+            assert!(
+                matches!(
+                    apply(&mut endpoint, *step),
+                    Err(AppError::Amqp(AmqpError::IllegalState))
+                ),
+                "{:?} then {:?}",
+                steps,
+                step
+            );
+            assert_eq!(endpoint.state(), before);
+        }
+    }
+
+    #[test]
+    fn test_transmits_header_and_frames_in_order() {
+        let mut endpoint = run(&[SendHeader, SendOpen]);
+        assert_eq!(
+            endpoint.poll_transmit(),
+            Some(Transmit::Header(amqp_header()))
+        );
+        assert_eq!(
+            endpoint.poll_transmit(),
+            Some(Transmit::Frame(
+                AmqpFrame::new(0, Performative::Open(Open::new("local".to_string()))).into()
+            ))
+        );
+        assert_eq!(endpoint.poll_transmit(), None);
+    }
+
+    #[test]
+    fn test_emits_events_for_remote_open_and_close() {
+        let mut endpoint = run(&[
+            SendHeader,
+            ReceiveHeader,
+            SendOpen,
+            ReceiveOpen,
+            ReceiveClose,
+        ]);
+        assert_eq!(
+            endpoint.poll_event(),
+            Some(ConnectionEvent::Opened(Open::new("remote".to_string())))
+        );
+        assert_eq!(endpoint.poll_event(), Some(ConnectionEvent::Closed(None)));
+        assert_eq!(endpoint.remote_open().unwrap().container_id(), "remote");
+    }
+
+    #[test]
+    fn test_session_frames_require_open_connection() {
+        let mut endpoint = run(&[SendHeader, ReceiveHeader, SendOpen]);
+        assert!(matches!(
+            endpoint.on_frame(remote_begin_frame()),
+            Err(AppError::Amqp(AmqpError::IllegalState))
+        ));
+
+        let mut endpoint = run(&[SendHeader, ReceiveHeader, SendOpen, ReceiveOpen]);
+        endpoint.poll_event();
+        endpoint.on_frame(remote_begin_frame()).unwrap();
+        endpoint.on_frame(attach_frame()).unwrap();
+        assert!(matches!(
+            endpoint.poll_event(),
+            Some(ConnectionEvent::Session(0, SessionEvent::Begun(_)))
+        ));
+        assert!(matches!(
+            endpoint.poll_event(),
+            Some(ConnectionEvent::Session(
+                0,
+                SessionEvent::Link(0, LinkEvent::Attached(_))
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_frame_on_unmapped_channel_is_framing_error() {
+        let mut endpoint = run(&[SendHeader, ReceiveHeader, SendOpen, ReceiveOpen]);
+        assert!(matches!(
+            endpoint.on_frame(attach_frame()),
+            Err(AppError::Connection(ConnectionError::FramingError))
+        ));
+    }
+
+    fn opened_pair() -> (ConnectionEndpoint, ConnectionEndpoint) {
+        let mut client = ConnectionEndpoint::new(Open::new("client".to_string()));
+        let mut server = ConnectionEndpoint::new(Open::new("server".to_string()));
+        client.send_header().unwrap();
+        client.send_open().unwrap();
+        server.send_header().unwrap();
+        server.send_open().unwrap();
+        pump(&mut client, &mut server);
+        while client.poll_event().is_some() {}
+        while server.poll_event().is_some() {}
+        (client, server)
+    }
+
+    fn tag(tag: u8) -> DeliveryTag {
+        DeliveryTag::new(vec![tag]).unwrap()
+    }
+
+    /// Delivers everything both endpoints want to send to the other one until both are quiet.
+    fn pump(a: &mut ConnectionEndpoint, b: &mut ConnectionEndpoint) {
+        fn deliver(from: &mut ConnectionEndpoint, to: &mut ConnectionEndpoint) -> bool {
+            let mut delivered = false;
+            while let Some(transmit) = from.poll_transmit() {
+                delivered = true;
+                match transmit {
+                    Transmit::Header(_) => to.on_header().unwrap(),
+                    Transmit::Frame(frame) => to.on_frame(frame).unwrap(),
+                }
+            }
+            delivered
+        }
+        while deliver(a, b) | deliver(b, a) {}
+    }
+
+    #[test]
+    fn test_channel_max_limits_sessions() {
+        let mut endpoint =
+            ConnectionEndpoint::new(Open::new("local".to_string()).with_channel_max(2));
+        endpoint.send_header().unwrap();
+        endpoint.send_open().unwrap();
+        endpoint.on_header().unwrap();
+        endpoint
+            .on_frame(
+                AmqpFrame::new(
+                    0,
+                    Performative::Open(Open::new("remote".to_string()).with_channel_max(1)),
+                )
+                .into(),
+            )
+            .unwrap();
+        assert_eq!(
+            endpoint.begin_session(Begin::new(0.into(), 1, 1)).unwrap(),
+            0
+        );
+        assert_eq!(
+            endpoint.begin_session(Begin::new(0.into(), 1, 1)).unwrap(),
+            1
+        );
+        assert!(matches!(
+            endpoint.begin_session(Begin::new(0.into(), 1, 1)),
+            Err(AppError::Amqp(AmqpError::ResourceLimitExceeded))
+        ));
+    }
+
+    #[test]
+    fn test_frame_beyond_channel_max_is_framing_error() {
+        let mut endpoint =
+            ConnectionEndpoint::new(Open::new("local".to_string()).with_channel_max(0));
+        for step in [SendHeader, ReceiveHeader, SendOpen, ReceiveOpen] {
+            apply(&mut endpoint, step).unwrap();
+        }
+        assert!(matches!(
+            endpoint.on_frame(remote_begin_frame()),
+            Err(AppError::Connection(ConnectionError::FramingError))
+        ));
+    }
+
+    #[test]
+    fn test_channel_not_reused_while_end_pending() {
+        let (mut client, mut server) = opened_pair();
+        client.begin_session(Begin::new(0.into(), 10, 10)).unwrap();
+        pump(&mut client, &mut server);
+        server.session_mut(0).unwrap().send_begin().unwrap();
+        pump(&mut client, &mut server);
+
+        client.session_mut(0).unwrap().send_end(None).unwrap();
+        pump(&mut client, &mut server);
+        assert_eq!(
+            client.begin_session(Begin::new(0.into(), 10, 10)).unwrap(),
+            1
+        );
+
+        server.session_mut(0).unwrap().send_end(None).unwrap();
+        pump(&mut client, &mut server);
+        assert_eq!(
+            client.begin_session(Begin::new(0.into(), 10, 10)).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_session_begin_and_end_between_endpoints() {
+        let (mut client, mut server) = opened_pair();
+        assert_eq!(
+            client.begin_session(Begin::new(0.into(), 10, 10)).unwrap(),
+            0
+        );
+        pump(&mut client, &mut server);
+
+        let Some(ConnectionEvent::Session(channel, SessionEvent::Begun(_))) = server.poll_event()
+        else {
+            panic!("expected the server to see the begin");
+        };
+        server.session_mut(channel).unwrap().send_begin().unwrap();
+        pump(&mut client, &mut server);
+        assert!(matches!(
+            client.poll_event(),
+            Some(ConnectionEvent::Session(0, SessionEvent::Begun(_)))
+        ));
+        assert_eq!(client.session(0).unwrap().state(), SessionState::Mapped);
+        assert_eq!(
+            server.session(channel).unwrap().state(),
+            SessionState::Mapped
+        );
+
+        client.session_mut(0).unwrap().send_end(None).unwrap();
+        pump(&mut client, &mut server);
+        assert!(matches!(
+            server.poll_event(),
+            Some(ConnectionEvent::Session(_, SessionEvent::Ended(None)))
+        ));
+        server.session_mut(channel).unwrap().send_end(None).unwrap();
+        pump(&mut client, &mut server);
+        assert!(client.session(0).is_none());
+        assert!(server.session(channel).is_none());
+    }
+
+    #[test]
+    fn test_session_ended_before_its_begin_is_answered() {
+        let (mut client, mut server) = opened_pair();
+        client.begin_session(Begin::new(0.into(), 10, 10)).unwrap();
+        pump(&mut client, &mut server);
+        server.poll_event();
+        server.session_mut(0).unwrap().send_begin().unwrap();
+        client.session_mut(0).unwrap().send_end(None).unwrap();
+        assert_eq!(client.session(0).unwrap().state(), SessionState::EndSent);
+
+        pump(&mut client, &mut server);
+        assert_eq!(client.session(0).unwrap().state(), SessionState::EndSent);
+        assert_eq!(server.session(0).unwrap().state(), SessionState::EndRcvd);
+        server.session_mut(0).unwrap().send_end(None).unwrap();
+        pump(&mut client, &mut server);
+        assert!(client.session(0).is_none());
+        assert!(server.session(0).is_none());
+    }
+
+    #[test]
+    fn test_link_transfers_resume_on_session_flow() {
+        let (mut client, mut server) = opened_pair();
+        client.begin_session(Begin::new(0.into(), 10, 10)).unwrap();
+        pump(&mut client, &mut server);
+        server.poll_event();
+        let server_session = server.session_mut(0).unwrap();
+        server_session.set_incoming_window(0).unwrap();
+        server_session.send_begin().unwrap();
+        let client_session = client.session_mut(0).unwrap();
+        let handle = client_session
+            .attach_link(Attach::new("link".to_string(), 0, Role::Sender))
+            .unwrap();
+        pump(&mut client, &mut server);
+
+        let server_link = server.session_mut(0).unwrap().link_mut(0).unwrap();
+        server_link.send_attach().unwrap();
+        server_link.grant_credit(10).unwrap();
+        pump(&mut client, &mut server);
+
+        let client_session = client.session_mut(0).unwrap();
+        let client_link = client_session.link_mut(handle).unwrap();
+        assert_eq!(client_link.link_credit(), 10);
+        client_link
+            .send_transfer(Transfer::new(0).with_delivery_tag(tag(1)), vec![1])
+            .unwrap();
+        client_link
+            .send_transfer(Transfer::new(0).with_delivery_tag(tag(2)), vec![2])
+            .unwrap();
+        pump(&mut client, &mut server);
+        assert!(client.session(0).unwrap().is_blocked());
+        assert_eq!(server.session(0).unwrap().next_incoming_id(), 0.into());
+
+        server
+            .session_mut(0)
+            .unwrap()
+            .set_incoming_window(1)
+            .unwrap();
+        // The window of one transfer is restored after each of them.
+        pump(&mut client, &mut server);
+        assert_eq!(server.session(0).unwrap().next_incoming_id(), 2.into());
+        assert!(!client.session(0).unwrap().is_blocked());
+        let payloads: Vec<Vec<u8>> = std::iter::from_fn(|| server.poll_event())
+            .filter_map(|event| match event {
+                ConnectionEvent::Session(
+                    _,
+                    SessionEvent::Link(_, LinkEvent::Delivery(_, payload)),
+                ) => Some(payload),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(payloads, vec![vec![1], vec![2]]);
+    }
+
+    #[test]
+    fn test_discarding_ignores_frames_until_close() {
+        let mut endpoint = run(&[
+            SendHeader,
+            ReceiveHeader,
+            SendOpen,
+            ReceiveOpen,
+            SendCloseWithError,
+        ]);
+        endpoint.poll_event();
+        endpoint.on_frame(attach_frame()).unwrap();
+        assert_eq!(endpoint.poll_event(), None);
+        endpoint.on_frame(close_frame()).unwrap();
+        assert_eq!(endpoint.state(), End);
+    }
+
+    #[test]
+    fn test_discarding_ignores_open_and_begin() {
+        let mut endpoint = run(&[
+            SendHeader,
+            ReceiveHeader,
+            SendOpen,
+            ReceiveOpen,
+            SendCloseWithError,
+        ]);
+        endpoint.poll_event();
+        endpoint.on_frame(open_frame()).unwrap();
+        endpoint.on_frame(remote_begin_frame()).unwrap();
+        endpoint.on_frame(Frame::heartbeat()).unwrap();
+        assert_eq!(endpoint.state(), Discarding);
+        assert_eq!(endpoint.poll_event(), None);
+        endpoint.on_frame(close_frame()).unwrap();
+        assert_eq!(endpoint.state(), End);
+    }
+
+    #[test]
+    fn test_empty_frames_are_accepted_after_header() {
+        let mut endpoint = run(&[]);
+        assert!(endpoint.on_frame(Frame::heartbeat()).is_err());
+        let mut endpoint = run(&[SendHeader, ReceiveHeader]);
+        endpoint.on_frame(Frame::heartbeat()).unwrap();
+        assert_eq!(endpoint.state(), HdrExch);
+        assert_eq!(endpoint.poll_event(), None);
+    }
+
+    /// An opened endpoint with the given idle timeouts, with its header and open already sent.
+    fn opened_with_idle_timeouts(
+        local: Option<u32>,
+        remote: Option<u32>,
+        clock: &ManualClock,
+    ) -> ConnectionEndpoint {
+        let mut local_open = Open::new("local".to_string());
+        if let Some(timeout) = local {
+            local_open = local_open.with_idle_timeout(timeout);
+        }
+        let mut remote_open = Open::new("remote".to_string());
+        if let Some(timeout) = remote {
+            remote_open = remote_open.with_idle_timeout(timeout);
+        }
+        let mut endpoint = ConnectionEndpoint::new(local_open).with_clock(clock.clone());
+        endpoint.send_header().unwrap();
+        endpoint.send_open().unwrap();
+        endpoint.on_header().unwrap();
+        endpoint
+            .on_frame(AmqpFrame::new(0, Performative::Open(remote_open)).into())
+            .unwrap();
+        while endpoint.poll_transmit().is_some() {}
+        endpoint
+    }
+
+    #[test]
+    fn test_heartbeat_at_half_remote_idle_timeout() {
+        let clock = ManualClock::new();
+        let mut endpoint = opened_with_idle_timeouts(None, Some(1000), &clock);
+        assert_eq!(
+            endpoint.heartbeat_interval(),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(
+            endpoint.poll_timeout(),
+            Some(clock.now() + Duration::from_millis(500))
+        );
+
+        clock.advance(499);
+        endpoint.handle_timeout();
+        assert_eq!(endpoint.poll_transmit(), None);
+
+        clock.advance(1);
+        endpoint.handle_timeout();
+        assert_eq!(
+            endpoint.poll_transmit(),
+            Some(Transmit::Frame(Frame::heartbeat()))
+        );
+        assert_eq!(
+            endpoint.poll_timeout(),
+            Some(clock.now() + Duration::from_millis(500))
+        );
+    }
+
+    #[test]
+    fn test_outgoing_frames_postpone_heartbeat() {
+        let clock = ManualClock::new();
+        let mut endpoint = opened_with_idle_timeouts(None, Some(1000), &clock);
+        clock.advance(400);
+        endpoint.begin_session(Begin::new(0.into(), 1, 1)).unwrap();
+        assert!(endpoint.poll_transmit().is_some());
+        clock.advance(400);
+        endpoint.handle_timeout();
+        assert_eq!(endpoint.poll_transmit(), None);
+    }
+
+    #[test]
+    fn test_silent_peer_closes_connection() {
+        let clock = ManualClock::new();
+        let mut endpoint = opened_with_idle_timeouts(Some(2000), None, &clock);
+        assert_eq!(endpoint.heartbeat_interval(), None);
+        assert_eq!(
+            endpoint.poll_timeout(),
+            Some(clock.now() + Duration::from_millis(2000))
+        );
+
+        clock.advance(1500);
+        endpoint.on_frame(Frame::heartbeat()).unwrap();
+        clock.advance(1500);
+        endpoint.handle_timeout();
+        assert_eq!(endpoint.state(), Opened);
+
+        clock.advance(500);
+        endpoint.handle_timeout();
+        assert_eq!(endpoint.state(), Discarding);
+        let Some(Transmit::Frame(Frame::AmqpFrame(frame))) = endpoint.poll_transmit() else {
+            panic!("expected a close frame");
+        };
+        let Performative::Close(close) = frame.performative() else {
+            panic!("expected a close frame");
+        };
+        assert_eq!(
+            close.error().unwrap().condition(),
+            &Symbol::with_ascii("amqp:resource-limit-exceeded")
+        );
+
+        clock.advance(2000);
+        endpoint.handle_timeout();
+        assert_eq!(endpoint.state(), End);
+    }
+
+    #[test]
+    fn test_zero_idle_timeout_disables_timers() {
+        let clock = ManualClock::new();
+        let endpoint = opened_with_idle_timeouts(Some(0), Some(0), &clock);
+        assert_eq!(endpoint.poll_timeout(), None);
+    }
+
+    #[test]
+    fn test_negotiated_limits() {
+        let mut endpoint =
+            ConnectionEndpoint::new(Open::new("local".to_string()).with_channel_max(100));
+        assert_eq!(endpoint.remote_max_frame_size(), 512);
+        endpoint.send_header().unwrap();
+        endpoint.on_header().unwrap();
+        endpoint
+            .on_frame(
+                AmqpFrame::new(
+                    0,
+                    Performative::Open(
+                        Open::new("remote".to_string())
+                            .with_channel_max(10)
+                            .with_max_frame_size(4096),
+                    ),
+                )
+                .into(),
+            )
+            .unwrap();
+        assert_eq!(endpoint.channel_max(), 10);
+        assert_eq!(endpoint.remote_max_frame_size(), 4096);
+    }
+
+    #[test]
+    fn test_remote_frame_size_below_minimum_is_rejected() {
+        let mut endpoint = run(&[SendHeader, ReceiveHeader, SendOpen]);
+        assert!(matches!(
+            endpoint.on_frame(
+                AmqpFrame::new(
+                    0,
+                    Performative::Open(Open::new("remote".to_string()).with_max_frame_size(256))
+                )
+                .into()
+            ),
+            Err(AppError::Amqp(AmqpError::FrameSizeTooSmall))
+        ));
+        assert_eq!(endpoint.state(), OpenSent);
+    }
+
+    #[test]
+    fn test_links_fragment_by_remote_max_frame_size() {
+        let mut client =
+            ConnectionEndpoint::new(Open::new("client".to_string()).with_max_frame_size(2048));
+        let mut server =
+            ConnectionEndpoint::new(Open::new("server".to_string()).with_max_frame_size(1024));
+        client.send_header().unwrap();
+        client.send_open().unwrap();
+        server.send_header().unwrap();
+        server.send_open().unwrap();
+        pump(&mut client, &mut server);
+        client.begin_session(Begin::new(0.into(), 10, 10)).unwrap();
+        pump(&mut client, &mut server);
+        server.session_mut(0).unwrap().send_begin().unwrap();
+        let handle = client
+            .session_mut(0)
+            .unwrap()
+            .attach_link(Attach::new("link".to_string(), 0, Role::Sender))
+            .unwrap();
+        pump(&mut client, &mut server);
+        assert_eq!(
+            client
+                .session(0)
+                .unwrap()
+                .link(handle)
+                .unwrap()
+                .max_frame_size(),
+            1024
+        );
+        assert_eq!(
+            server.session(0).unwrap().link(0).unwrap().max_frame_size(),
+            2048
+        );
+    }
+
+    #[test]
+    fn test_disposition_settles_deliveries_across_links() {
+        let (mut client, mut server) = opened_pair();
+        client.begin_session(Begin::new(0.into(), 10, 10)).unwrap();
+        pump(&mut client, &mut server);
+        server.session_mut(0).unwrap().send_begin().unwrap();
+        let client_session = client.session_mut(0).unwrap();
+        let first = client_session
+            .attach_link(Attach::new("a".to_string(), 0, Role::Sender))
+            .unwrap();
+        let second = client_session
+            .attach_link(Attach::new("b".to_string(), 0, Role::Sender))
+            .unwrap();
+        pump(&mut client, &mut server);
+        for handle in [0, 1] {
+            let server_link = server.session_mut(0).unwrap().link_mut(handle).unwrap();
+            server_link.send_attach().unwrap();
+            server_link.grant_credit(1).unwrap();
+        }
+        pump(&mut client, &mut server);
+
+        let client_session = client.session_mut(0).unwrap();
+        let mut outcomes = Vec::new();
+        for (handle, tag_byte) in [(first, 1), (second, 2)] {
+            let link = client_session.link_mut(handle).unwrap();
+            outcomes.push(
+                link.send_message(
+                    Transfer::new(0).with_delivery_tag(tag(tag_byte)),
+                    vec![tag_byte],
+                )
+                .unwrap(),
+            );
+        }
+        pump(&mut client, &mut server);
+        let delivery_ids: Vec<_> = std::iter::from_fn(|| server.poll_event())
+            .filter_map(|event| match event {
+                ConnectionEvent::Session(
+                    _,
+                    SessionEvent::Link(_, LinkEvent::Delivery(transfer, _)),
+                ) => transfer.delivery_id(),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(delivery_ids, vec![0.into(), 1.into()]);
+
+        let accepted = DeliveryState::Accepted(Accepted {});
+        let disposition = Disposition::new(Role::Receiver, 0.into())
+            .with_last(1.into())
+            .with_settled(true)
+            .with_state(accepted.clone());
+        server
+            .session_mut(0)
+            .unwrap()
+            .send_performative(Performative::Disposition(disposition))
+            .unwrap();
+        pump(&mut client, &mut server);
+        for outcome in outcomes {
+            assert_eq!(
+                outcome.now_or_never().unwrap().unwrap(),
+                Some(accepted.clone())
+            );
+        }
+        let client_session = client.session(0).unwrap();
+        assert!(client_session.link(first).unwrap().unsettled().is_empty());
+        assert!(client_session.link(second).unwrap().unsettled().is_empty());
+    }
+}
@@ -44,6 +44,16 @@ pub const MINOR: u8 = 0;
 /// ```
 pub const REVISION: u8 = 0;
 
+/// The smallest max-frame-size a peer may advertise.
+/// Until the open frames have been exchanged, no frame may be larger than this.
+/// ```
+///# use amqp_transport::constants::MIN_MAX_FRAME_SIZE;
+/// assert_eq!(MIN_MAX_FRAME_SIZE, 512);
+/// ```
+pub const MIN_MAX_FRAME_SIZE: u32 = 512;
+
 // Utility Constants
 pub const AMQP_FRAME: u8 = 0x00;
 pub const SASL_FRAME: u8 = 0x01;
+/// Size of the fixed frame header: size (4), doff (1), type (1) and type specific (2).
+pub const FRAME_HEADER_SIZE: usize = 8;
@@ -0,0 +1,737 @@
+use crate::client::session::DEFAULT_LINK_CREDIT;
+use crate::connection::{ConnectionEndpoint, ConnectionEvent, ConnectionState, Transmit};
+use crate::error::TransportError;
+use crate::frame::codec::FrameCodec;
+use crate::link::delivery::DeliveryFuture;
+use crate::link::{LinkEndpoint, LinkEvent, LinkState};
+use crate::protocol_header::{self, NegotiationError, ProtocolHeader, ProtocolNegotiation};
+use crate::server::{Handler, Link};
+use crate::session::{SessionEvent, SessionState};
+use amqp_messaging::interceptor::Interceptors;
+use amqp_messaging::message::Message;
+use amqp_type::composite::messaging::delivery_state::rejected::Rejected;
+use amqp_type::composite::messaging::delivery_state::DeliveryState;
+use amqp_type::composite::transport::frame::performatives::attach::Attach;
+use amqp_type::composite::transport::frame::performatives::begin::Begin;
+use amqp_type::composite::transport::frame::performatives::open::Open;
+use amqp_type::composite::transport::frame::performatives::transfer::Transfer;
+use amqp_type::composite::transport::transport::error::Error;
+use amqp_type::error::amqp_error::AmqpError;
+use amqp_type::error::AppError;
+use amqp_type::restricted::delivery_tag::DeliveryTag;
+use amqp_type::restricted::handle::Handle;
+use amqp_type::restricted::role::Role;
+use bytes::BytesMut;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::StreamExt;
+use tokio_util::codec::{Encoder, FramedRead};
+
+pub(crate) type Reply<T> = oneshot::Sender<Result<T, TransportError>>;
+
+/// The deliveries arriving on a receiving link, ending with the error that detached it.
+pub(crate) type Deliveries = mpsc::UnboundedSender<Result<(Transfer, Vec<u8>), TransportError>>;
+
+/// A message waiting for credit, and who to tell once it is sent.
+type QueuedSend = (Transfer, Vec<u8>, Reply<DeliveryFuture>);
+
+/// A link is addressed by the local channel of its session and its local handle.
+type LinkKey = (u16, Handle);
+
+/// A request from a connection, session or link handle to the task driving the connection.
+/// Each request is answered on its reply channel once the peer has done its part.
+pub(crate) enum Command {
+    Begin {
+        begin: Begin,
+        reply: Reply<u16>,
+    },
+    End {
+        channel: u16,
+        reply: Reply<()>,
+    },
+    /// Attaches a link. A receiver grants `credit` once attached and forwards its deliveries.
+    Attach {
+        channel: u16,
+        attach: Attach,
+        credit: u32,
+        deliveries: Option<Deliveries>,
+        reply: Reply<Handle>,
+    },
+    Detach {
+        link: LinkKey,
+        reply: Reply<()>,
+    },
+    /// Sends a message as soon as the link has credit for it.
+    Send {
+        link: LinkKey,
+        transfer: Transfer,
+        payload: Vec<u8>,
+        reply: Reply<DeliveryFuture>,
+    },
+    Dispose {
+        link: LinkKey,
+        tag: DeliveryTag,
+        state: DeliveryState,
+        reply: Reply<()>,
+    },
+    /// Grants a receiver `credit` more deliveries.
+    Credit {
+        link: LinkKey,
+        credit: u32,
+    },
+    Close {
+        reply: Reply<()>,
+    },
+}
+
+/// What woke the driver up.
+enum Input {
+    Frame(Option<Result<crate::frame::Frame, crate::frame::codec::CodecError>>),
+    Command(Option<Command>),
+    Timeout,
+}
+
+/// The handler of a served connection, and the links it accepted.
+struct Serving {
+    handler: Box<dyn Handler>,
+    interceptors: Arc<Interceptors>,
+    links: HashMap<LinkKey, Link>,
+    next_tag: u64,
+}
+
+/// # Driver
+/// Runs a [`ConnectionEndpoint`] over a socket: it writes what the endpoint transmits, feeds it
+/// the frames read from the peer, fires its timers, and carries out the [`Command`]s of the
+/// handles, answering each once the peer has answered the frame it caused.
+///
+/// The driver answers what the peer starts on its own: sessions the peer begins are begun,
+/// ends, detaches and closes are answered. Links the peer attaches are refused, unless the
+/// connection is served by a [`Handler`], which then decides about the connection, its
+/// sessions and links, and the messages on them.
+///
+/// Receivers get back a unit of link credit for every delivery taken, and the sessions restore
+/// their windows as transfers use them up, so a session carries any number of messages.
+///
+/// Once the connection ends, every outstanding request fails with [`TransportError::Closed`].
+pub(crate) struct Driver<S> {
+    endpoint: ConnectionEndpoint,
+    reader: FramedRead<ReadHalf<S>, FrameCodec>,
+    writer: WriteHalf<S>,
+    codec: FrameCodec,
+    commands: mpsc::UnboundedReceiver<Command>,
+    /// No more commands arrive once all handles are dropped.
+    listening: bool,
+    opened: Option<Reply<Open>>,
+    begins: HashMap<u16, Reply<u16>>,
+    ends: HashMap<u16, Reply<()>>,
+    attaches: HashMap<LinkKey, (Reply<Handle>, u32)>,
+    detaches: HashMap<LinkKey, Reply<()>>,
+    receivers: HashMap<LinkKey, Deliveries>,
+    sends: HashMap<LinkKey, VecDeque<QueuedSend>>,
+    closes: Vec<Reply<()>>,
+    /// The error the peer closed the connection with.
+    error: Option<Error>,
+    serving: Option<Serving>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Send + 'static> Driver<S> {
+    pub(crate) fn new(
+        stream: S,
+        endpoint: ConnectionEndpoint,
+        commands: mpsc::UnboundedReceiver<Command>,
+    ) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        let max_frame_size = endpoint.local_open().max_frame_size();
+        Driver {
+            endpoint,
+            reader: FramedRead::new(
+                reader,
+                FrameCodec::new().with_max_frame_size(max_frame_size),
+            ),
+            writer,
+            codec: FrameCodec::new(),
+            commands,
+            listening: true,
+            opened: None,
+            begins: HashMap::new(),
+            ends: HashMap::new(),
+            attaches: HashMap::new(),
+            detaches: HashMap::new(),
+            receivers: HashMap::new(),
+            sends: HashMap::new(),
+            closes: Vec::new(),
+            error: None,
+            serving: None,
+        }
+    }
+
+    /// Serves the connection with `handler`. A served connection takes no commands.
+    pub(crate) fn with_handler(
+        mut self,
+        handler: Box<dyn Handler>,
+        interceptors: Arc<Interceptors>,
+    ) -> Self {
+        self.listening = false;
+        self.serving = Some(Serving {
+            handler,
+            interceptors,
+            links: HashMap::new(),
+            next_tag: 0,
+        });
+        self
+    }
+
+    /// Opens the connection as the initiating peer, pipelining the open frame after the protocol
+    /// header, and runs it until it ends. `opened` is answered with the peer's open.
+    pub(crate) async fn run_client(mut self, opened: Reply<Open>) {
+        self.opened = Some(opened);
+        let result = match (self.open_client().await, self.opened.take()) {
+            // Whoever is still waiting for the connection to open learns why it did not.
+            (Err(error), Some(opened)) => {
+                let _ = opened.send(Err(error));
+                Ok(())
+            }
+            (result, opened) => {
+                self.opened = opened;
+                result
+            }
+        };
+        let _ = self.finish(result).await;
+    }
+
+    /// Accepts the connection as the listening peer and runs it until it ends.
+    pub(crate) async fn run_server(mut self) -> Result<(), TransportError> {
+        let result = self.open_server().await;
+        self.finish(result).await
+    }
+
+    async fn open_client(&mut self) -> Result<(), TransportError> {
+        let mut negotiation = ProtocolNegotiation::amqp();
+        self.endpoint.send_header()?;
+        self.endpoint.send_open()?;
+        self.flush().await?;
+        let reply = self.read_header().await?;
+        negotiation.on_reply(reply)?;
+        self.endpoint.on_header()?;
+        self.drive().await
+    }
+
+    /// Accepts the client's AMQP header. Any other header is answered with ours before the
+    /// socket is closed (spec section 2.2).
+    async fn open_server(&mut self) -> Result<(), TransportError> {
+        let mut negotiation = ProtocolNegotiation::amqp();
+        let request = self.read_header().await?;
+        match negotiation.on_request(request)? {
+            protocol_header::Reply::Accept(_) => self.endpoint.on_header()?,
+            protocol_header::Reply::Reject(supported) => {
+                self.writer.write_all(&supported.encode()).await?;
+                self.writer.flush().await?;
+                Err(NegotiationError::Rejected {
+                    requested: ProtocolHeader::try_decode(request)?,
+                    supported,
+                })?
+            }
+        }
+        self.endpoint.send_header()?;
+        self.drive().await
+    }
+
+    async fn read_header(&mut self) -> Result<[u8; ProtocolHeader::SIZE], TransportError> {
+        let mut bytes = [0; ProtocolHeader::SIZE];
+        self.reader.get_mut().read_exact(&mut bytes).await?;
+        Ok(bytes)
+    }
+
+    async fn finish(mut self, result: Result<(), TransportError>) -> Result<(), TransportError> {
+        let _ = self.writer.shutdown().await;
+        let error = self.error.take();
+        self.fail_all(error);
+        result
+    }
+
+    /// Runs the connection until it ends.
+    async fn drive(&mut self) -> Result<(), TransportError> {
+        loop {
+            self.dispatch_events()?;
+            self.flush().await?;
+            if self.endpoint.state() == ConnectionState::End {
+                return Ok(());
+            }
+            let timeout = self.endpoint.poll_timeout();
+            let sleep = async {
+                match timeout {
+                    Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                    None => std::future::pending().await,
+                }
+            };
+            let input = tokio::select! {
+                frame = self.reader.next() => Input::Frame(frame),
+                command = self.commands.recv(), if self.listening => Input::Command(command),
+                _ = sleep => Input::Timeout,
+            };
+            match input {
+                Input::Frame(Some(frame)) => {
+                    let result = self.endpoint.on_frame(frame?);
+                    self.close_on_error(result)?;
+                }
+                Input::Frame(None) => Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof))?,
+                Input::Command(Some(command)) => self.on_command(command),
+                Input::Command(None) => {
+                    // Nobody can use the connection anymore.
+                    self.listening = false;
+                    let result = self.endpoint.send_close(None);
+                    self.close_on_error(result)?;
+                }
+                Input::Timeout => self.endpoint.handle_timeout(),
+            }
+        }
+    }
+
+    /// Closes the connection with the error, or gives up on it if it cannot be closed anymore.
+    fn close_on_error(&mut self, result: Result<(), AppError>) -> Result<(), TransportError> {
+        let Err(error) = result else {
+            return Ok(());
+        };
+        let error = Error::from(error);
+        match self.endpoint.send_close(Some(error.clone())) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(TransportError::Closed(Some(error))),
+        }
+    }
+
+    async fn flush(&mut self) -> Result<(), TransportError> {
+        let mut buffer = BytesMut::new();
+        while let Some(transmit) = self.endpoint.poll_transmit() {
+            match transmit {
+                Transmit::Header(header) => buffer.extend_from_slice(&header.encode()),
+                Transmit::Frame(frame) => self.codec.encode(frame, &mut buffer)?,
+            }
+        }
+        if !buffer.is_empty() {
+            self.writer.write_all(&buffer).await?;
+            self.writer.flush().await?;
+        }
+        Ok(())
+    }
+
+    fn dispatch_events(&mut self) -> Result<(), TransportError> {
+        while let Some(event) = self.endpoint.poll_event() {
+            match event {
+                ConnectionEvent::Opened(open) => {
+                    self.codec.set_max_frame_size(open.max_frame_size());
+                    if self.endpoint.state() == ConnectionState::OpenRcvd {
+                        let refusal = self
+                            .serving
+                            .as_mut()
+                            .and_then(|serving| serving.handler.on_open(&open).err());
+                        self.endpoint.send_open()?;
+                        if let Some(error) = refusal {
+                            self.endpoint.send_close(Some(error))?;
+                        }
+                    }
+                    if let Some(opened) = self.opened.take() {
+                        let _ = opened.send(Ok(open));
+                    }
+                }
+                ConnectionEvent::Closed(error) => {
+                    if self.endpoint.state() == ConnectionState::CloseRcvd {
+                        self.endpoint.send_close(None)?;
+                    }
+                    if let Some(serving) = self.serving.as_mut() {
+                        serving.handler.on_close(error.as_ref());
+                    }
+                    self.error = error;
+                }
+                ConnectionEvent::Session(channel, event) => {
+                    self.on_session_event(channel, event)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sessions and links that both ends have ended are already gone when their last event
+    /// is dispatched, so only the requests waiting for them are answered.
+    fn on_session_event(&mut self, channel: u16, event: SessionEvent) -> Result<(), AppError> {
+        let session = self.endpoint.session_mut(channel);
+        match event {
+            SessionEvent::Begun(begin) => {
+                if let Some(session) =
+                    session.filter(|session| session.state() == SessionState::BeginRcvd)
+                {
+                    let refusal = self
+                        .serving
+                        .as_mut()
+                        .and_then(|serving| serving.handler.on_begin(channel, &begin).err());
+                    session.send_begin()?;
+                    if let Some(error) = refusal {
+                        session.send_end(Some(error))?;
+                    }
+                }
+                if let Some(reply) = self.begins.remove(&channel) {
+                    let _ = reply.send(Ok(channel));
+                }
+            }
+            SessionEvent::Ended(error) => {
+                if let Some(session) =
+                    session.filter(|session| session.state() == SessionState::EndRcvd)
+                {
+                    session.send_end(None)?;
+                }
+                if let Some(reply) = self.ends.remove(&channel) {
+                    let _ = reply.send(Ok(()));
+                }
+                self.fail_links(|(link_channel, _)| link_channel == channel, error);
+            }
+            SessionEvent::Link(handle, event) => self.on_link_event((channel, handle), event)?,
+        }
+        Ok(())
+    }
+
+    fn on_link_event(&mut self, key: LinkKey, event: LinkEvent) -> Result<(), AppError> {
+        let link = link_mut(&mut self.endpoint, key);
+        match event {
+            LinkEvent::Attached(attach) => match (self.attaches.remove(&key), link) {
+                // A peer refusing the link attaches without a terminus and detaches right away.
+                (Some(pending), _) if refused(&attach) => {
+                    self.attaches.insert(key, pending);
+                }
+                (Some((reply, credit)), link) => {
+                    if let Some(link) = link.filter(|_| credit > 0) {
+                        link.grant_credit(credit)?;
+                    }
+                    let _ = reply.send(Ok(key.1));
+                }
+                (None, Some(link)) if link.state() == LinkState::AttachRcvd => {
+                    match self.serving.as_mut() {
+                        Some(serving) => {
+                            let info = Link::new(key.0, link.local_attach());
+                            match serving.handler.on_attach(&info) {
+                                Ok(()) => {
+                                    link.send_attach()?;
+                                    if link.role() == Role::Receiver {
+                                        link.grant_credit(DEFAULT_LINK_CREDIT)?;
+                                    }
+                                    serving.links.insert(key, info);
+                                }
+                                Err(error) => link.refuse(error)?,
+                            }
+                        }
+                        None => {
+                            let error = Error::from(AppError::from(AmqpError::NotImplemented))
+                                .with_description(
+                                    "Links can only be attached by this end.".to_string(),
+                                );
+                            link.refuse(error)?;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            LinkEvent::Detached { closed, error } => {
+                if let Some(link) = link.filter(|link| link.state() == LinkState::DetachRcvd) {
+                    link.send_detach(closed, None)?;
+                }
+                if let Some(serving) = self.serving.as_mut() {
+                    if let Some(info) = serving.links.remove(&key) {
+                        serving.handler.on_detach(&info, error.as_ref());
+                    }
+                }
+                if let Some(reply) = self.detaches.remove(&key) {
+                    let _ = reply.send(Ok(()));
+                }
+                self.fail_links(|link| link == key, error);
+            }
+            LinkEvent::Flow(_) => {
+                self.send_queued(key);
+                self.supply(key)?;
+            }
+            LinkEvent::Delivery(transfer, payload) => match self.receivers.get(&key) {
+                Some(deliveries) => {
+                    let _ = deliveries.send(Ok((transfer, payload)));
+                }
+                None => self.serve_delivery(key, transfer, payload)?,
+            },
+            LinkEvent::Settled { tag, state } => {
+                if let Some(serving) = self.serving.as_mut() {
+                    if let Some(info) = serving.links.get(&key) {
+                        serving.handler.on_outcome(info, &tag, state.as_ref());
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Sends a served link the messages the handler has for the credit the peer granted.
+    fn supply(&mut self, key: LinkKey) -> Result<(), AppError> {
+        let (Some(serving), Some(link)) =
+            (self.serving.as_mut(), link_mut(&mut self.endpoint, key))
+        else {
+            return Ok(());
+        };
+        let Some(info) = serving.links.get(&key) else {
+            return Ok(());
+        };
+        let credit = link.link_credit();
+        if link.role() != Role::Sender || credit == 0 {
+            return Ok(());
+        }
+        for mut message in serving
+            .handler
+            .on_credit(info, credit)
+            .into_iter()
+            .take(credit as usize)
+        {
+            serving.interceptors.on_send(&mut message);
+            let tag = DeliveryTag::new(serving.next_tag.to_be_bytes().to_vec())?;
+            serving.next_tag += 1;
+            let transfer = Transfer::new(key.1)
+                .with_delivery_tag(tag)
+                .with_message_format(0);
+            // The outcome is reported to the handler once the delivery is settled.
+            link.send_message(transfer, message.encode())?;
+        }
+        Ok(())
+    }
+
+    /// Hands a delivery on a served link to the handler, settles it with the outcome it
+    /// decides, and lets the peer send another one. A message that cannot be decoded is rejected.
+    fn serve_delivery(
+        &mut self,
+        key: LinkKey,
+        transfer: Transfer,
+        payload: Vec<u8>,
+    ) -> Result<(), AppError> {
+        let (Some(serving), Some(link)) =
+            (self.serving.as_mut(), link_mut(&mut self.endpoint, key))
+        else {
+            return Ok(());
+        };
+        let Some(info) = serving.links.get(&key) else {
+            return Ok(());
+        };
+        let state = match Message::try_decode(&mut payload.into_iter()) {
+            Ok(mut message) => {
+                serving.interceptors.on_receive(&mut message);
+                serving.handler.on_message(info, message)
+            }
+            Err(_) => DeliveryState::Rejected(Rejected {}),
+        };
+        if let Some(tag) = transfer
+            .delivery_tag()
+            .filter(|_| transfer.settled() != Some(true))
+        {
+            link.dispose(tag, state)?;
+        }
+        link.grant_credit(link.link_credit() + 1)
+    }
+
+    fn on_command(&mut self, command: Command) {
+        match command {
+            Command::Begin { begin, reply } => match self.endpoint.begin_session(begin) {
+                Ok(channel) => {
+                    self.begins.insert(channel, reply);
+                }
+                Err(error) => {
+                    let _ = reply.send(Err(error.into()));
+                }
+            },
+            Command::End { channel, reply } => {
+                let result = match self.endpoint.session_mut(channel) {
+                    Some(session) => session.send_end(None),
+                    None => Err(AmqpError::NotFound.into()),
+                };
+                match result {
+                    Ok(()) => {
+                        self.ends.insert(channel, reply);
+                    }
+                    Err(error) => {
+                        let _ = reply.send(Err(error.into()));
+                    }
+                }
+            }
+            Command::Attach {
+                channel,
+                attach,
+                credit,
+                deliveries,
+                reply,
+            } => {
+                let result = match self.endpoint.session_mut(channel) {
+                    Some(session) => session.attach_link(attach),
+                    None => Err(AmqpError::NotFound.into()),
+                };
+                match result {
+                    Ok(handle) => {
+                        self.attaches.insert((channel, handle), (reply, credit));
+                        if let Some(deliveries) = deliveries {
+                            self.receivers.insert((channel, handle), deliveries);
+                        }
+                    }
+                    Err(error) => {
+                        let _ = reply.send(Err(error.into()));
+                    }
+                }
+            }
+            Command::Detach { link, reply } => {
+                match link_mut(&mut self.endpoint, link)
+                    .map(|endpoint| endpoint.send_detach(true, None))
+                {
+                    Some(Ok(())) => {
+                        self.detaches.insert(link, reply);
+                    }
+                    Some(Err(error)) => {
+                        let _ = reply.send(Err(error.into()));
+                    }
+                    None => {
+                        let _ = reply.send(Err(TransportError::Closed(None)));
+                    }
+                }
+            }
+            Command::Send {
+                link,
+                transfer,
+                payload,
+                reply,
+            } => {
+                if link_mut(&mut self.endpoint, link).is_none() {
+                    let _ = reply.send(Err(TransportError::Closed(None)));
+                    return;
+                }
+                self.sends
+                    .entry(link)
+                    .or_default()
+                    .push_back((transfer, payload, reply));
+                self.send_queued(link);
+            }
+            Command::Dispose {
+                link,
+                tag,
+                state,
+                reply,
+            } => {
+                let result = match link_mut(&mut self.endpoint, link) {
+                    Some(endpoint) => endpoint.dispose(&tag, state).map_err(Into::into),
+                    None => Err(TransportError::Closed(None)),
+                };
+                let _ = reply.send(result);
+            }
+            Command::Credit { link, credit } => {
+                if let Some(endpoint) = link_mut(&mut self.endpoint, link) {
+                    let _ = endpoint.grant_credit(endpoint.link_credit() + credit);
+                }
+            }
+            Command::Close { reply } => match self.endpoint.send_close(None) {
+                Ok(()) => self.closes.push(reply),
+                Err(error) => {
+                    let _ = reply.send(Err(error.into()));
+                }
+            },
+        }
+    }
+
+    /// Sends the messages queued on a link for as long as it has credit.
+    fn send_queued(&mut self, key: LinkKey) {
+        let (Some(queue), Some(link)) =
+            (self.sends.get_mut(&key), link_mut(&mut self.endpoint, key))
+        else {
+            return;
+        };
+        while link.link_credit() > 0 {
+            let Some((transfer, payload, reply)) = queue.pop_front() else {
+                break;
+            };
+            let _ = reply.send(link.send_message(transfer, payload).map_err(Into::into));
+        }
+    }
+
+    /// Fails every request on the links the predicate selects.
+    fn fail_links(&mut self, selected: impl Fn(LinkKey) -> bool, error: Option<Error>) {
+        for key in self
+            .attaches
+            .keys()
+            .copied()
+            .filter(|key| selected(*key))
+            .collect::<Vec<_>>()
+        {
+            if let Some((reply, _)) = self.attaches.remove(&key) {
+                let _ = reply.send(closed(&error));
+            }
+        }
+        for key in self
+            .detaches
+            .keys()
+            .copied()
+            .filter(|key| selected(*key))
+            .collect::<Vec<_>>()
+        {
+            if let Some(reply) = self.detaches.remove(&key) {
+                let _ = reply.send(Ok(()));
+            }
+        }
+        for key in self
+            .receivers
+            .keys()
+            .copied()
+            .filter(|key| selected(*key))
+            .collect::<Vec<_>>()
+        {
+            if let Some(deliveries) = self.receivers.remove(&key) {
+                let _ = deliveries.send(closed(&error));
+            }
+        }
+        for key in self
+            .sends
+            .keys()
+            .copied()
+            .filter(|key| selected(*key))
+            .collect::<Vec<_>>()
+        {
+            for (_, _, reply) in self.sends.remove(&key).into_iter().flatten() {
+                let _ = reply.send(closed(&error));
+            }
+        }
+    }
+
+    /// Answers every outstanding request once the connection has ended.
+    fn fail_all(&mut self, error: Option<Error>) {
+        if let Some(opened) = self.opened.take() {
+            let _ = opened.send(closed(&error));
+        }
+        for (_, reply) in self.begins.drain() {
+            let _ = reply.send(closed(&error));
+        }
+        for (_, reply) in self.ends.drain() {
+            let _ = reply.send(Ok(()));
+        }
+        for reply in self.closes.drain(..) {
+            let _ = reply.send(Ok(()));
+        }
+        self.fail_links(|_| true, error);
+    }
+}
+
+/// Whether the peer answered an attach without the terminus it was asked to attach to.
+fn refused(attach: &Attach) -> bool {
+    match attach.role() {
+        Role::Receiver => attach.target().is_none(),
+        Role::Sender => attach.source().is_none(),
+    }
+}
+
+fn closed<T>(error: &Option<Error>) -> Result<T, TransportError> {
+    Err(TransportError::Closed(error.clone()))
+}
+
+fn link_mut(
+    endpoint: &mut ConnectionEndpoint,
+    (channel, handle): LinkKey,
+) -> Option<&mut LinkEndpoint> {
+    endpoint
+        .session_mut(channel)
+        .and_then(|session| session.link_mut(handle))
+}
@@ -0,0 +1,89 @@
+use crate::frame::codec::CodecError;
+use crate::protocol_header::NegotiationError;
+use amqp_type::composite::transport::transport::error::Error;
+use amqp_type::error::amqp_error::AmqpError;
+use amqp_type::error::AppError;
+use std::fmt::{Display, Formatter};
+use tokio_rustls::rustls;
+use tokio_tungstenite::tungstenite;
+
+/// # Transport Error
+/// Why an operation on a connection, session or link handle failed.
+#[derive(Debug)]
+pub enum TransportError {
+    Io(std::io::Error),
+    /// The operation broke the protocol, or a frame from the peer did.
+    Amqp(AppError),
+    Negotiation(NegotiationError),
+    /// The TLS configuration is invalid.
+    Tls(rustls::Error),
+    /// The WebSocket handshake failed.
+    WebSocket(tungstenite::Error),
+    /// The connection, session or link is gone, with the error it was closed with.
+    Closed(Option<Error>),
+}
+
+impl Display for TransportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransportError::Io(e) => write!(f, "{}", e),
+            TransportError::Amqp(e) => write!(f, "{}", e),
+            TransportError::Negotiation(e) => write!(f, "{}", e),
+            TransportError::Tls(e) => write!(f, "{}", e),
+            TransportError::WebSocket(e) => write!(f, "{}", e),
+            TransportError::Closed(Some(e)) => write!(f, "closed by the peer: {:?}", e),
+            TransportError::Closed(None) => write!(f, "closed"),
+        }
+    }
+}
+
+impl std::error::Error for TransportError {}
+
+impl From<std::io::Error> for TransportError {
+    fn from(error: std::io::Error) -> Self {
+        TransportError::Io(error)
+    }
+}
+
+impl From<AppError> for TransportError {
+    fn from(error: AppError) -> Self {
+        TransportError::Amqp(error)
+    }
+}
+
+impl From<AmqpError> for TransportError {
+    fn from(error: AmqpError) -> Self {
+        TransportError::Amqp(error.into())
+    }
+}
+
+impl From<NegotiationError> for TransportError {
+    fn from(error: NegotiationError) -> Self {
+        TransportError::Negotiation(error)
+    }
+}
+
+impl From<rustls::Error> for TransportError {
+    fn from(error: rustls::Error) -> Self {
+        TransportError::Tls(error)
+    }
+}
+
+impl From<tungstenite::Error> for TransportError {
+    fn from(error: tungstenite::Error) -> Self {
+        TransportError::WebSocket(error)
+    }
+}
+
+impl From<CodecError> for TransportError {
+    fn from(error: CodecError) -> Self {
+        match error {
+            CodecError::Io(e) => TransportError::Io(e),
+            CodecError::Amqp(e) => TransportError::Amqp(e),
+            // A frame we may not send is our mistake, like writing to a closed socket.
+            CodecError::FrameTooLarge { .. } => {
+                TransportError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, error))
+            }
+        }
+    }
+}
@@ -1,33 +1,78 @@
 use crate::constants::AMQP_FRAME;
+use crate::frame::{encode_frame, read_extended_header, verify_extended_header};
 use amqp_type::composite::transport::frame::performative::Performative;
 use amqp_type::error::AppError;
 use amqp_type::utils::sync_util::read_bytes_2;
-use amqp_type::utils::vec::VecExt;
 use std::vec::IntoIter;
 
-#[derive(Debug, Clone)]
+/// # AMQP Frame
+/// A frame of the AMQP layer, carrying a performative on a channel.
+///
+/// Any bytes following the performative in the frame body are the payload. Only transfer
+/// frames carry a payload, which holds (a section of) the encoded message.
+///
+/// The extended header is not interpreted by this library. It is preserved on decode and can
+/// be set on encode, in which case the data offset (doff) is computed from its size.
+#[derive(Debug, Clone, PartialEq)]
 pub struct AmqpFrame {
     channel: u16,
+    extended_header: Vec<u8>,
     performative: Performative,
+    payload: Vec<u8>,
 }
 
 impl AmqpFrame {
     pub fn new(channel: u16, performative: Performative) -> Self {
         AmqpFrame {
             channel,
+            extended_header: Vec::new(),
             performative,
+            payload: Vec::new(),
         }
     }
 
+    /// Sets the extended header. Its size must be a multiple of four bytes, at most
+    /// [`MAX_EXTENDED_HEADER_SIZE`](crate::frame::MAX_EXTENDED_HEADER_SIZE).
+    pub fn with_extended_header(mut self, extended_header: Vec<u8>) -> Result<Self, AppError> {
+        verify_extended_header(&extended_header)?;
+        self.extended_header = extended_header;
+        Ok(self)
+    }
+
+    pub fn with_payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    pub fn channel(&self) -> u16 {
+        self.channel
+    }
+
+    pub fn extended_header(&self) -> &[u8] {
+        &self.extended_header
+    }
+
+    pub fn performative(&self) -> &Performative {
+        &self.performative
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    pub fn into_parts(self) -> (u16, Performative, Vec<u8>) {
+        (self.channel, self.performative, self.payload)
+    }
+
     pub fn encode(self) -> Vec<u8> {
-        let mut data = self.performative.encode();
-        data.prepend(&mut self.channel.to_be_bytes().to_vec());
-        data.prepend(&mut vec![AMQP_FRAME]);
-        data.prepend(&mut vec![2]);
-        // adjust size by + 4 to include the 4 bytes of the size itself
-        let mut size = ((data.len() + 4) as u32).to_be_bytes().to_vec();
-        data.prepend(&mut size);
-        data
+        let mut body = self.performative.encode();
+        body.extend(self.payload);
+        encode_frame(
+            AMQP_FRAME,
+            self.channel.to_be_bytes(),
+            self.extended_header,
+            body,
+        )
     }
 
     pub fn try_decode(doff: u8, stream: &mut IntoIter<u8>) -> Result<Self, AppError>
@@ -35,58 +80,94 @@ impl AmqpFrame {
         Self: Sized,
     {
         let channel = u16::from_be_bytes(read_bytes_2(stream)?);
-        skip_extended_header(doff, stream);
+        let extended_header = read_extended_header(doff, stream)?;
         let performative = Performative::try_decode(stream)?;
-        Ok(AmqpFrame::new(channel, performative))
-    }
-}
-
-fn skip_extended_header(doff: u8, stream: &mut IntoIter<u8>) {
-    if doff == 2 {
-        return;
-    }
-    for _ in 0..(doff * 4) - 8 {
-        stream.next();
+        let payload = stream.collect();
+        Ok(AmqpFrame {
+            channel,
+            extended_header,
+            performative,
+            payload,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use amqp_type::composite::transport::frame::performatives::open::Open;
+    use amqp_type::composite::transport::frame::performatives::transfer::Transfer;
+    use amqp_type::error::amqp_error::AmqpError;
+    use amqp_type::error::connection_error::ConnectionError;
 
-    /*#[test]
-    fn test_encode_decode_round_trip_amqp_frame_open() {
-        todo!()
-    }
-    #[test]
-    fn test_encode_decode_round_trip_amqp_frame_begin() {
-        todo!()
+    fn round_trip(frame: AmqpFrame) -> AmqpFrame {
+        let mut encoded = frame.encode().into_iter();
+        // skip size, doff and frame type
+        let header: Vec<u8> = encoded.by_ref().take(6).collect();
+        AmqpFrame::try_decode(header[4], &mut encoded).unwrap()
     }
+
     #[test]
-    fn test_encode_decode_round_trip_amqp_frame_attach() {
-        todo!()
+    fn test_encode_decode_round_trip_amqp_frame_open() {
+        let frame = AmqpFrame::new(0, Performative::Open(Open::new("container".to_string())));
+        assert_eq!(round_trip(frame.clone()), frame);
     }
+
     #[test]
-    fn test_encode_decode_round_trip_amqp_frame_flow() {
-        todo!()
+    fn test_encode_decode_round_trip_amqp_frame_transfer_with_payload() {
+        let frame = AmqpFrame::new(3, Performative::Transfer(Transfer::new(1).with_more(false)))
+            .with_payload(vec![0x00, 0x53, 0x77, 0xa1, 0x02, b'h', b'i']);
+        let decoded = round_trip(frame.clone());
+        assert_eq!(decoded.channel(), 3);
+        assert_eq!(decoded.payload(), frame.payload());
+        assert_eq!(decoded, frame);
     }
+
     #[test]
-    fn test_encode_decode_round_trip_amqp_frame_transfer() {
-        todo!()
+    fn test_encode_includes_payload_in_size() {
+        let frame =
+            AmqpFrame::new(0, Performative::Transfer(Transfer::new(1))).with_payload(vec![1; 10]);
+        let without_payload = AmqpFrame::new(0, Performative::Transfer(Transfer::new(1))).encode();
+        let encoded = frame.encode();
+        assert_eq!(encoded.len(), without_payload.len() + 10);
+        assert_eq!(
+            u32::from_be_bytes(encoded[..4].try_into().unwrap()) as usize,
+            encoded.len()
+        );
     }
+
     #[test]
-    fn test_encode_decode_round_trip_amqp_frame_disposition() {
-        todo!()
+    fn test_encode_decode_round_trip_extended_header() {
+        let frame = AmqpFrame::new(1, Performative::Open(Open::new("container".to_string())))
+            .with_extended_header(vec![1, 2, 3, 4, 5, 6, 7, 8])
+            .unwrap();
+        let encoded = frame.clone().encode();
+        assert_eq!(encoded[4], 4);
+        assert_eq!(round_trip(frame.clone()), frame);
+        assert_eq!(
+            round_trip(frame).extended_header(),
+            &[1, 2, 3, 4, 5, 6, 7, 8]
+        );
     }
+
     #[test]
-    fn test_encode_decode_round_trip_amqp_frame_detach() {
-        todo!()
+    fn test_extended_header_must_be_word_aligned() {
+        let frame = AmqpFrame::new(1, Performative::Open(Open::new("container".to_string())));
+        assert!(matches!(
+            frame.with_extended_header(vec![1, 2, 3]),
+            Err(AppError::Amqp(AmqpError::InvalidField))
+        ));
     }
+
     #[test]
-    fn test_encode_decode_round_trip_amqp_frame_end() {
-        todo!()
+    fn test_decode_rejects_malformed_doff() {
+        let encoded = AmqpFrame::new(0, Performative::Open(Open::new("c".to_string()))).encode();
+        for doff in [0, 1, 200] {
+            let mut stream = encoded.clone().split_off(6).into_iter();
+            assert!(matches!(
+                AmqpFrame::try_decode(doff, &mut stream),
+                Err(AppError::Connection(ConnectionError::FramingError))
+            ));
+        }
     }
-    #[test]
-    fn test_encode_decode_round_trip_amqp_frame_close() {
-        todo!()
-    }*/
 }
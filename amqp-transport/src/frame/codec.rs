@@ -0,0 +1,303 @@
+use crate::constants::{FRAME_HEADER_SIZE, MIN_MAX_FRAME_SIZE};
+use crate::frame::Frame;
+use amqp_type::error::amqp_error::AmqpError;
+use amqp_type::error::connection_error::ConnectionError;
+use amqp_type::error::AppError;
+use bytes::{Buf, BufMut, BytesMut};
+use std::fmt::{Display, Formatter};
+use tokio_util::codec::{Decoder, Encoder};
+
+#[derive(Debug)]
+pub enum CodecError {
+    Io(std::io::Error),
+    Amqp(AppError),
+    /// A frame to encode is larger than the max-frame-size of the peer.
+    FrameTooLarge {
+        size: usize,
+        max: u32,
+    },
+}
+
+impl Display for CodecError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodecError::Io(e) => write!(f, "{}", e),
+            CodecError::Amqp(e) => write!(f, "{}", e),
+            CodecError::FrameTooLarge { size, max } => {
+                write!(f, "frame of {} bytes exceeds max-frame-size {}", size, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl From<std::io::Error> for CodecError {
+    fn from(error: std::io::Error) -> Self {
+        CodecError::Io(error)
+    }
+}
+
+impl From<AppError> for CodecError {
+    fn from(error: AppError) -> Self {
+        CodecError::Amqp(error)
+    }
+}
+
+impl From<AmqpError> for CodecError {
+    fn from(error: AmqpError) -> Self {
+        CodecError::Amqp(error.into())
+    }
+}
+
+impl From<ConnectionError> for CodecError {
+    fn from(error: ConnectionError) -> Self {
+        CodecError::Amqp(error.into())
+    }
+}
+
+/// # Frame Codec
+/// Splits a byte stream into frames, for use with [`tokio_util::codec::Framed`].
+///
+/// ```text
+///              +0       +1       +2       +3
+///         +-----------------------------------+ -.
+///       0 |                SIZE               |  |
+///         +-----------------------------------+  |---> Frame Header
+///       4 |  DOFF  |  TYPE  | <TYPE-SPECIFIC> |  |      (8 bytes)
+///         +-----------------------------------+ -'
+/// ```
+/// The codec rejects frames whose size is smaller than the frame header or larger than the
+/// max-frame-size, and frames whose data offset does not lie within the frame, with
+/// `amqp:connection:framing-error`. Until the open frames have been exchanged, the
+/// max-frame-size is [`MIN_MAX_FRAME_SIZE`]; update it with [`FrameCodec::set_max_frame_size`]
+/// once it has been negotiated.
+///
+/// The max-frame-size limits whichever direction the codec is used for: a codec that decodes
+/// the frames read from the peer holds our own max-frame-size, and a codec that encodes the
+/// frames written to the peer holds the one the peer announced. A frame larger than that fails
+/// to encode with [`CodecError::FrameTooLarge`]. A connection that reads and writes frames
+/// therefore keeps one codec for each direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameCodec {
+    max_frame_size: u32,
+}
+
+impl Default for FrameCodec {
+    fn default() -> Self {
+        FrameCodec::new()
+    }
+}
+
+impl FrameCodec {
+    pub fn new() -> Self {
+        FrameCodec {
+            max_frame_size: MIN_MAX_FRAME_SIZE,
+        }
+    }
+
+    pub fn with_max_frame_size(mut self, max_frame_size: u32) -> Self {
+        self.set_max_frame_size(max_frame_size);
+        self
+    }
+
+    pub fn max_frame_size(&self) -> u32 {
+        self.max_frame_size
+    }
+
+    pub fn set_max_frame_size(&mut self, max_frame_size: u32) {
+        self.max_frame_size = max_frame_size.max(MIN_MAX_FRAME_SIZE);
+    }
+}
+
+impl Decoder for FrameCodec {
+    type Item = Frame;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < FRAME_HEADER_SIZE {
+            src.reserve(FRAME_HEADER_SIZE - src.len());
+            return Ok(None);
+        }
+        let size = u32::from_be_bytes([src[0], src[1], src[2], src[3]]);
+        if (size as usize) < FRAME_HEADER_SIZE || size > self.max_frame_size {
+            Err(ConnectionError::FramingError)?
+        }
+        let doff = src[4] as usize;
+        if doff < 2 || doff * 4 > size as usize {
+            Err(ConnectionError::FramingError)?
+        }
+        if src.len() < size as usize {
+            src.reserve(size as usize - src.len());
+            return Ok(None);
+        }
+        let mut frame = src.split_to(size as usize);
+        frame.advance(4);
+        Ok(Some(Frame::try_decode_body(frame.to_vec())?))
+    }
+}
+
+impl Encoder<Frame> for FrameCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, item: Frame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let encoded = item.encode();
+        if encoded.len() > self.max_frame_size as usize {
+            Err(CodecError::FrameTooLarge {
+                size: encoded.len(),
+                max: self.max_frame_size,
+            })?
+        }
+        dst.reserve(encoded.len());
+        dst.put_slice(&encoded);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::amqp_frame::AmqpFrame;
+    use amqp_type::composite::transport::frame::performative::Performative;
+    use amqp_type::composite::transport::frame::performatives::open::Open;
+    use futures::{SinkExt, StreamExt};
+    use tokio_util::codec::Framed;
+
+    fn open_frame() -> Frame {
+        AmqpFrame::new(0, Performative::Open(Open::new("container".to_string()))).into()
+    }
+
+    fn encoded_open_frame() -> Vec<u8> {
+        open_frame().encode()
+    }
+
+    #[test]
+    fn test_decode_waits_for_partial_frame() {
+        let encoded = encoded_open_frame();
+        let mut codec = FrameCodec::new();
+        let mut buffer = BytesMut::new();
+        for byte in &encoded[..encoded.len() - 1] {
+            buffer.put_u8(*byte);
+            assert!(codec.decode(&mut buffer).unwrap().is_none());
+        }
+        buffer.put_u8(encoded[encoded.len() - 1]);
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(open_frame()));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_decode_consecutive_frames() {
+        let mut buffer = BytesMut::new();
+        buffer.put_slice(&encoded_open_frame());
+        buffer.put_slice(&encoded_open_frame());
+        let mut codec = FrameCodec::new();
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(open_frame()));
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(open_frame()));
+        assert_eq!(codec.decode(&mut buffer).unwrap(), None);
+    }
+
+    #[test]
+    fn test_decode_rejects_size_below_header() {
+        let mut buffer = BytesMut::from(&[0, 0, 0, 7, 2, 0, 0, 0][..]);
+        assert!(matches!(
+            FrameCodec::new().decode(&mut buffer),
+            Err(CodecError::Amqp(AppError::Connection(
+                ConnectionError::FramingError
+            )))
+        ));
+    }
+
+    #[test]
+    fn test_decode_rejects_frame_larger_than_max_frame_size() {
+        let mut buffer = BytesMut::from(&[0, 0, 2, 1, 2, 0, 0, 0][..]);
+        assert!(matches!(
+            FrameCodec::new().decode(&mut buffer),
+            Err(CodecError::Amqp(AppError::Connection(
+                ConnectionError::FramingError
+            )))
+        ));
+        let mut buffer = BytesMut::from(&[0, 0, 2, 1, 2, 0, 0, 0][..]);
+        let mut codec = FrameCodec::new().with_max_frame_size(1024);
+        assert!(codec.decode(&mut buffer).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_decode_rejects_invalid_doff() {
+        for doff in [0, 1, 3] {
+            let mut buffer = BytesMut::from(&[0, 0, 0, 8, doff, 0, 0, 0][..]);
+            assert!(matches!(
+                FrameCodec::new().decode(&mut buffer),
+                Err(CodecError::Amqp(AppError::Connection(
+                    ConnectionError::FramingError
+                )))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_encode_rejects_frame_larger_than_max_frame_size() {
+        let mut buffer = BytesMut::new();
+        let mut codec = FrameCodec::new();
+        codec.encode(open_frame(), &mut buffer).unwrap();
+        assert_eq!(buffer.to_vec(), encoded_open_frame());
+
+        let open = Open::new("c".repeat(600));
+        let frame: Frame = AmqpFrame::new(0, Performative::Open(open)).into();
+        let size = frame.clone().encode().len();
+        let mut buffer = BytesMut::new();
+        assert!(matches!(
+            codec.encode(frame, &mut buffer),
+            Err(CodecError::FrameTooLarge { size: s, max: 512 }) if s == size
+        ));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_encode_limit_is_the_peers_max_frame_size() {
+        let open = Open::new("c".repeat(600));
+        let frame: Frame = AmqpFrame::new(0, Performative::Open(open)).into();
+        let mut writer = FrameCodec::new().with_max_frame_size(1024);
+        let mut buffer = BytesMut::new();
+        writer.encode(frame, &mut buffer).unwrap();
+
+        // A reader with our smaller max-frame-size refuses what the peer may be sent.
+        assert!(matches!(
+            FrameCodec::new().decode(&mut buffer),
+            Err(CodecError::Amqp(AppError::Connection(
+                ConnectionError::FramingError
+            )))
+        ));
+    }
+
+    #[test]
+    fn test_decode_heartbeat() {
+        let mut buffer = BytesMut::new();
+        let mut codec = FrameCodec::new();
+        codec.encode(Frame::heartbeat(), &mut buffer).unwrap();
+        codec.encode(open_frame(), &mut buffer).unwrap();
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(Frame::heartbeat()));
+        assert_eq!(codec.decode(&mut buffer).unwrap(), Some(open_frame()));
+    }
+
+    #[test]
+    fn test_max_frame_size_is_at_least_minimum() {
+        assert_eq!(
+            FrameCodec::new().with_max_frame_size(100).max_frame_size(),
+            MIN_MAX_FRAME_SIZE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_framed_drives_duplex_stream() {
+        let (client, server) = tokio::io::duplex(16);
+        let mut client = Framed::new(client, FrameCodec::new());
+        let mut server = Framed::new(server, FrameCodec::new());
+        let sender = tokio::spawn(async move {
+            client.send(open_frame()).await.unwrap();
+            client.send(open_frame()).await.unwrap();
+        });
+        assert_eq!(server.next().await.unwrap().unwrap(), open_frame());
+        assert_eq!(server.next().await.unwrap().unwrap(), open_frame());
+        sender.await.unwrap();
+    }
+}
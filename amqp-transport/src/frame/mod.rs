@@ -1,25 +1,75 @@
 pub mod amqp_frame;
+pub mod codec;
 pub mod sasl_frame;
 
-use crate::constants::{AMQP_FRAME, SASL_FRAME};
+use crate::constants::{AMQP_FRAME, FRAME_HEADER_SIZE, SASL_FRAME};
 use crate::frame::amqp_frame::AmqpFrame;
 use crate::frame::sasl_frame::SaslFrame;
+use amqp_type::error::amqp_error::AmqpError;
+use amqp_type::error::connection_error::ConnectionError;
 use amqp_type::error::AppError;
 use amqp_type::utils::async_util::{read_bytes, read_bytes_4};
+use amqp_type::utils::sync_util::read_bytes_2;
+use amqp_type::utils::vec::VecExt;
 use std::pin::Pin;
+use std::vec::IntoIter;
 use tokio_stream::Stream;
-use amqp_type::error::amqp_error::AmqpError;
 
+/// The largest extended header a data offset (doff) of 255 words leaves room for.
+pub const MAX_EXTENDED_HEADER_SIZE: usize = 255 * 4 - FRAME_HEADER_SIZE;
+
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
 pub enum Frame {
     AmqpFrame(AmqpFrame),
     SaslFrame(SaslFrame),
+    /// An AMQP frame without a body. Empty frames carry no information and are sent to keep
+    /// an idle connection alive. Like any other frame, they may have an extended header.
+    Empty {
+        channel: u16,
+        extended_header: Vec<u8>,
+    },
 }
 
 impl Frame {
+    /// An empty frame on channel 0, as sent to satisfy the peer's idle timeout.
+    pub fn heartbeat() -> Self {
+        Frame::empty(0)
+    }
+
+    /// An empty frame on the given channel, without an extended header.
+    pub fn empty(channel: u16) -> Self {
+        Frame::Empty {
+            channel,
+            extended_header: Vec::new(),
+        }
+    }
+
+    /// An empty frame with an extended header, whose size must be a multiple of four bytes.
+    pub fn empty_with_extended_header(
+        channel: u16,
+        extended_header: Vec<u8>,
+    ) -> Result<Self, AppError> {
+        verify_extended_header(&extended_header)?;
+        Ok(Frame::Empty {
+            channel,
+            extended_header,
+        })
+    }
+
     pub fn encode(self) -> Vec<u8> {
         match self {
             Frame::AmqpFrame(amqp) => amqp.encode(),
             Frame::SaslFrame(sasl) => sasl.encode(),
+            Frame::Empty {
+                channel,
+                extended_header,
+            } => encode_frame(
+                AMQP_FRAME,
+                channel.to_be_bytes(),
+                extended_header,
+                Vec::new(),
+            ),
         }
     }
 }
@@ -31,7 +81,13 @@ impl Frame {
     {
         let size = u32::from_be_bytes(read_bytes_4(stream).await?);
         // size adjusted by -4 to account for already read size bytes
-        let mut buffer = read_bytes(stream, size as usize - 4).await?.into_iter();
+        let buffer = read_bytes(stream, size as usize - 4).await?;
+        Self::try_decode_body(buffer)
+    }
+
+    /// Decodes a frame from its bytes following the size field.
+    pub(crate) fn try_decode_body(buffer: Vec<u8>) -> Result<Self, AppError> {
+        let mut buffer = buffer.into_iter();
         let doff = buffer
             .next()
             .ok_or(AmqpError::DecodeError)?;
@@ -39,6 +95,14 @@ impl Frame {
             .next()
             .ok_or(AmqpError::DecodeError)?;
         match frame_type {
+            AMQP_FRAME if is_empty(doff, &buffer) => {
+                let channel = u16::from_be_bytes(read_bytes_2(&mut buffer)?);
+                let extended_header = read_extended_header(doff, &mut buffer)?;
+                Ok(Frame::Empty {
+                    channel,
+                    extended_header,
+                })
+            }
             AMQP_FRAME => AmqpFrame::try_decode(doff, &mut buffer).map(Frame::AmqpFrame),
             SASL_FRAME => SaslFrame::try_decode(doff, &mut buffer).map(Frame::SaslFrame),
             _ => Err(AmqpError::DecodeError)?
@@ -58,8 +122,159 @@ impl From<SaslFrame> for Frame {
     }
 }
 
+/// Whether the frame ends right after its header, given the bytes following the frame type.
+fn is_empty(doff: u8, buffer: &IntoIter<u8>) -> bool {
+    doff >= 2 && buffer.as_slice().len() + 2 == doff as usize * 4 - 4
+}
+
+/// Checks that an extended header can be encoded: its size must be a multiple of four bytes
+/// and fit into the data offset.
+pub(crate) fn verify_extended_header(extended_header: &[u8]) -> Result<(), AppError> {
+    if !extended_header.len().is_multiple_of(4) || extended_header.len() > MAX_EXTENDED_HEADER_SIZE
+    {
+        Err(AmqpError::InvalidField)?
+    }
+    Ok(())
+}
+
+/// Reads the extended header that sits between the frame header and the frame body.
+/// The stream must be positioned right after the type specific bytes of the frame header.
+pub(crate) fn read_extended_header(
+    doff: u8,
+    stream: &mut IntoIter<u8>,
+) -> Result<Vec<u8>, AppError> {
+    let size = (doff as usize * 4)
+        .checked_sub(FRAME_HEADER_SIZE)
+        .ok_or(ConnectionError::FramingError)?;
+    if stream.as_slice().len() < size {
+        Err(ConnectionError::FramingError)?
+    }
+    Ok(stream.take(size).collect())
+}
+
+/// Prepends the frame header to the frame body. The data offset is derived from the extended header.
+pub(crate) fn encode_frame(
+    frame_type: u8,
+    type_specific: [u8; 2],
+    extended_header: Vec<u8>,
+    body: Vec<u8>,
+) -> Vec<u8> {
+    let doff = ((FRAME_HEADER_SIZE + extended_header.len()) / 4) as u8;
+    let size = (FRAME_HEADER_SIZE + extended_header.len() + body.len()) as u32;
+    let mut data = body;
+    data.prepend(&mut extended_header.clone());
+    data.prepend(&mut type_specific.to_vec());
+    data.prepend(&mut vec![doff, frame_type]);
+    data.prepend(&mut size.to_be_bytes().to_vec());
+    data
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use amqp_type::composite::transport::frame::performative::Performative;
+    use amqp_type::composite::transport::frame::performatives::open::Open;
 
-    //TODO: write tests
+    async fn round_trip(frame: Frame) -> Frame {
+        let encoded = frame.encode();
+        let mut stream = Box::pin(tokio_stream::iter(encoded));
+        Frame::try_decode(&mut stream).await.unwrap()
+    }
+
+    #[test]
+    fn test_encode_empty_frame() {
+        assert_eq!(
+            Frame::heartbeat().encode(),
+            vec![0, 0, 0, 8, 2, AMQP_FRAME, 0, 0]
+        );
+        assert_eq!(
+            Frame::empty(5).encode(),
+            vec![0, 0, 0, 8, 2, AMQP_FRAME, 0, 5]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_encode_decode_round_trip_empty_frame() {
+        assert_eq!(round_trip(Frame::heartbeat()).await, Frame::heartbeat());
+        assert_eq!(round_trip(Frame::empty(7)).await, Frame::empty(7));
+        let frame = Frame::empty_with_extended_header(7, vec![1, 2, 3, 4]).unwrap();
+        assert_eq!(round_trip(frame.clone()).await, frame);
+    }
+
+    #[test]
+    fn test_empty_frame_extended_header_must_be_whole_words() {
+        assert!(Frame::empty_with_extended_header(0, vec![1, 2, 3]).is_err());
+        assert_eq!(
+            Frame::empty_with_extended_header(2, vec![9; 4])
+                .unwrap()
+                .encode(),
+            vec![0, 0, 0, 12, 3, AMQP_FRAME, 0, 2, 9, 9, 9, 9]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decode_empty_frame_with_extended_header() {
+        let encoded = vec![0, 0, 0, 12, 3, AMQP_FRAME, 0, 1, 9, 9, 9, 9];
+        let mut stream = Box::pin(tokio_stream::iter(encoded));
+        assert_eq!(
+            Frame::try_decode(&mut stream).await.unwrap(),
+            Frame::Empty {
+                channel: 1,
+                extended_header: vec![9, 9, 9, 9]
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_empty_frame_is_distinct_from_performative_frame() {
+        let frame: Frame = AmqpFrame::new(0, Performative::Open(Open::new("c".to_string()))).into();
+        assert_eq!(round_trip(frame.clone()).await, frame);
+        assert_ne!(frame, Frame::heartbeat());
+    }
+
+    #[test]
+    fn test_empty_sasl_frame_is_not_a_heartbeat() {
+        let encoded = SaslFrame::new(Vec::new()).encode();
+        assert_eq!(
+            Frame::try_decode_body(encoded[4..].to_vec()).unwrap(),
+            Frame::SaslFrame(SaslFrame::new(Vec::new()))
+        );
+    }
+
+    #[test]
+    fn test_read_extended_header() {
+        let mut stream = vec![1, 2, 3, 4, 5].into_iter();
+        assert_eq!(
+            read_extended_header(3, &mut stream).unwrap(),
+            vec![1, 2, 3, 4]
+        );
+        assert_eq!(stream.collect::<Vec<u8>>(), vec![5]);
+    }
+
+    #[test]
+    fn test_read_extended_header_rejects_doff_below_two() {
+        for doff in [0, 1] {
+            assert!(matches!(
+                read_extended_header(doff, &mut vec![0; 16].into_iter()),
+                Err(AppError::Connection(ConnectionError::FramingError))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_read_extended_header_rejects_doff_past_frame() {
+        assert!(matches!(
+            read_extended_header(4, &mut vec![0; 7].into_iter()),
+            Err(AppError::Connection(ConnectionError::FramingError))
+        ));
+    }
+
+    #[test]
+    fn test_verify_extended_header() {
+        assert!(verify_extended_header(&[]).is_ok());
+        assert!(verify_extended_header(&[0; 4]).is_ok());
+        assert!(verify_extended_header(&[0; MAX_EXTENDED_HEADER_SIZE]).is_ok());
+        assert!(verify_extended_header(&[0; 3]).is_err());
+        assert!(verify_extended_header(&[0; MAX_EXTENDED_HEADER_SIZE + 4]).is_err());
+    }
 }
@@ -1,25 +1,96 @@
+use crate::constants::SASL_FRAME;
+use crate::frame::{encode_frame, read_extended_header, verify_extended_header};
 use amqp_type::error::AppError;
+use amqp_type::utils::sync_util::read_bytes_2;
 use std::vec::IntoIter;
 
-pub struct SaslFrame {}
+/// # SASL Frame
+/// A frame of the SASL layer.
+///
+/// The body holds the encoded SASL performative. The type specific bytes of the frame header
+/// are ignored on receipt and sent as zero. Like on [`AmqpFrame`](crate::frame::amqp_frame::AmqpFrame),
+/// the extended header is preserved but not interpreted.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SaslFrame {
+    extended_header: Vec<u8>,
+    body: Vec<u8>,
+}
 
 impl SaslFrame {
+    pub fn new(body: Vec<u8>) -> Self {
+        SaslFrame {
+            extended_header: Vec::new(),
+            body,
+        }
+    }
+
+    /// Sets the extended header. Its size must be a multiple of four bytes, at most
+    /// [`MAX_EXTENDED_HEADER_SIZE`](crate::frame::MAX_EXTENDED_HEADER_SIZE).
+    pub fn with_extended_header(mut self, extended_header: Vec<u8>) -> Result<Self, AppError> {
+        verify_extended_header(&extended_header)?;
+        self.extended_header = extended_header;
+        Ok(self)
+    }
+
+    pub fn extended_header(&self) -> &[u8] {
+        &self.extended_header
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
     pub(crate) fn encode(self) -> Vec<u8> {
-        todo!()
+        encode_frame(SASL_FRAME, [0, 0], self.extended_header, self.body)
     }
 }
 
 impl SaslFrame {
-    pub fn try_decode(_doff: u8, _stream: &mut IntoIter<u8>) -> Result<Self, AppError>
+    pub fn try_decode(doff: u8, stream: &mut IntoIter<u8>) -> Result<Self, AppError>
     where
         Self: Sized,
     {
-        todo!()
+        read_bytes_2(stream)?;
+        let extended_header = read_extended_header(doff, stream)?;
+        Ok(SaslFrame {
+            extended_header,
+            body: stream.collect(),
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use amqp_type::error::connection_error::ConnectionError;
+
+    fn round_trip(frame: SaslFrame) -> SaslFrame {
+        let mut encoded = frame.encode().into_iter();
+        let header: Vec<u8> = encoded.by_ref().take(6).collect();
+        assert_eq!(header[5], SASL_FRAME);
+        SaslFrame::try_decode(header[4], &mut encoded).unwrap()
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip_sasl_frame() {
+        let frame = SaslFrame::new(vec![0x00, 0x53, 0x40, 0x45]);
+        assert_eq!(round_trip(frame.clone()), frame);
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip_extended_header() {
+        let frame = SaslFrame::new(vec![1, 2])
+            .with_extended_header(vec![9; 12])
+            .unwrap();
+        assert_eq!(frame.clone().encode()[4], 5);
+        assert_eq!(round_trip(frame.clone()), frame);
+    }
 
-    //TODO: write tests
+    #[test]
+    fn test_decode_rejects_doff_below_two() {
+        assert!(matches!(
+            SaslFrame::try_decode(1, &mut vec![0, 0, 1, 2, 3, 4].into_iter()),
+            Err(AppError::Connection(ConnectionError::FramingError))
+        ));
+    }
 }
@@ -1,5 +1,14 @@
+pub mod allocator;
+pub mod client;
 pub mod connection;
 pub mod constants;
+mod driver;
+pub mod error;
 pub mod frame;
 pub mod link;
+pub mod memory;
+pub mod protocol_header;
+pub mod server;
 pub mod session;
+pub mod tls;
+pub mod websocket;
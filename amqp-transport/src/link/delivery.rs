@@ -0,0 +1,196 @@
+use amqp_type::composite::messaging::delivery_state::DeliveryState;
+use amqp_type::composite::transport::frame::performatives::transfer::Transfer;
+use amqp_type::error::link_error::LinkError;
+use amqp_type::error::AppError;
+use amqp_type::primitive::compound::map::Map;
+use amqp_type::primitive::Primitive;
+use amqp_type::restricted::delivery_number::DeliveryNumber;
+use amqp_type::restricted::delivery_tag::DeliveryTag;
+use amqp_type::restricted::receiver_settle_mode::ReceiverSettleMode;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::oneshot;
+
+/// # Unsettled Delivery
+/// What a link remembers about a delivery that is not settled yet. The link keys it by the
+/// delivery tag.
+///
+/// The sender learns the delivery-id once the session sends the first transfer frame; the
+/// receiver takes it from that frame. A sender also keeps the fields and payload of a message
+/// it sent, so that it can send it again when the link is resumed.
+#[derive(Debug)]
+pub struct UnsettledDelivery {
+    delivery_id: Option<DeliveryNumber>,
+    rcv_settle_mode: ReceiverSettleMode,
+    batchable: bool,
+    local_state: Option<DeliveryState>,
+    remote_state: Option<DeliveryState>,
+    message: Option<(Transfer, Vec<u8>)>,
+    outcome: Option<oneshot::Sender<Option<DeliveryState>>>,
+}
+
+impl UnsettledDelivery {
+    pub(crate) fn new(
+        delivery_id: Option<DeliveryNumber>,
+        rcv_settle_mode: ReceiverSettleMode,
+        batchable: bool,
+    ) -> Self {
+        UnsettledDelivery {
+            delivery_id,
+            rcv_settle_mode,
+            batchable,
+            local_state: None,
+            remote_state: None,
+            message: None,
+            outcome: None,
+        }
+    }
+
+    pub fn delivery_id(&self) -> Option<DeliveryNumber> {
+        self.delivery_id
+    }
+
+    /// Whether the receiver settles first (`first`) or only after the sender did (`second`).
+    pub fn rcv_settle_mode(&self) -> ReceiverSettleMode {
+        self.rcv_settle_mode
+    }
+
+    /// Whether the transfer said that updates to its state are not urgent, so that the
+    /// dispositions for it may be batched.
+    pub fn batchable(&self) -> bool {
+        self.batchable
+    }
+
+    pub fn local_state(&self) -> Option<&DeliveryState> {
+        self.local_state.as_ref()
+    }
+
+    pub fn remote_state(&self) -> Option<&DeliveryState> {
+        self.remote_state.as_ref()
+    }
+
+    pub(crate) fn set_delivery_id(&mut self, delivery_id: DeliveryNumber) {
+        self.delivery_id = Some(delivery_id);
+    }
+
+    pub(crate) fn clear_delivery_id(&mut self) {
+        self.delivery_id = None;
+    }
+
+    /// The state to report in the unsettled map of an attach: our own if we have one,
+    /// otherwise the last one the peer reported.
+    pub fn state(&self) -> Option<&DeliveryState> {
+        self.local_state.as_ref().or(self.remote_state.as_ref())
+    }
+
+    pub(crate) fn set_local_state(&mut self, state: DeliveryState) {
+        self.local_state = Some(state);
+    }
+
+    pub(crate) fn set_remote_state(&mut self, state: Option<DeliveryState>) {
+        if state.is_some() {
+            self.remote_state = state;
+        }
+    }
+
+    pub(crate) fn set_message(&mut self, transfer: Transfer, payload: Vec<u8>) {
+        self.message = Some((transfer, payload));
+    }
+
+    pub(crate) fn message(&self) -> Option<&(Transfer, Vec<u8>)> {
+        self.message.as_ref()
+    }
+
+    /// Creates the future that resolves once the delivery is settled.
+    pub(crate) fn outcome(&mut self) -> DeliveryFuture {
+        let (sender, receiver) = oneshot::channel();
+        self.outcome = Some(sender);
+        DeliveryFuture { receiver }
+    }
+
+    /// Resolves the future of the delivery, if one was created, with its final state: the
+    /// outcome the peer reported, else our own outcome, else whatever state is known.
+    pub(crate) fn settle(mut self) -> Option<DeliveryState> {
+        let remote = self.remote_state.take();
+        let local = self.local_state.take();
+        let state = match (remote, local) {
+            (Some(remote), _) if is_outcome(&remote) => Some(remote),
+            (_, Some(local)) if is_outcome(&local) => Some(local),
+            (remote, local) => remote.or(local),
+        };
+        if let Some(outcome) = self.outcome.take() {
+            // Nobody may be waiting for the outcome anymore.
+            let _ = outcome.send(state.clone());
+        }
+        state
+    }
+}
+
+/// # Delivery Future
+/// Resolves with the state a delivery was settled with, usually the outcome the receiver
+/// reported (`accepted`, `rejected`, `released` or `modified`). A delivery sent settled
+/// resolves right away without a state.
+///
+/// If the link goes away before the delivery is settled, the future fails with
+/// `amqp:link:detach-forced`.
+#[derive(Debug)]
+pub struct DeliveryFuture {
+    receiver: oneshot::Receiver<Option<DeliveryState>>,
+}
+
+impl DeliveryFuture {
+    /// A future for a delivery that was settled when it was sent.
+    pub(crate) fn settled() -> Self {
+        let (sender, receiver) = oneshot::channel();
+        let _ = sender.send(None);
+        DeliveryFuture { receiver }
+    }
+}
+
+impl Future for DeliveryFuture {
+    type Output = Result<Option<DeliveryState>, AppError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.receiver)
+            .poll(cx)
+            .map(|result| result.map_err(|_| LinkError::DetachForced.into()))
+    }
+}
+
+/// Whether a state is an outcome, i.e. a terminal state after which the delivery can be settled.
+pub fn is_outcome(state: &DeliveryState) -> bool {
+    !matches!(state, DeliveryState::Received(_))
+}
+
+/// Builds the unsettled map of an attach, which maps each delivery tag to the state of the
+/// delivery, or to null if it has none.
+pub fn encode_unsettled<'a>(
+    deliveries: impl IntoIterator<Item = (&'a DeliveryTag, Option<&'a DeliveryState>)>,
+) -> Map {
+    let mut map = Map::default();
+    for (tag, state) in deliveries {
+        let state = match state {
+            Some(state) => state.clone().into(),
+            None => Primitive::Null,
+        };
+        map.insert(tag.clone(), state);
+    }
+    map
+}
+
+/// Reads the unsettled map of an attach.
+pub fn decode_unsettled(
+    map: &Map,
+) -> Result<HashMap<DeliveryTag, Option<DeliveryState>>, AppError> {
+    map.inner()
+        .iter()
+        .map(|(tag, state)| {
+            Ok((
+                DeliveryTag::try_from(tag.clone())?,
+                Option::<DeliveryState>::try_from(state.clone())?,
+            ))
+        })
+        .collect()
+}
@@ -1 +1,2101 @@
+pub mod delivery;
 
+use crate::constants::{FRAME_HEADER_SIZE, MIN_MAX_FRAME_SIZE};
+use crate::link::delivery::{
+    decode_unsettled, encode_unsettled, is_outcome, DeliveryFuture, UnsettledDelivery,
+};
+use amqp_type::composite::messaging::delivery_state::DeliveryState;
+use amqp_type::composite::transport::frame::performative::Performative;
+use amqp_type::composite::transport::frame::performatives::attach::Attach;
+use amqp_type::composite::transport::frame::performatives::detach::Detach;
+use amqp_type::composite::transport::frame::performatives::disposition::Disposition;
+use amqp_type::composite::transport::frame::performatives::flow::Flow;
+use amqp_type::composite::transport::frame::performatives::transfer::Transfer;
+use amqp_type::composite::transport::transport::error::Error;
+use amqp_type::error::amqp_error::AmqpError;
+use amqp_type::error::link_error::LinkError;
+use amqp_type::error::AppError;
+use amqp_type::restricted::delivery_number::DeliveryNumber;
+use amqp_type::restricted::delivery_tag::DeliveryTag;
+use amqp_type::restricted::handle::Handle;
+use amqp_type::restricted::receiver_settle_mode::ReceiverSettleMode;
+use amqp_type::restricted::role::Role;
+use amqp_type::restricted::sender_settle_mode::SenderSettleMode;
+use amqp_type::restricted::sequence_no::{SequenceNumber, HALF_MAX};
+use std::collections::{HashMap, VecDeque};
+
+/// # Link State
+/// The states of a link endpoint, following the attach and detach exchange of spec section 2.6.
+/// ```text
+/// DETACHED    --S:ATTACH--> ATTACH_SENT   DETACHED    --R:ATTACH--> ATTACH_RCVD
+/// ATTACH_SENT --R:ATTACH--> ATTACHED      ATTACH_RCVD --S:ATTACH--> ATTACHED
+/// ATTACHED    --S:DETACH--> DETACH_SENT   ATTACHED    --R:DETACH--> DETACH_RCVD
+/// DETACH_SENT --R:DETACH--> DETACHED      DETACH_RCVD --S:DETACH--> DETACHED
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkState {
+    Detached,
+    AttachSent,
+    AttachRcvd,
+    Attached,
+    DetachSent,
+    DetachRcvd,
+}
+
+/// Something that happened on the link that the application needs to know about.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkEvent {
+    /// The peer attached its side of the link.
+    Attached(Attach),
+    /// The peer detached its side of the link. A closing detach ends the link for good.
+    Detached { closed: bool, error: Option<Error> },
+    /// The peer sent the state of its side of the link. A sender has been granted new credit.
+    Flow(Flow),
+    /// The sender used up all credit the receiver asked it to drain.
+    Drained,
+    /// A delivery arrived on a receiving link. The payload is reassembled from all its
+    /// transfer frames, and the transfer carries the fields of the delivery.
+    Delivery(Transfer, Vec<u8>),
+    /// The delivery with the given tag is settled, with the final state of the delivery.
+    Settled {
+        tag: DeliveryTag,
+        state: Option<DeliveryState>,
+    },
+    /// The sender aborted the delivery with the given tag. Whatever arrived of it is discarded.
+    Aborted { tag: DeliveryTag },
+}
+
+/// # Link Endpoint
+/// The state machine of one end of a link, without any I/O.
+///
+/// The role of the local attach decides whether the endpoint is a sender or a receiver.
+/// Both keep the flow control state of spec section 2.6.7:
+/// ```text
+/// delivery-count   the number of deliveries the sender has sent (a sequence-no)
+/// link-credit      the number of deliveries the sender may send
+/// available        the number of deliveries the sender has waiting
+/// drain            the receiver asks the sender to use up its credit
+/// ```
+/// The sender owns the delivery-count and the available count, the receiver owns the
+/// link-credit and drain flag; each side learns the other's values from [`Flow`] frames.
+/// A delivery spanning several transfer frames consumes one credit.
+///
+/// A message larger than the max-frame-size of the connection is split across several transfer
+/// frames with `more` set on all but the last ([`LinkEndpoint::send_message`]), and the frames of
+/// a delivery are put back together on receipt. Both directions enforce the max-message-size of
+/// the receiving end with `amqp:link:message-size-exceeded`.
+///
+/// A sender may give up on a delivery it has not finished sending with
+/// [`LinkEndpoint::abort`]. The receiver discards the frames it has of the delivery and reports
+/// [`LinkEvent::Aborted`]. An aborted delivery is settled, and it still used up its credit and
+/// advanced the delivery-count on both ends.
+///
+/// Deliveries that are not settled when they are sent are kept in the unsettled map, keyed by
+/// their delivery tag, until both ends have settled them (spec section 2.6.12). The
+/// rcv-settle-mode decides who settles first:
+/// ```text
+/// first    the receiver settles when it reports the outcome, the sender follows
+/// second   the receiver reports the outcome unsettled, the sender settles, and the
+///          receiver settles once it learns that the sender did
+/// ```
+/// The snd-settle-mode of the sender decides whether deliveries are sent settled (`settled`),
+/// unsettled (`unsettled`) or either way as the transfer says (`mixed`). The sender's
+/// [`DeliveryFuture`] resolves with the outcome once the delivery is settled.
+///
+/// A link detached without closing it keeps its unsettled deliveries and can be attached again
+/// with [`LinkEndpoint::resume`]. Both ends then send their unsettled map in the attach, and
+/// reconcile it with the peer's as in spec section 3.4.6:
+/// ```text
+///     sender      receiver    recovery
+/// A   -           -           nothing to do
+/// B   -           null        the receiver settles
+/// C   -           terminal    the receiver settles
+/// D   null        -           the sender resends the delivery with resume = true
+/// E   null        null        the sender resends the delivery with resume = true
+/// F   null        terminal    the sender settles with the receiver's outcome, resume = true
+/// G   terminal    -           the sender settles
+/// H   terminal    null        the sender settles with its own outcome, resume = true
+/// I   terminal    terminal    the sender settles with the receiver's outcome, resume = true
+/// ```
+/// A delivery the sender cannot send again is aborted. An unsettled map that does not fit into
+/// one frame is cut short and marked `incomplete-unsettled`; deliveries missing from such a map
+/// are resent rather than settled, and no new deliveries may be sent until the link is attached
+/// with a complete map again.
+///
+/// The link runs inside a [`SessionEndpoint`](crate::session::SessionEndpoint), which routes the
+/// frames and fills in the session fields of the flows the link sends.
+#[derive(Debug)]
+pub struct LinkEndpoint {
+    state: LinkState,
+    local_attach: Attach,
+    remote_attach: Option<Attach>,
+    delivery_count: SequenceNumber,
+    link_credit: u32,
+    available: u32,
+    drain: bool,
+    incomplete: bool,
+    /// Sender: the tag of the delivery being sent.
+    sending: Option<DeliveryTag>,
+    partial: Option<(Transfer, Vec<u8>)>,
+    max_frame_size: u32,
+    closed: bool,
+    unsettled: HashMap<DeliveryTag, UnsettledDelivery>,
+    /// Only resumed deliveries may be transferred, as one end's unsettled map was incomplete.
+    resume_only: bool,
+    /// Resumed deliveries waiting for credit.
+    resuming: VecDeque<(Transfer, Vec<u8>)>,
+    /// Frames to send; the flag marks the first transfer frame of a delivery.
+    transmit: VecDeque<(Performative, Vec<u8>, bool)>,
+    events: VecDeque<LinkEvent>,
+}
+
+impl LinkEndpoint {
+    /// Creates a detached link. The handle of `local_attach` is the local handle of the link.
+    pub fn new(local_attach: Attach) -> Self {
+        LinkEndpoint {
+            state: LinkState::Detached,
+            delivery_count: local_attach.initial_delivery_count().unwrap_or_default(),
+            local_attach,
+            remote_attach: None,
+            link_credit: 0,
+            available: 0,
+            drain: false,
+            incomplete: false,
+            sending: None,
+            partial: None,
+            max_frame_size: MIN_MAX_FRAME_SIZE,
+            closed: false,
+            unsettled: HashMap::new(),
+            resume_only: false,
+            resuming: VecDeque::new(),
+            transmit: VecDeque::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn state(&self) -> LinkState {
+        self.state
+    }
+
+    pub fn name(&self) -> &str {
+        self.local_attach.name()
+    }
+
+    pub fn handle(&self) -> Handle {
+        self.local_attach.handle()
+    }
+
+    pub fn remote_handle(&self) -> Option<Handle> {
+        self.remote_attach.as_ref().map(Attach::handle)
+    }
+
+    pub fn role(&self) -> Role {
+        self.local_attach.role()
+    }
+
+    pub fn local_attach(&self) -> &Attach {
+        &self.local_attach
+    }
+
+    pub fn remote_attach(&self) -> Option<&Attach> {
+        self.remote_attach.as_ref()
+    }
+
+    pub fn delivery_count(&self) -> SequenceNumber {
+        self.delivery_count
+    }
+
+    pub fn link_credit(&self) -> u32 {
+        self.link_credit
+    }
+
+    pub fn available(&self) -> u32 {
+        self.available
+    }
+
+    pub fn drain(&self) -> bool {
+        self.drain
+    }
+
+    pub fn max_frame_size(&self) -> u32 {
+        self.max_frame_size
+    }
+
+    /// Whether either end sent a closing detach, after which the link cannot be resumed.
+    pub fn closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Sets the largest frame the peer accepts, as negotiated on the connection.
+    /// No peer may accept less than [`MIN_MAX_FRAME_SIZE`].
+    pub fn set_max_frame_size(&mut self, max_frame_size: u32) -> Result<(), AppError> {
+        if max_frame_size < MIN_MAX_FRAME_SIZE {
+            Err(AmqpError::FrameSizeTooSmall)?
+        }
+        self.max_frame_size = max_frame_size;
+        Ok(())
+    }
+
+    /// The deliveries that are not settled yet, by delivery tag.
+    pub fn unsettled(&self) -> &HashMap<DeliveryTag, UnsettledDelivery> {
+        &self.unsettled
+    }
+
+    /// Polls the next frame to send. The first transfer frame of a delivery takes the
+    /// delivery-id `next_delivery_id`, which the session counts across all its links.
+    pub(crate) fn poll_transmit(
+        &mut self,
+        next_delivery_id: &mut DeliveryNumber,
+    ) -> Option<(Performative, Vec<u8>)> {
+        let (performative, payload, first) = self.transmit.pop_front()?;
+        match performative {
+            Performative::Transfer(transfer) if first => {
+                let delivery_id = *next_delivery_id;
+                *next_delivery_id += 1.into();
+                if let Some(delivery) = transfer
+                    .delivery_tag()
+                    .and_then(|tag| self.unsettled.get_mut(tag))
+                {
+                    delivery.set_delivery_id(delivery_id);
+                }
+                Some((
+                    Performative::Transfer(transfer.with_delivery_id(delivery_id)),
+                    payload,
+                ))
+            }
+            performative => Some((performative, payload)),
+        }
+    }
+
+    pub(crate) fn poll_event(&mut self) -> Option<LinkEvent> {
+        self.events.pop_front()
+    }
+
+    pub(crate) fn has_transmit(&self) -> bool {
+        !self.transmit.is_empty()
+    }
+
+    /// The flow carrying the link state. Its session fields are filled in by the session.
+    pub fn flow(&self) -> Flow {
+        let flow = Flow::new(0.into(), 0, 0.into(), 0)
+            .with_handle(self.handle())
+            .with_delivery_count(self.delivery_count)
+            .with_link_credit(self.link_credit)
+            .with_drain(self.drain);
+        match self.role() {
+            Role::Sender => flow.with_available(self.available),
+            Role::Receiver => flow,
+        }
+    }
+
+    /// Sends the attach frame, answering the peer's attach if it attached the link.
+    pub fn send_attach(&mut self) -> Result<(), AppError> {
+        self.state = match self.state {
+            LinkState::Detached => LinkState::AttachSent,
+            LinkState::AttachRcvd => LinkState::Attached,
+            _ => Err(AmqpError::IllegalState)?,
+        };
+        let mut attach = self.local_attach.clone();
+        if self.role() == Role::Sender {
+            attach = attach.with_initial_delivery_count(self.delivery_count);
+        }
+        if !self.unsettled.is_empty() {
+            attach = self.with_unsettled(attach);
+        }
+        if self.role() == Role::Receiver {
+            self.resume_only = attach.incomplete_unsettled();
+        }
+        self.push(Performative::Attach(attach));
+        if self.state == LinkState::Attached {
+            self.recover()?;
+        }
+        Ok(())
+    }
+
+    /// Adds the unsettled map to an attach. Deliveries that do not fit into one frame are left
+    /// out, and the map is marked incomplete.
+    fn with_unsettled(&self, attach: Attach) -> Attach {
+        let mut deliveries: Vec<_> = self.unsettled.iter().collect();
+        deliveries.sort_by_key(|(_, delivery)| delivery.delivery_id().map(|id| id.inner()));
+        let mut count = deliveries.len();
+        loop {
+            let unsettled = encode_unsettled(
+                deliveries[..count]
+                    .iter()
+                    .map(|(tag, delivery)| (*tag, delivery.state())),
+            );
+            let mut candidate = attach.clone().with_unsettled(unsettled);
+            if count < deliveries.len() {
+                candidate = candidate.with_incomplete_unsettled(true);
+            }
+            let size = FRAME_HEADER_SIZE + Performative::Attach(candidate.clone()).encode().len();
+            if count == 0 || size <= self.max_frame_size as usize {
+                return candidate;
+            }
+            count /= 2;
+        }
+    }
+
+    /// Refuses a link the peer attached: answers its attach without a source or target, as
+    /// spec section 2.6.3 asks, and closes the link right away with the error.
+    pub fn refuse(&mut self, error: Error) -> Result<(), AppError> {
+        if self.state != LinkState::AttachRcvd {
+            Err(AmqpError::IllegalState)?
+        }
+        self.local_attach = Attach::new(self.name().to_string(), self.handle(), self.role());
+        self.send_attach()?;
+        self.send_detach(true, Some(error))
+    }
+
+    /// Prepares a link that was detached without closing it to be attached again with the
+    /// given handle, e.g. on a new session. The unsettled deliveries are kept, and reconciled
+    /// with those of the peer once both ends have attached.
+    pub fn resume(&mut self, handle: Handle) -> Result<(), AppError> {
+        if self.state != LinkState::Detached || self.closed {
+            Err(AmqpError::IllegalState)?
+        }
+        self.local_attach = self.local_attach.clone().with_handle(handle);
+        self.remote_attach = None;
+        self.link_credit = 0;
+        self.drain = false;
+        self.incomplete = false;
+        self.sending = None;
+        self.partial = None;
+        self.resume_only = false;
+        self.resuming.clear();
+        self.transmit.clear();
+        Ok(())
+    }
+
+    /// Sends the detach frame. A closing detach ends the link, otherwise it can be attached
+    /// again later, e.g. to resume it on another connection.
+    pub fn send_detach(&mut self, closed: bool, error: Option<Error>) -> Result<(), AppError> {
+        self.state = match self.state {
+            LinkState::AttachSent | LinkState::Attached => LinkState::DetachSent,
+            LinkState::DetachRcvd => LinkState::Detached,
+            _ => Err(AmqpError::IllegalState)?,
+        };
+        let mut detach = Detach::new(self.handle()).with_closed(closed);
+        if let Some(error) = error {
+            detach = detach.with_error(error);
+        }
+        self.push(Performative::Detach(detach));
+        self.closed |= closed;
+        self.forget_if_closed();
+        Ok(())
+    }
+
+    /// Sends the link state, asking the peer to answer with its own if `echo` is set.
+    pub fn send_flow(&mut self, echo: bool) -> Result<(), AppError> {
+        if self.state != LinkState::Attached {
+            Err(AmqpError::IllegalState)?
+        }
+        let flow = match echo {
+            true => self.flow().with_echo(true),
+            false => self.flow(),
+        };
+        self.push(Performative::Flow(flow));
+        Ok(())
+    }
+
+    /// Receiver: grants the sender `link_credit` deliveries, counted from the current delivery-count.
+    pub fn grant_credit(&mut self, link_credit: u32) -> Result<(), AppError> {
+        self.require_role(Role::Receiver)?;
+        self.link_credit = link_credit;
+        self.send_flow(false)
+    }
+
+    /// Receiver: asks the sender to use up all credit, sending what it has and advancing the
+    /// delivery-count for the rest. [`LinkEvent::Drained`] follows once it has done so.
+    pub fn request_drain(&mut self) -> Result<(), AppError> {
+        self.require_role(Role::Receiver)?;
+        self.drain = true;
+        self.send_flow(false)
+    }
+
+    /// Sender: tells the receiver how many deliveries are waiting for credit.
+    pub fn set_available(&mut self, available: u32) -> Result<(), AppError> {
+        self.require_role(Role::Sender)?;
+        self.available = available;
+        self.send_flow(false)
+    }
+
+    /// Sender: answers a drain request by using up the remaining credit.
+    pub fn drained(&mut self) -> Result<(), AppError> {
+        self.require_role(Role::Sender)?;
+        self.delivery_count += self.link_credit.into();
+        self.link_credit = 0;
+        self.available = 0;
+        self.send_flow(false)
+    }
+
+    /// Sender: sends a transfer frame. The first frame of a delivery consumes one credit;
+    /// without credit the transfer fails with `amqp:link:transfer-limit-exceeded`.
+    ///
+    /// The first frame must carry a delivery tag that no unsettled delivery uses. Whether the
+    /// delivery is sent settled follows the snd-settle-mode; an unsettled delivery is added to
+    /// the unsettled map.
+    pub fn send_transfer(&mut self, transfer: Transfer, payload: Vec<u8>) -> Result<(), AppError> {
+        self.require_role(Role::Sender)?;
+        if self.state != LinkState::Attached {
+            Err(AmqpError::IllegalState)?
+        }
+        let first = !self.incomplete;
+        let mut transfer = transfer.with_handle(self.handle());
+        if first {
+            // A resumed delivery keeps its tag and its place in the unsettled map.
+            let tag = match transfer.delivery_tag() {
+                Some(tag) if transfer.resume() || !self.unsettled.contains_key(tag) => tag.clone(),
+                _ => Err(AmqpError::InvalidField)?,
+            };
+            if self.resume_only && !transfer.resume() {
+                Err(AmqpError::NotAllowed)?
+            }
+            let settled = match (self.local_attach.snd_settle_mode(), transfer.settled()) {
+                // A resumed delivery settles as the recovery decided.
+                (_, Some(settled)) if transfer.resume() => settled,
+                (SenderSettleMode::Settled, Some(false))
+                | (SenderSettleMode::Unsettled, Some(true)) => Err(AmqpError::NotAllowed)?,
+                (SenderSettleMode::Settled, _) => true,
+                (_, settled) => settled.unwrap_or(false),
+            };
+            if self.link_credit == 0 {
+                Err(LinkError::TransferLimitExceeded)?
+            }
+            self.delivery_count += 1.into();
+            self.link_credit -= 1;
+            self.available = self.available.saturating_sub(1);
+            transfer = transfer.with_settled(settled);
+            if !settled && !self.unsettled.contains_key(&tag) {
+                let rcv_settle_mode = transfer
+                    .rcv_settle_mode()
+                    .unwrap_or(self.remote_rcv_settle_mode());
+                let delivery = UnsettledDelivery::new(None, rcv_settle_mode, transfer.batchable());
+                self.unsettled.insert(tag.clone(), delivery);
+            }
+            self.sending = Some(tag);
+        }
+        self.incomplete = transfer.more() && !transfer.aborted();
+        if transfer.aborted() {
+            // An aborted delivery is settled without a state.
+            let delivery = self
+                .sending
+                .take()
+                .and_then(|tag| self.unsettled.remove(&tag));
+            if let Some(delivery) = delivery {
+                delivery.settle();
+            }
+        } else if !self.incomplete {
+            self.sending = None;
+        }
+        self.transmit
+            .push_back((Performative::Transfer(transfer), payload, first));
+        Ok(())
+    }
+
+    /// Sender: aborts the delivery being sent, i.e. one whose last transfer frame had `more`
+    /// set. The receiver discards what it got of it; the credit it used up stays used.
+    pub fn abort(&mut self) -> Result<(), AppError> {
+        self.require_role(Role::Sender)?;
+        if !self.incomplete {
+            Err(AmqpError::IllegalState)?
+        }
+        self.send_transfer(Transfer::new(self.handle()).with_aborted(true), Vec::new())
+    }
+
+    /// Sender: sends a message as one delivery, split across as many transfer frames as the
+    /// max-frame-size requires. `transfer` holds the fields of the delivery.
+    ///
+    /// The returned future resolves once the delivery is settled. Until then the link keeps
+    /// the message, so that it can send it again if the link is resumed.
+    pub fn send_message(
+        &mut self,
+        transfer: Transfer,
+        payload: Vec<u8>,
+    ) -> Result<DeliveryFuture, AppError> {
+        self.require_role(Role::Sender)?;
+        let max_message_size = self
+            .remote_attach
+            .as_ref()
+            .and_then(Attach::max_message_size);
+        if max_message_size.is_some_and(|max| max > 0 && payload.len() as u64 > max) {
+            Err(LinkError::MessageSizeExceeded)?
+        }
+        let tag = transfer.delivery_tag().cloned();
+        let settled = transfer.settled() == Some(true)
+            || self.local_attach.snd_settle_mode() == SenderSettleMode::Settled;
+        let message = (!settled).then(|| (transfer.clone(), payload.clone()));
+        self.send_delivery(transfer, payload)?;
+        if let Some((transfer, payload)) = message {
+            if let Some(delivery) = tag.as_ref().and_then(|tag| self.unsettled.get_mut(tag)) {
+                delivery.set_message(transfer, payload);
+            }
+        }
+        Ok(self.outcome(tag))
+    }
+
+    /// Sends one delivery, split across as many transfer frames as the max-frame-size requires.
+    fn send_delivery(&mut self, transfer: Transfer, payload: Vec<u8>) -> Result<(), AppError> {
+        let more = transfer.more();
+        // The session numbers the delivery later; leave room for the longest delivery-id.
+        let transfer = transfer
+            .with_handle(self.handle())
+            .with_delivery_id(u32::MAX.into());
+        if payload.len() <= self.payload_budget(&transfer)? {
+            return self.send_transfer(transfer, payload);
+        }
+
+        // Only the first frame carries the fields of the delivery; it is checked against
+        // the credit before anything is queued.
+        let mut frames = Vec::new();
+        let mut remaining = payload.as_slice();
+        let mut next = transfer.with_more(true);
+        loop {
+            let last = next.clone().with_more(more);
+            if remaining.len() <= self.payload_budget(&last)? {
+                frames.push((last, remaining.to_vec()));
+                break;
+            }
+            let (chunk, rest) = remaining.split_at(self.payload_budget(&next)?);
+            frames.push((next, chunk.to_vec()));
+            remaining = rest;
+            next = Transfer::new(self.handle()).with_more(true);
+        }
+        for (frame, chunk) in frames {
+            self.send_transfer(frame, chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Sender: sends the resumed deliveries that are waiting for credit.
+    fn send_resumed(&mut self) -> Result<(), AppError> {
+        while self.link_credit > 0 && self.state == LinkState::Attached {
+            let Some((transfer, payload)) = self.resuming.pop_front() else {
+                break;
+            };
+            self.send_delivery(transfer, payload)?;
+        }
+        Ok(())
+    }
+
+    fn outcome(&mut self, tag: Option<DeliveryTag>) -> DeliveryFuture {
+        match tag.and_then(|tag| self.unsettled.get_mut(&tag)) {
+            Some(delivery) => delivery.outcome(),
+            None => DeliveryFuture::settled(),
+        }
+    }
+
+    /// Receiver: reports the state of a delivery to the sender. An outcome settles the
+    /// delivery right away in rcv-settle-mode `first`; in mode `second` it stays unsettled
+    /// until the sender has settled it.
+    pub fn dispose(&mut self, tag: &DeliveryTag, state: DeliveryState) -> Result<(), AppError> {
+        self.require_role(Role::Receiver)?;
+        let Some(delivery) = self.unsettled.get_mut(tag) else {
+            Err(AmqpError::NotFound)?
+        };
+        let delivery_id = delivery.delivery_id().ok_or(AmqpError::InternalError)?;
+        let settled = delivery.rcv_settle_mode() == ReceiverSettleMode::First && is_outcome(&state);
+        let batchable = delivery.batchable();
+        delivery.set_local_state(state.clone());
+        if settled {
+            self.unsettled.remove(tag);
+        }
+        self.push(Performative::Disposition(
+            Disposition::new(self.role(), delivery_id)
+                .with_settled(settled)
+                .with_state(state)
+                .with_batchable(batchable),
+        ));
+        Ok(())
+    }
+
+    /// Settles a delivery without waiting for the peer, e.g. to give up on it. The peer is
+    /// told with the local state of the delivery.
+    pub fn settle(&mut self, tag: &DeliveryTag) -> Result<(), AppError> {
+        let Some(delivery) = self.unsettled.remove(tag) else {
+            Err(AmqpError::NotFound)?
+        };
+        let delivery_id = delivery.delivery_id();
+        let mut disposition = Disposition::new(self.role(), delivery_id.unwrap_or_default())
+            .with_settled(true)
+            .with_batchable(delivery.batchable());
+        if let Some(state) = delivery.local_state() {
+            disposition = disposition.with_state(state.clone());
+        }
+        delivery.settle();
+        // A delivery the session has not numbered yet is unknown to the peer.
+        if delivery_id.is_some() {
+            self.push(Performative::Disposition(disposition));
+        }
+        Ok(())
+    }
+
+    /// The rcv-settle-mode the receiving end of the link uses.
+    fn remote_rcv_settle_mode(&self) -> ReceiverSettleMode {
+        match self.remote_attach.as_ref() {
+            Some(attach) => attach.rcv_settle_mode(),
+            None => self.local_attach.rcv_settle_mode(),
+        }
+    }
+
+    fn push(&mut self, performative: Performative) {
+        self.transmit.push_back((performative, Vec::new(), false));
+    }
+
+    /// How many payload bytes fit into a frame next to the given transfer.
+    fn payload_budget(&self, transfer: &Transfer) -> Result<usize, AppError> {
+        let overhead = FRAME_HEADER_SIZE + Performative::Transfer(transfer.clone()).encode().len();
+        match (self.max_frame_size as usize).checked_sub(overhead) {
+            Some(budget) if budget > 0 => Ok(budget),
+            _ => Err(AmqpError::FrameSizeTooSmall)?,
+        }
+    }
+
+    /// Handles a frame the peer sent for this link.
+    pub fn on_performative(
+        &mut self,
+        performative: Performative,
+        payload: Vec<u8>,
+    ) -> Result<(), AppError> {
+        match performative {
+            Performative::Attach(attach) => self.on_attach(attach),
+            Performative::Detach(detach) => self.on_detach(detach),
+            Performative::Flow(flow) => self.on_flow(flow),
+            Performative::Transfer(transfer) => self.on_transfer(transfer, payload),
+            Performative::Disposition(disposition) => self.on_disposition(disposition),
+            _ => Err(AmqpError::IllegalState)?,
+        }
+    }
+
+    fn on_attach(&mut self, attach: Attach) -> Result<(), AppError> {
+        self.state = match self.state {
+            LinkState::Detached => LinkState::AttachRcvd,
+            LinkState::AttachSent => LinkState::Attached,
+            _ => Err(AmqpError::IllegalState)?,
+        };
+        if self.role() == Role::Receiver {
+            self.delivery_count = attach.initial_delivery_count().unwrap_or_default();
+        }
+        self.remote_attach = Some(attach.clone());
+        self.events.push_back(LinkEvent::Attached(attach));
+        if self.state == LinkState::Attached {
+            self.recover()?;
+        }
+        Ok(())
+    }
+
+    /// Reconciles the unsettled deliveries with the unsettled map of the peer, once both ends
+    /// have attached. See the table on [`LinkEndpoint`].
+    fn recover(&mut self) -> Result<(), AppError> {
+        let remote = self
+            .remote_attach
+            .as_ref()
+            .ok_or(AmqpError::InternalError)?;
+        let remote_incomplete = remote.incomplete_unsettled();
+        let remote_unsettled = match remote.unsettled() {
+            Some(unsettled) => decode_unsettled(unsettled)?,
+            None => HashMap::new(),
+        };
+        if self.role() == Role::Sender {
+            self.resume_only = remote_incomplete;
+        }
+        let mut tags: Vec<DeliveryTag> = self.unsettled.keys().cloned().collect();
+        tags.sort_by_key(|tag| self.unsettled[tag].delivery_id().map(|id| id.inner()));
+        for tag in tags {
+            let remote_state = remote_unsettled.get(&tag);
+            match self.role() {
+                Role::Sender => self.recover_sent(tag, remote_state, remote_incomplete),
+                // The sender has forgotten the delivery, so it has settled it.
+                Role::Receiver if remote_state.is_none() && !remote_incomplete => {
+                    self.settle_locally(&tag, None)
+                }
+                Role::Receiver => {}
+            }
+        }
+        // The delivery-ids of the previous attachment mean nothing anymore.
+        for delivery in self.unsettled.values_mut() {
+            delivery.clear_delivery_id();
+        }
+        self.send_resumed()
+    }
+
+    /// Sender: decides what to do with an unsettled delivery given the receiver's state of it,
+    /// `None` if the receiver does not know the delivery.
+    fn recover_sent(
+        &mut self,
+        tag: DeliveryTag,
+        remote_state: Option<&Option<DeliveryState>>,
+        remote_incomplete: bool,
+    ) {
+        let Some(delivery) = self.unsettled.get(&tag) else {
+            return;
+        };
+        let local_outcome = delivery
+            .local_state()
+            .filter(|state| is_outcome(state))
+            .cloned();
+        let remote_outcome = remote_state.cloned().flatten().filter(is_outcome);
+        let message = delivery.message().cloned();
+        let resumed = Transfer::new(self.handle())
+            .with_delivery_tag(tag.clone())
+            .with_resume(true);
+        match (remote_state, remote_outcome.or(local_outcome)) {
+            // The receiver has settled a delivery whose outcome is known.
+            (None, Some(_)) if !remote_incomplete => self.settle_locally(&tag, None),
+            // One end knows the outcome, the receiver's taking precedence; settle with it.
+            (Some(_), Some(outcome)) => {
+                self.resuming.push_back((
+                    resumed.with_settled(true).with_state(outcome.clone()),
+                    Vec::new(),
+                ));
+                self.settle_locally(&tag, Some(outcome));
+            }
+            // Nobody knows the outcome, so the message goes again.
+            _ => match message {
+                Some((transfer, payload)) => self
+                    .resuming
+                    .push_back((transfer.with_resume(true), payload)),
+                None => {
+                    self.resuming
+                        .push_back((resumed.with_settled(true).with_aborted(true), Vec::new()));
+                    self.unsettled.remove(&tag);
+                }
+            },
+        }
+    }
+
+    /// Settles a delivery without telling the peer, taking the state the peer reported.
+    fn settle_locally(&mut self, tag: &DeliveryTag, remote_state: Option<DeliveryState>) {
+        if let Some(mut delivery) = self.unsettled.remove(tag) {
+            delivery.set_remote_state(remote_state);
+            let state = delivery.settle();
+            self.events.push_back(LinkEvent::Settled {
+                tag: tag.clone(),
+                state,
+            });
+        }
+    }
+
+    /// Drops the unsettled deliveries once a closed link is detached, as it cannot be resumed.
+    fn forget_if_closed(&mut self) {
+        if self.state == LinkState::Detached && self.closed {
+            self.unsettled.clear();
+            self.resuming.clear();
+        }
+    }
+
+    fn on_detach(&mut self, detach: Detach) -> Result<(), AppError> {
+        self.state = match self.state {
+            LinkState::AttachSent | LinkState::Attached => LinkState::DetachRcvd,
+            LinkState::DetachSent => LinkState::Detached,
+            _ => Err(AmqpError::IllegalState)?,
+        };
+        self.closed |= detach.closed();
+        self.forget_if_closed();
+        self.events.push_back(LinkEvent::Detached {
+            closed: detach.closed(),
+            error: detach.error().cloned(),
+        });
+        Ok(())
+    }
+
+    fn on_flow(&mut self, flow: Flow) -> Result<(), AppError> {
+        match self.state {
+            LinkState::Attached | LinkState::DetachSent => {}
+            _ => Err(AmqpError::IllegalState)?,
+        }
+        self.events.push_back(LinkEvent::Flow(flow.clone()));
+        match self.role() {
+            Role::Sender => self.on_receiver_flow(&flow)?,
+            Role::Receiver => self.on_sender_flow(&flow),
+        }
+        if flow.echo() && self.state == LinkState::Attached {
+            self.send_flow(false)?;
+        }
+        Ok(())
+    }
+
+    fn on_receiver_flow(&mut self, flow: &Flow) -> Result<(), AppError> {
+        if let Some(link_credit) = flow.link_credit() {
+            // link-credit(snd) = delivery-count(rcv) + link-credit(rcv) - delivery-count(snd)
+            let delivery_count = flow
+                .delivery_count()
+                .or(self.local_attach.initial_delivery_count())
+                .unwrap_or_default();
+            let limit = delivery_count + link_credit.into();
+            // A receiver that has not seen all our transfers yet may set a limit we already
+            // passed, which leaves no credit rather than wrapping around to almost 2^32.
+            self.link_credit =
+                if limit.in_range(self.delivery_count, self.delivery_count + HALF_MAX.into()) {
+                    self.delivery_count.distance(limit)
+                } else {
+                    0
+                };
+        }
+        self.send_resumed()?;
+        self.drain = flow.drain();
+        if self.drain
+            && self.available == 0
+            && self.resuming.is_empty()
+            && self.state == LinkState::Attached
+        {
+            self.drained()?;
+        }
+        Ok(())
+    }
+
+    fn on_sender_flow(&mut self, flow: &Flow) {
+        if let Some(available) = flow.available() {
+            self.available = available;
+        }
+        if let Some(delivery_count) = flow.delivery_count() {
+            // Deliveries the sender counted without sending them (drain) use up credit.
+            let advanced = self.delivery_count.distance(delivery_count);
+            if advanced <= self.link_credit {
+                self.link_credit -= advanced;
+                self.delivery_count = delivery_count;
+            }
+        }
+        if self.drain && flow.drain() && self.link_credit == 0 {
+            self.drain = false;
+            self.events.push_back(LinkEvent::Drained);
+        }
+    }
+
+    fn on_transfer(&mut self, transfer: Transfer, payload: Vec<u8>) -> Result<(), AppError> {
+        if self.role() != Role::Receiver
+            || !matches!(self.state, LinkState::Attached | LinkState::DetachSent)
+        {
+            Err(AmqpError::IllegalState)?
+        }
+        let (delivery, mut buffer) = match self.partial.take() {
+            Some((first, buffer)) => {
+                verify_continuation(&first, &transfer)?;
+                (merge_continuation(first, &transfer), buffer)
+            }
+            None => {
+                if transfer.delivery_id().is_none() || transfer.delivery_tag().is_none() {
+                    Err(AmqpError::InvalidField)?
+                }
+                if self.resume_only && !transfer.resume() {
+                    Err(AmqpError::NotAllowed)?
+                }
+                if self.link_credit == 0 {
+                    Err(LinkError::TransferLimitExceeded)?
+                }
+                self.delivery_count += 1.into();
+                self.link_credit -= 1;
+                self.available = self.available.saturating_sub(1);
+                (transfer.clone(), Vec::new())
+            }
+        };
+        if transfer.aborted() {
+            // The payload of the aborting frame is ignored, like everything before it.
+            let tag = delivery
+                .delivery_tag()
+                .cloned()
+                .ok_or(AmqpError::InternalError)?;
+            if delivery.resume() {
+                self.unsettled.remove(&tag);
+            }
+            self.events.push_back(LinkEvent::Aborted { tag });
+            return Ok(());
+        }
+        buffer.extend(payload);
+        let max_message_size = self.local_attach.max_message_size();
+        if max_message_size.is_some_and(|max| max > 0 && buffer.len() as u64 > max) {
+            Err(LinkError::MessageSizeExceeded)?
+        }
+        let resumed = delivery
+            .delivery_tag()
+            .filter(|tag| delivery.resume() && self.unsettled.contains_key(*tag))
+            .cloned();
+        match (transfer.more(), resumed) {
+            (true, _) => self.partial = Some((delivery, buffer)),
+            (false, Some(tag)) => self.on_resumed(tag, delivery, buffer)?,
+            (false, None) => {
+                if delivery.settled() != Some(true) {
+                    self.track(&delivery)?;
+                }
+                self.events
+                    .push_back(LinkEvent::Delivery(delivery.with_more(false), buffer));
+            }
+        }
+        Ok(())
+    }
+
+    /// Receiver: handles a resumed delivery that is still in the unsettled map.
+    fn on_resumed(
+        &mut self,
+        tag: DeliveryTag,
+        delivery: Transfer,
+        payload: Vec<u8>,
+    ) -> Result<(), AppError> {
+        if delivery.settled() == Some(true) {
+            self.settle_locally(&tag, delivery.state().cloned());
+            return Ok(());
+        }
+        let unsettled = self
+            .unsettled
+            .get_mut(&tag)
+            .ok_or(AmqpError::InternalError)?;
+        unsettled.set_delivery_id(delivery.delivery_id().ok_or(AmqpError::InvalidField)?);
+        match unsettled
+            .local_state()
+            .filter(|state| is_outcome(state))
+            .cloned()
+        {
+            // The message was processed already; report the outcome again instead of
+            // delivering it twice.
+            Some(outcome) => self.dispose(&tag, outcome),
+            None => {
+                self.events
+                    .push_back(LinkEvent::Delivery(delivery.with_more(false), payload));
+                Ok(())
+            }
+        }
+    }
+
+    /// Receiver: adds a complete, unsettled delivery to the unsettled map.
+    fn track(&mut self, delivery: &Transfer) -> Result<(), AppError> {
+        let tag = delivery.delivery_tag().ok_or(AmqpError::InvalidField)?;
+        if self.unsettled.contains_key(tag) {
+            Err(AmqpError::InvalidField)?
+        }
+        let rcv_settle_mode = match (
+            self.local_attach.rcv_settle_mode(),
+            delivery.rcv_settle_mode(),
+        ) {
+            // A transfer may only ask for mode `second` if the link uses it.
+            (ReceiverSettleMode::First, Some(ReceiverSettleMode::Second)) => {
+                Err(AmqpError::InvalidField)?
+            }
+            (mode, requested) => requested.unwrap_or(mode),
+        };
+        let unsettled = UnsettledDelivery::new(
+            delivery.delivery_id(),
+            rcv_settle_mode,
+            delivery.batchable(),
+        );
+        self.unsettled.insert(tag.clone(), unsettled);
+        Ok(())
+    }
+
+    /// Applies a disposition from the peer to the unsettled deliveries within its range.
+    fn on_disposition(&mut self, disposition: Disposition) -> Result<(), AppError> {
+        let tags: Vec<DeliveryTag> = self
+            .unsettled
+            .iter()
+            .filter(|(_, delivery)| {
+                delivery
+                    .delivery_id()
+                    .is_some_and(|id| disposition.contains(id))
+            })
+            .map(|(tag, _)| tag.clone())
+            .collect();
+        for tag in tags {
+            let delivery = self
+                .unsettled
+                .get_mut(&tag)
+                .ok_or(AmqpError::InternalError)?;
+            delivery.set_remote_state(disposition.state().cloned());
+            // The sender settles as soon as it knows the outcome.
+            let settle_locally =
+                self.role() == Role::Sender && disposition.state().is_some_and(is_outcome);
+            if !disposition.settled() && !settle_locally {
+                continue;
+            }
+            let Some(delivery) = self.unsettled.remove(&tag) else {
+                continue;
+            };
+            let delivery_id = delivery.delivery_id().ok_or(AmqpError::InternalError)?;
+            let batchable = delivery.batchable();
+            let state = delivery.settle();
+            if !disposition.settled() {
+                let mut settlement = Disposition::new(self.role(), delivery_id)
+                    .with_settled(true)
+                    .with_batchable(batchable);
+                if let Some(state) = &state {
+                    settlement = settlement.with_state(state.clone());
+                }
+                self.push(Performative::Disposition(settlement));
+            }
+            self.events.push_back(LinkEvent::Settled { tag, state });
+        }
+        Ok(())
+    }
+
+    fn require_role(&self, role: Role) -> Result<(), AppError> {
+        match self.role() == role {
+            true => Ok(()),
+            false => Err(AmqpError::NotAllowed)?,
+        }
+    }
+}
+
+/// Checks that a continuation frame belongs to the delivery started by `first`.
+/// A continuation may leave out the delivery-id and tag, but must not change them.
+fn verify_continuation(first: &Transfer, continuation: &Transfer) -> Result<(), AppError> {
+    let same_handle = first.handle() == continuation.handle();
+    let same_id = continuation
+        .delivery_id()
+        .is_none_or(|id| Some(id) == first.delivery_id());
+    let same_tag = continuation
+        .delivery_tag()
+        .is_none_or(|tag| Some(tag) == first.delivery_tag());
+    match same_handle && same_id && same_tag {
+        true => Ok(()),
+        false => Err(AmqpError::InvalidField)?,
+    }
+}
+
+/// Takes the settlement and state a continuation frame may add to the delivery.
+fn merge_continuation(mut delivery: Transfer, continuation: &Transfer) -> Transfer {
+    if continuation.settled() == Some(true) {
+        delivery = delivery.with_settled(true);
+    }
+    if let Some(state) = continuation.state() {
+        delivery = delivery.with_state(state.clone());
+    }
+    delivery
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amqp_type::composite::messaging::delivery_state::accepted::Accepted;
+    use amqp_type::composite::messaging::delivery_state::received::Received;
+    use amqp_type::composite::messaging::delivery_state::released::Released;
+    use amqp_type::composite::transport::transport::target::Target;
+    use amqp_type::primitive::variable_width::symbol::Symbol;
+    use futures::FutureExt;
+    use std::cell::Cell;
+    use LinkState::*;
+
+    #[derive(Debug, Clone, Copy)]
+    enum Step {
+        SendAttach,
+        SendDetach,
+        ReceiveAttach,
+        ReceiveDetach,
+    }
+    use Step::*;
+
+    fn apply(link: &mut LinkEndpoint, step: Step) -> Result<(), AppError> {
+        match step {
+            SendAttach => link.send_attach(),
+            SendDetach => link.send_detach(true, None),
+            ReceiveAttach => link.on_performative(
+                Performative::Attach(Attach::new("link".to_string(), 7, Role::Receiver)),
+                vec![],
+            ),
+            ReceiveDetach => link.on_performative(
+                Performative::Detach(Detach::new(7).with_closed(true)),
+                vec![],
+            ),
+        }
+    }
+
+    fn run(steps: &[Step]) -> LinkEndpoint {
+        let mut link = LinkEndpoint::new(Attach::new("link".to_string(), 0, Role::Sender));
+        for step in steps {
+            apply(&mut link, *step)
+                .unwrap_or_else(|e| panic!("{:?} failed in {:?}: {}", step, link.state(), e));
+        }
+        link
+    }
+
+    /// An attached sender and receiver, with everything they sent so far dropped.
+    fn attached_pair() -> (LinkEndpoint, LinkEndpoint) {
+        attached_with(
+            Attach::new("link".to_string(), 0, Role::Sender)
+                .with_initial_delivery_count(u32::MAX.into()),
+            Attach::new("link".to_string(), 1, Role::Receiver),
+        )
+    }
+
+    fn attached_with(sender: Attach, receiver: Attach) -> (LinkEndpoint, LinkEndpoint) {
+        let mut sender = LinkEndpoint::new(sender);
+        let mut receiver = LinkEndpoint::new(receiver);
+        sender.send_attach().unwrap();
+        deliver(&mut sender, &mut receiver);
+        receiver.send_attach().unwrap();
+        deliver(&mut receiver, &mut sender);
+        while sender.poll_event().is_some() {}
+        while receiver.poll_event().is_some() {}
+        (sender, receiver)
+    }
+
+    thread_local! {
+        /// The delivery-id the session would hand out next, counted per test.
+        static NEXT_DELIVERY_ID: Cell<DeliveryNumber> = Cell::new(DeliveryNumber::default());
+    }
+
+    fn poll(link: &mut LinkEndpoint) -> Option<(Performative, Vec<u8>)> {
+        NEXT_DELIVERY_ID.with(|next| {
+            let mut delivery_id = next.get();
+            let frame = link.poll_transmit(&mut delivery_id);
+            next.set(delivery_id);
+            frame
+        })
+    }
+
+    fn tagged(tag: u8) -> Transfer {
+        Transfer::new(0).with_delivery_tag(DeliveryTag::new(vec![tag]).unwrap())
+    }
+
+    fn deliver(from: &mut LinkEndpoint, to: &mut LinkEndpoint) {
+        while let Some((performative, payload)) = poll(from) {
+            to.on_performative(performative, payload).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_legal_transitions() {
+        let paths: &[(&[Step], LinkState)] = &[
+            (&[], Detached),
+            (&[SendAttach], AttachSent),
+            (&[ReceiveAttach], AttachRcvd),
+            (&[SendAttach, ReceiveAttach], Attached),
+            (&[ReceiveAttach, SendAttach], Attached),
+            (&[SendAttach, SendDetach], DetachSent),
+            (&[SendAttach, ReceiveAttach, SendDetach], DetachSent),
+            (&[SendAttach, ReceiveAttach, ReceiveDetach], DetachRcvd),
+            (
+                &[SendAttach, ReceiveAttach, SendDetach, ReceiveDetach],
+                Detached,
+            ),
+            (
+                &[SendAttach, ReceiveAttach, ReceiveDetach, SendDetach],
+                Detached,
+            ),
+        ];
+        for (steps, expected) in paths {
+            assert_eq!(run(steps).state(), *expected, "{:?}", steps);
+        }
+    }
+
+    #[test]
+    fn test_illegal_transitions() {
+        let paths: &[(&[Step], Step)] = &[
+            (&[], SendDetach),
+            (&[], ReceiveDetach),
+            (&[SendAttach], SendAttach),
+            (&[ReceiveAttach], ReceiveAttach),
+            (&[SendAttach, ReceiveAttach], SendAttach),
+            (&[SendAttach, ReceiveAttach, SendDetach], SendDetach),
+            (&[SendAttach, ReceiveAttach, ReceiveDetach], ReceiveDetach),
+        ];
+        for (steps, step) in paths {
+            let mut link = run(steps);
+            let before = link.state();
+            assert!(
+                matches!(
+                    apply(&mut link, *step),
+                    Err(AppError::Amqp(AmqpError::IllegalState))
+                ),
+                "{:?} then {:?}",
+                steps,
+                step
+            );
+            assert_eq!(link.state(), before);
+        }
+    }
+
+    #[test]
+    fn test_receiver_learns_initial_delivery_count() {
+        let (sender, receiver) = attached_pair();
+        assert_eq!(sender.delivery_count(), u32::MAX.into());
+        assert_eq!(receiver.delivery_count(), u32::MAX.into());
+        assert_eq!(sender.remote_handle(), Some(1));
+        assert_eq!(receiver.remote_handle(), Some(0));
+    }
+
+    #[test]
+    fn test_credit_flows_from_receiver_to_sender() {
+        let (mut sender, mut receiver) = attached_pair();
+        receiver.grant_credit(2).unwrap();
+        deliver(&mut receiver, &mut sender);
+        assert_eq!(sender.link_credit(), 2);
+        assert!(matches!(sender.poll_event(), Some(LinkEvent::Flow(_))));
+
+        sender.send_transfer(tagged(1), vec![1]).unwrap();
+        sender.send_transfer(tagged(2), vec![2]).unwrap();
+        assert!(matches!(
+            sender.send_transfer(tagged(3), vec![3]),
+            Err(AppError::Link(LinkError::TransferLimitExceeded))
+        ));
+        deliver(&mut sender, &mut receiver);
+        assert_eq!(sender.delivery_count(), 1.into());
+        assert_eq!(receiver.delivery_count(), 1.into());
+        assert_eq!(receiver.link_credit(), 0);
+        assert!(
+            matches!(receiver.poll_event(), Some(LinkEvent::Delivery(_, payload)) if payload == vec![1])
+        );
+    }
+
+    #[test]
+    fn test_credit_lowered_below_transfers_in_flight_leaves_none() {
+        let (mut sender, mut receiver) = attached_pair();
+        receiver.grant_credit(5).unwrap();
+        deliver(&mut receiver, &mut sender);
+        sender.send_transfer(tagged(1), vec![1]).unwrap();
+        sender.send_transfer(tagged(2), vec![2]).unwrap();
+        assert_eq!(sender.link_credit(), 3);
+
+        // The receiver takes its credit back before the transfers reach it.
+        receiver.grant_credit(0).unwrap();
+        deliver(&mut receiver, &mut sender);
+        assert_eq!(sender.link_credit(), 0);
+        assert!(matches!(
+            sender.send_transfer(tagged(3), vec![3]),
+            Err(AppError::Link(LinkError::TransferLimitExceeded))
+        ));
+    }
+
+    #[test]
+    fn test_multi_frame_delivery_consumes_one_credit() {
+        let (mut sender, mut receiver) = attached_pair();
+        receiver.grant_credit(1).unwrap();
+        deliver(&mut receiver, &mut sender);
+        sender
+            .send_transfer(tagged(1).with_more(true), vec![1])
+            .unwrap();
+        sender
+            .send_transfer(Transfer::new(0).with_more(true), vec![2])
+            .unwrap();
+        sender.send_transfer(Transfer::new(0), vec![3]).unwrap();
+        deliver(&mut sender, &mut receiver);
+        assert_eq!(sender.link_credit(), 0);
+        assert_eq!(receiver.link_credit(), 0);
+        assert_eq!(receiver.delivery_count(), 0.into());
+        assert!(
+            matches!(receiver.poll_event(), Some(LinkEvent::Delivery(_, payload)) if payload == vec![1, 2, 3])
+        );
+        assert_eq!(receiver.poll_event(), None);
+    }
+
+    #[test]
+    fn test_large_message_is_fragmented_and_reassembled() {
+        let (mut sender, mut receiver) = attached_pair();
+        receiver.grant_credit(1).unwrap();
+        deliver(&mut receiver, &mut sender);
+        let tag = DeliveryTag::new(vec![7]).unwrap();
+        let transfer = Transfer::new(0).with_delivery_tag(tag.clone());
+        let payload: Vec<u8> = (0..2000).map(|i| i as u8).collect();
+        sender.send_message(transfer, payload.clone()).unwrap();
+        assert_eq!(sender.link_credit(), 0);
+
+        let mut frames = 0;
+        while let Some((performative, chunk)) = poll(&mut sender) {
+            let size = FRAME_HEADER_SIZE + performative.clone().encode().len() + chunk.len();
+            assert!(
+                size <= MIN_MAX_FRAME_SIZE as usize,
+                "frame of {} bytes",
+                size
+            );
+            receiver.on_performative(performative, chunk).unwrap();
+            frames += 1;
+        }
+        assert!(frames > 4);
+        assert_eq!(receiver.link_credit(), 0);
+        let Some(LinkEvent::Delivery(delivery, received)) = receiver.poll_event() else {
+            panic!("expected a delivery");
+        };
+        assert!(delivery.delivery_id().is_some());
+        assert_eq!(delivery.delivery_tag(), Some(&tag));
+        assert!(!delivery.more());
+        assert_eq!(received, payload);
+    }
+
+    #[test]
+    fn test_small_message_fits_one_frame() {
+        let (mut sender, mut receiver) = attached_pair();
+        receiver.grant_credit(1).unwrap();
+        deliver(&mut receiver, &mut sender);
+        sender.send_message(tagged(1), vec![1; 100]).unwrap();
+        assert!(poll(&mut sender).is_some());
+        assert!(poll(&mut sender).is_none());
+    }
+
+    #[test]
+    fn test_inconsistent_continuation_is_rejected() {
+        let (_, mut receiver) = attached_pair();
+        receiver.grant_credit(1).unwrap();
+        let first = tagged(1).with_delivery_id(1.into()).with_more(true);
+        receiver
+            .on_performative(Performative::Transfer(first), vec![1])
+            .unwrap();
+        let continuation = Transfer::new(0).with_delivery_id(2.into());
+        assert!(matches!(
+            receiver.on_performative(Performative::Transfer(continuation), vec![2]),
+            Err(AppError::Amqp(AmqpError::InvalidField))
+        ));
+    }
+
+    #[test]
+    fn test_max_message_size_is_enforced() {
+        let mut sender = LinkEndpoint::new(Attach::new("link".to_string(), 0, Role::Sender));
+        let mut receiver = LinkEndpoint::new(
+            Attach::new("link".to_string(), 1, Role::Receiver).with_max_message_size(10),
+        );
+        sender.send_attach().unwrap();
+        deliver(&mut sender, &mut receiver);
+        receiver.send_attach().unwrap();
+        receiver.grant_credit(2).unwrap();
+        deliver(&mut receiver, &mut sender);
+        assert!(matches!(
+            sender.send_message(tagged(1), vec![0; 11]),
+            Err(AppError::Link(LinkError::MessageSizeExceeded))
+        ));
+        assert_eq!(sender.link_credit(), 2);
+
+        // A sender ignoring the limit is caught by the receiver.
+        receiver
+            .on_performative(
+                Performative::Transfer(tagged(1).with_delivery_id(0.into()).with_more(true)),
+                vec![0; 6],
+            )
+            .unwrap();
+        assert!(matches!(
+            receiver.on_performative(Performative::Transfer(Transfer::new(0)), vec![0; 6]),
+            Err(AppError::Link(LinkError::MessageSizeExceeded))
+        ));
+    }
+
+    #[test]
+    fn test_frame_size_below_minimum_is_rejected() {
+        let (mut sender, _) = attached_pair();
+        assert!(matches!(
+            sender.set_max_frame_size(256),
+            Err(AppError::Amqp(AmqpError::FrameSizeTooSmall))
+        ));
+        assert_eq!(sender.max_frame_size(), MIN_MAX_FRAME_SIZE);
+    }
+
+    #[test]
+    fn test_transfer_without_credit_is_rejected_by_receiver() {
+        let (_, mut receiver) = attached_pair();
+        assert!(matches!(
+            receiver.on_performative(
+                Performative::Transfer(tagged(1).with_delivery_id(0.into())),
+                vec![]
+            ),
+            Err(AppError::Link(LinkError::TransferLimitExceeded))
+        ));
+    }
+
+    #[test]
+    fn test_drain_uses_up_credit() {
+        let (mut sender, mut receiver) = attached_pair();
+        receiver.grant_credit(5).unwrap();
+        deliver(&mut receiver, &mut sender);
+        sender.set_available(1).unwrap();
+        sender.send_transfer(tagged(1), vec![1]).unwrap();
+        deliver(&mut sender, &mut receiver);
+        assert_eq!(receiver.available(), 0);
+
+        receiver.request_drain().unwrap();
+        deliver(&mut receiver, &mut sender);
+        // Nothing is available, so the sender drains right away.
+        deliver(&mut sender, &mut receiver);
+        assert_eq!(sender.link_credit(), 0);
+        assert_eq!(sender.delivery_count(), 4.into());
+        assert_eq!(receiver.delivery_count(), 4.into());
+        assert_eq!(receiver.link_credit(), 0);
+        assert!(!receiver.drain());
+        let events: Vec<LinkEvent> = std::iter::from_fn(|| receiver.poll_event()).collect();
+        assert_eq!(events.last(), Some(&LinkEvent::Drained));
+    }
+
+    #[test]
+    fn test_sender_with_available_deliveries_drains_explicitly() {
+        let (mut sender, mut receiver) = attached_pair();
+        sender.set_available(3).unwrap();
+        deliver(&mut sender, &mut receiver);
+        assert_eq!(receiver.available(), 3);
+        receiver.grant_credit(5).unwrap();
+        receiver.request_drain().unwrap();
+        deliver(&mut receiver, &mut sender);
+        assert!(sender.drain());
+        assert_eq!(sender.link_credit(), 5);
+        sender.drained().unwrap();
+        deliver(&mut sender, &mut receiver);
+        assert_eq!(receiver.link_credit(), 0);
+    }
+
+    #[test]
+    fn test_echo_is_answered_with_flow() {
+        let (mut sender, mut receiver) = attached_pair();
+        receiver.send_flow(true).unwrap();
+        deliver(&mut receiver, &mut sender);
+        let (performative, _) = poll(&mut sender).unwrap();
+        let Performative::Flow(flow) = performative else {
+            panic!("expected a flow");
+        };
+        assert_eq!(flow.handle(), Some(0));
+        assert_eq!(flow.delivery_count(), Some(u32::MAX.into()));
+        assert_eq!(flow.available(), Some(0));
+        assert!(!flow.echo());
+    }
+
+    #[test]
+    fn test_closing_detach_with_error() {
+        let (mut sender, mut receiver) = attached_pair();
+        let error = Error::new(Symbol::with_ascii("amqp:link:detach-forced"));
+        sender.send_detach(true, Some(error.clone())).unwrap();
+        deliver(&mut sender, &mut receiver);
+        assert_eq!(
+            receiver.poll_event(),
+            Some(LinkEvent::Detached {
+                closed: true,
+                error: Some(error)
+            })
+        );
+        receiver.send_detach(true, None).unwrap();
+        deliver(&mut receiver, &mut sender);
+        assert_eq!(sender.state(), Detached);
+        assert_eq!(receiver.state(), Detached);
+    }
+
+    #[test]
+    fn test_refused_link_is_answered_without_terminus() {
+        let target = Target::new("queue");
+        let mut sender = LinkEndpoint::new(
+            Attach::new("link".to_string(), 0, Role::Sender).with_target(target.clone()),
+        );
+        let mut receiver = LinkEndpoint::new(
+            Attach::new("link".to_string(), 1, Role::Receiver).with_target(target),
+        );
+        let error = Error::new(Symbol::with_ascii("amqp:not-found"));
+        assert!(matches!(
+            receiver.refuse(error.clone()),
+            Err(AppError::Amqp(AmqpError::IllegalState))
+        ));
+        sender.send_attach().unwrap();
+        deliver(&mut sender, &mut receiver);
+        receiver.refuse(error.clone()).unwrap();
+        deliver(&mut receiver, &mut sender);
+        let Some(LinkEvent::Attached(answer)) = sender.poll_event() else {
+            panic!("the refusal starts with an attach")
+        };
+        assert_eq!(answer.target(), None);
+        assert_eq!(
+            sender.poll_event(),
+            Some(LinkEvent::Detached {
+                closed: true,
+                error: Some(error)
+            })
+        );
+        assert_eq!(receiver.state(), DetachSent);
+    }
+
+    #[test]
+    fn test_role_specific_operations() {
+        let (mut sender, mut receiver) = attached_pair();
+        assert!(matches!(
+            sender.grant_credit(1),
+            Err(AppError::Amqp(AmqpError::NotAllowed))
+        ));
+        assert!(matches!(
+            receiver.send_transfer(Transfer::new(0), vec![]),
+            Err(AppError::Amqp(AmqpError::NotAllowed))
+        ));
+    }
+
+    /// Sends one unsettled delivery with the given tag from an attached sender to the receiver.
+    fn send_unsettled(
+        sender: &mut LinkEndpoint,
+        receiver: &mut LinkEndpoint,
+        tag: u8,
+    ) -> DeliveryFuture {
+        receiver.grant_credit(1).unwrap();
+        deliver(receiver, sender);
+        let outcome = sender.send_message(tagged(tag), vec![tag]).unwrap();
+        deliver(sender, receiver);
+        while sender.poll_event().is_some() {}
+        while receiver.poll_event().is_some() {}
+        outcome
+    }
+
+    fn accepted() -> DeliveryState {
+        DeliveryState::Accepted(Accepted {})
+    }
+
+    #[test]
+    fn test_receiver_settles_first() {
+        let (mut sender, mut receiver) = attached_pair();
+        let mut outcome = send_unsettled(&mut sender, &mut receiver, 1);
+        let tag = DeliveryTag::new(vec![1]).unwrap();
+        assert!(sender.unsettled().contains_key(&tag));
+        assert!(receiver.unsettled()[&tag].delivery_id().is_some());
+        assert!((&mut outcome).now_or_never().is_none());
+
+        receiver.dispose(&tag, accepted()).unwrap();
+        assert!(receiver.unsettled().is_empty());
+        deliver(&mut receiver, &mut sender);
+        assert!(sender.unsettled().is_empty());
+        assert_eq!(
+            sender.poll_event(),
+            Some(LinkEvent::Settled {
+                tag,
+                state: Some(accepted())
+            })
+        );
+        // The receiver settled already, so the sender has nothing more to say.
+        assert!(poll(&mut sender).is_none());
+        assert_eq!(outcome.now_or_never().unwrap().unwrap(), Some(accepted()));
+    }
+
+    #[test]
+    fn test_receiver_settles_second() {
+        let (mut sender, mut receiver) = attached_with(
+            Attach::new("link".to_string(), 0, Role::Sender),
+            Attach::new("link".to_string(), 1, Role::Receiver)
+                .with_rcv_settle_mode(ReceiverSettleMode::Second),
+        );
+        let outcome = send_unsettled(&mut sender, &mut receiver, 1);
+        let tag = DeliveryTag::new(vec![1]).unwrap();
+
+        receiver.dispose(&tag, accepted()).unwrap();
+        assert_eq!(receiver.unsettled()[&tag].local_state(), Some(&accepted()));
+        deliver(&mut receiver, &mut sender);
+        assert!(sender.unsettled().is_empty());
+        let Some((Performative::Disposition(settlement), _)) = poll(&mut sender) else {
+            panic!("expected the sender to settle");
+        };
+        assert!(settlement.settled());
+        assert_eq!(settlement.role(), Role::Sender);
+        assert_eq!(settlement.state(), Some(&accepted()));
+
+        receiver
+            .on_performative(Performative::Disposition(settlement), vec![])
+            .unwrap();
+        assert!(receiver.unsettled().is_empty());
+        assert_eq!(
+            receiver.poll_event(),
+            Some(LinkEvent::Settled {
+                tag,
+                state: Some(accepted())
+            })
+        );
+        assert_eq!(outcome.now_or_never().unwrap().unwrap(), Some(accepted()));
+    }
+
+    #[test]
+    fn test_non_terminal_state_keeps_delivery_unsettled() {
+        let (mut sender, mut receiver) = attached_pair();
+        let mut outcome = send_unsettled(&mut sender, &mut receiver, 1);
+        let tag = DeliveryTag::new(vec![1]).unwrap();
+        let received = DeliveryState::Received(Received {});
+        receiver.dispose(&tag, received.clone()).unwrap();
+        deliver(&mut receiver, &mut sender);
+        assert_eq!(sender.unsettled()[&tag].remote_state(), Some(&received));
+        assert!(receiver.unsettled().contains_key(&tag));
+        assert!((&mut outcome).now_or_never().is_none());
+    }
+
+    #[test]
+    fn test_sender_settle_mode() {
+        let (mut sender, mut receiver) = attached_with(
+            Attach::new("link".to_string(), 0, Role::Sender)
+                .with_snd_settle_mode(SenderSettleMode::Settled),
+            Attach::new("link".to_string(), 1, Role::Receiver),
+        );
+        let outcome = send_unsettled(&mut sender, &mut receiver, 1);
+        assert!(sender.unsettled().is_empty());
+        assert!(receiver.unsettled().is_empty());
+        assert_eq!(outcome.now_or_never().unwrap().unwrap(), None);
+
+        let (mut sender, mut receiver) = attached_with(
+            Attach::new("link".to_string(), 0, Role::Sender)
+                .with_snd_settle_mode(SenderSettleMode::Unsettled),
+            Attach::new("link".to_string(), 1, Role::Receiver),
+        );
+        receiver.grant_credit(1).unwrap();
+        deliver(&mut receiver, &mut sender);
+        assert!(matches!(
+            sender.send_transfer(tagged(1).with_settled(true), vec![]),
+            Err(AppError::Amqp(AmqpError::NotAllowed))
+        ));
+    }
+
+    #[test]
+    fn test_delivery_tags_must_be_unique_while_unsettled() {
+        let (mut sender, mut receiver) = attached_pair();
+        receiver.grant_credit(2).unwrap();
+        deliver(&mut receiver, &mut sender);
+        assert!(matches!(
+            sender.send_transfer(Transfer::new(0), vec![]),
+            Err(AppError::Amqp(AmqpError::InvalidField))
+        ));
+        sender.send_transfer(tagged(1), vec![]).unwrap();
+        assert!(matches!(
+            sender.send_transfer(tagged(1), vec![]),
+            Err(AppError::Amqp(AmqpError::InvalidField))
+        ));
+        assert_eq!(sender.link_credit(), 1);
+    }
+
+    #[test]
+    fn test_outcome_fails_when_link_goes_away() {
+        let (mut sender, mut receiver) = attached_pair();
+        let outcome = send_unsettled(&mut sender, &mut receiver, 1);
+        drop(sender);
+        assert!(matches!(
+            outcome.now_or_never(),
+            Some(Err(AppError::Link(LinkError::DetachForced)))
+        ));
+    }
+
+    #[test]
+    fn test_range_disposition_wraps_around() {
+        let (mut sender, mut receiver) = attached_pair();
+        NEXT_DELIVERY_ID.with(|next| next.set((u32::MAX - 1).into()));
+        let outcomes: Vec<DeliveryFuture> = (1..=4)
+            .map(|tag| send_unsettled(&mut sender, &mut receiver, tag))
+            .collect();
+        let ids: Vec<DeliveryNumber> = (1..=4)
+            .map(|tag| {
+                sender.unsettled()[&DeliveryTag::new(vec![tag]).unwrap()]
+                    .delivery_id()
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(
+            ids,
+            vec![(u32::MAX - 1).into(), u32::MAX.into(), 0.into(), 1.into()]
+        );
+
+        let disposition = Disposition::new(Role::Receiver, (u32::MAX - 1).into())
+            .with_last(0.into())
+            .with_settled(true)
+            .with_state(accepted());
+        sender
+            .on_performative(Performative::Disposition(disposition), vec![])
+            .unwrap();
+        assert_eq!(sender.unsettled().len(), 1);
+        assert!(sender
+            .unsettled()
+            .contains_key(&DeliveryTag::new(vec![4]).unwrap()));
+        let resolved: Vec<bool> = outcomes
+            .into_iter()
+            .map(|outcome| outcome.now_or_never().is_some())
+            .collect();
+        assert_eq!(resolved, vec![true, true, true, false]);
+    }
+
+    fn tag(tag: u8) -> DeliveryTag {
+        DeliveryTag::new(vec![tag]).unwrap()
+    }
+
+    fn drop_frames(link: &mut LinkEndpoint) {
+        while poll(link).is_some() {}
+    }
+
+    /// The events of a link, leaving out the attaches and flows of the exchange itself.
+    fn events(link: &mut LinkEndpoint) -> Vec<LinkEvent> {
+        std::iter::from_fn(|| link.poll_event())
+            .filter(|event| !matches!(event, LinkEvent::Attached(_) | LinkEvent::Flow(_)))
+            .collect()
+    }
+
+    /// A sender and a receiver in rcv-settle-mode `second`, so that the receiver can hold on
+    /// to deliveries it knows the outcome of.
+    fn resumable_pair() -> (LinkEndpoint, LinkEndpoint) {
+        attached_with(
+            Attach::new("link".to_string(), 0, Role::Sender),
+            Attach::new("link".to_string(), 1, Role::Receiver)
+                .with_rcv_settle_mode(ReceiverSettleMode::Second),
+        )
+    }
+
+    /// Detaches both ends without closing the link, resumes them on new handles and attaches
+    /// them again. The receiver then grants credit for the resumed deliveries.
+    fn reattach(sender: &mut LinkEndpoint, receiver: &mut LinkEndpoint) {
+        sender.send_detach(false, None).unwrap();
+        deliver(sender, receiver);
+        receiver.send_detach(false, None).unwrap();
+        deliver(receiver, sender);
+        events(sender);
+        events(receiver);
+
+        sender.resume(2).unwrap();
+        receiver.resume(3).unwrap();
+        sender.send_attach().unwrap();
+        deliver(sender, receiver);
+        receiver.send_attach().unwrap();
+        deliver(receiver, sender);
+        assert_eq!(sender.state(), Attached);
+        assert_eq!(receiver.state(), Attached);
+        receiver.grant_credit(10).unwrap();
+        deliver(receiver, sender);
+        deliver(sender, receiver);
+    }
+
+    fn redelivered(events: &[LinkEvent]) -> Vec<&Transfer> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                LinkEvent::Delivery(transfer, _) => Some(transfer),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_unsettled_map_round_trips() {
+        let states = [(tag(1), None), (tag(2), Some(accepted()))];
+        let unsettled = encode_unsettled(states.iter().map(|(tag, state)| (tag, state.as_ref())));
+        let attach = Attach::new("link".to_string(), 0, Role::Sender).with_unsettled(unsettled);
+        let Performative::Attach(decoded) =
+            Performative::try_decode(&mut attach.encode().into_iter()).unwrap()
+        else {
+            panic!("expected an attach");
+        };
+        let decoded = decode_unsettled(decoded.unsettled().unwrap()).unwrap();
+        assert_eq!(decoded, states.into_iter().collect());
+    }
+
+    #[test]
+    fn test_recovery_a_both_settled() {
+        let (mut sender, mut receiver) = resumable_pair();
+        send_unsettled(&mut sender, &mut receiver, 1);
+        receiver.dispose(&tag(1), accepted()).unwrap();
+        deliver(&mut receiver, &mut sender);
+        deliver(&mut sender, &mut receiver);
+        assert!(sender.unsettled().is_empty() && receiver.unsettled().is_empty());
+
+        reattach(&mut sender, &mut receiver);
+        assert_eq!(receiver.remote_attach().unwrap().unsettled(), None);
+        assert_eq!(sender.remote_attach().unwrap().unsettled(), None);
+        assert!(redelivered(&events(&mut receiver)).is_empty());
+        assert_eq!(sender.link_credit(), 10);
+    }
+
+    #[test]
+    fn test_recovery_b_receiver_settles_unknown_delivery() {
+        let (mut sender, mut receiver) = resumable_pair();
+        send_unsettled(&mut sender, &mut receiver, 1);
+        // The sender gives up on the delivery, but the receiver never hears of it.
+        sender.settle(&tag(1)).unwrap();
+        drop_frames(&mut sender);
+
+        reattach(&mut sender, &mut receiver);
+        assert!(receiver.unsettled().is_empty());
+        assert_eq!(
+            events(&mut receiver),
+            vec![LinkEvent::Settled {
+                tag: tag(1),
+                state: None
+            }]
+        );
+        assert_eq!(sender.link_credit(), 10);
+    }
+
+    #[test]
+    fn test_recovery_c_receiver_settles_with_its_outcome() {
+        let (mut sender, mut receiver) = resumable_pair();
+        let outcome = send_unsettled(&mut sender, &mut receiver, 1);
+        receiver.dispose(&tag(1), accepted()).unwrap();
+        deliver(&mut receiver, &mut sender);
+        // The sender's settlement is lost.
+        drop_frames(&mut sender);
+        assert_eq!(outcome.now_or_never().unwrap().unwrap(), Some(accepted()));
+
+        reattach(&mut sender, &mut receiver);
+        assert!(receiver.unsettled().is_empty());
+        assert_eq!(
+            events(&mut receiver),
+            vec![LinkEvent::Settled {
+                tag: tag(1),
+                state: Some(accepted())
+            }]
+        );
+    }
+
+    #[test]
+    fn test_recovery_d_lost_delivery_is_resent() {
+        let (mut sender, mut receiver) = resumable_pair();
+        receiver.grant_credit(1).unwrap();
+        deliver(&mut receiver, &mut sender);
+        let outcome = sender.send_message(tagged(1), vec![1, 2, 3]).unwrap();
+        drop_frames(&mut sender);
+
+        reattach(&mut sender, &mut receiver);
+        let events = events(&mut receiver);
+        assert_eq!(events.len(), 1);
+        let LinkEvent::Delivery(transfer, payload) = &events[0] else {
+            panic!("expected the delivery to be resent");
+        };
+        assert!(transfer.resume());
+        assert_eq!(transfer.handle(), 2);
+        assert_eq!(payload, &vec![1, 2, 3]);
+        assert_eq!(sender.link_credit(), 9);
+
+        receiver.dispose(&tag(1), accepted()).unwrap();
+        deliver(&mut receiver, &mut sender);
+        assert_eq!(outcome.now_or_never().unwrap().unwrap(), Some(accepted()));
+    }
+
+    #[test]
+    fn test_recovery_e_unprocessed_delivery_is_resent() {
+        let (mut sender, mut receiver) = resumable_pair();
+        let outcome = send_unsettled(&mut sender, &mut receiver, 1);
+
+        reattach(&mut sender, &mut receiver);
+        let events = events(&mut receiver);
+        let resent = redelivered(&events);
+        assert_eq!(resent.len(), 1);
+        assert!(resent[0].resume());
+        assert!(receiver.unsettled()[&tag(1)].delivery_id().is_some());
+
+        receiver.dispose(&tag(1), accepted()).unwrap();
+        deliver(&mut receiver, &mut sender);
+        assert!(sender.unsettled().is_empty());
+        assert_eq!(outcome.now_or_never().unwrap().unwrap(), Some(accepted()));
+    }
+
+    #[test]
+    fn test_recovery_e_delivery_without_message_is_aborted() {
+        let (mut sender, mut receiver) = resumable_pair();
+        receiver.grant_credit(1).unwrap();
+        deliver(&mut receiver, &mut sender);
+        sender.send_transfer(tagged(1), vec![1]).unwrap();
+        deliver(&mut sender, &mut receiver);
+        events(&mut receiver);
+
+        reattach(&mut sender, &mut receiver);
+        assert!(sender.unsettled().is_empty());
+        assert!(receiver.unsettled().is_empty());
+        assert_eq!(
+            events(&mut receiver),
+            vec![LinkEvent::Aborted { tag: tag(1) }]
+        );
+    }
+
+    #[test]
+    fn test_recovery_f_sender_takes_receivers_outcome() {
+        let (mut sender, mut receiver) = resumable_pair();
+        let mut outcome = send_unsettled(&mut sender, &mut receiver, 1);
+        receiver.dispose(&tag(1), accepted()).unwrap();
+        // The receiver's outcome is lost.
+        drop_frames(&mut receiver);
+        assert!((&mut outcome).now_or_never().is_none());
+
+        reattach(&mut sender, &mut receiver);
+        assert!(sender.unsettled().is_empty());
+        assert_eq!(outcome.now_or_never().unwrap().unwrap(), Some(accepted()));
+        assert!(receiver.unsettled().is_empty());
+        assert_eq!(
+            events(&mut receiver),
+            vec![LinkEvent::Settled {
+                tag: tag(1),
+                state: Some(accepted())
+            }]
+        );
+    }
+
+    #[test]
+    fn test_recovery_g_sender_settles_delivery_the_receiver_settled() {
+        let (mut sender, mut receiver) = resumable_pair();
+        receiver.grant_credit(1).unwrap();
+        deliver(&mut receiver, &mut sender);
+        let outcome = sender.send_message(tagged(1), vec![1]).unwrap();
+        drop_frames(&mut sender);
+        sender
+            .unsettled
+            .get_mut(&tag(1))
+            .unwrap()
+            .set_local_state(accepted());
+
+        reattach(&mut sender, &mut receiver);
+        assert!(sender.unsettled().is_empty());
+        assert!(events(&mut sender).contains(&LinkEvent::Settled {
+            tag: tag(1),
+            state: Some(accepted())
+        }));
+        assert_eq!(outcome.now_or_never().unwrap().unwrap(), Some(accepted()));
+        assert!(events(&mut receiver).is_empty());
+        assert_eq!(sender.link_credit(), 10);
+    }
+
+    #[test]
+    fn test_recovery_h_sender_settles_with_its_outcome() {
+        let (mut sender, mut receiver) = resumable_pair();
+        let outcome = send_unsettled(&mut sender, &mut receiver, 1);
+        sender
+            .unsettled
+            .get_mut(&tag(1))
+            .unwrap()
+            .set_local_state(accepted());
+
+        reattach(&mut sender, &mut receiver);
+        assert!(sender.unsettled().is_empty());
+        assert_eq!(outcome.now_or_never().unwrap().unwrap(), Some(accepted()));
+        assert!(receiver.unsettled().is_empty());
+        assert_eq!(
+            events(&mut receiver),
+            vec![LinkEvent::Settled {
+                tag: tag(1),
+                state: Some(accepted())
+            }]
+        );
+    }
+
+    #[test]
+    fn test_recovery_i_receivers_outcome_wins() {
+        let released = DeliveryState::Released(Released {});
+        let (mut sender, mut receiver) = resumable_pair();
+        let outcome = send_unsettled(&mut sender, &mut receiver, 1);
+        receiver.dispose(&tag(1), released.clone()).unwrap();
+        drop_frames(&mut receiver);
+        sender
+            .unsettled
+            .get_mut(&tag(1))
+            .unwrap()
+            .set_local_state(accepted());
+
+        reattach(&mut sender, &mut receiver);
+        assert!(sender.unsettled().is_empty());
+        assert_eq!(
+            outcome.now_or_never().unwrap().unwrap(),
+            Some(released.clone())
+        );
+        assert_eq!(
+            events(&mut receiver),
+            vec![LinkEvent::Settled {
+                tag: tag(1),
+                state: Some(released)
+            }]
+        );
+    }
+
+    #[test]
+    fn test_incomplete_unsettled_allows_only_resumed_deliveries() {
+        let (mut sender, mut receiver) = resumable_pair();
+        for tag in 0..200 {
+            send_unsettled(&mut sender, &mut receiver, tag);
+        }
+        sender.send_detach(false, None).unwrap();
+        deliver(&mut sender, &mut receiver);
+        receiver.send_detach(false, None).unwrap();
+        deliver(&mut receiver, &mut sender);
+        sender.resume(2).unwrap();
+        receiver.resume(3).unwrap();
+        sender.send_attach().unwrap();
+        deliver(&mut sender, &mut receiver);
+        receiver.send_attach().unwrap();
+        deliver(&mut receiver, &mut sender);
+
+        let attach = sender.remote_attach().unwrap();
+        assert!(attach.incomplete_unsettled());
+        assert!(attach.unsettled().unwrap().len() < 200);
+        // Deliveries missing from an incomplete map are not settled, but resent.
+        assert_eq!(sender.unsettled().len(), 200);
+        assert!(matches!(
+            sender.send_message(tagged(250), vec![]),
+            Err(AppError::Amqp(AmqpError::NotAllowed))
+        ));
+
+        receiver.grant_credit(1).unwrap();
+        let transfer = Transfer::new(2)
+            .with_delivery_id(1000.into())
+            .with_delivery_tag(tag(251));
+        assert!(matches!(
+            receiver.on_performative(Performative::Transfer(transfer), vec![]),
+            Err(AppError::Amqp(AmqpError::NotAllowed))
+        ));
+    }
+
+    #[test]
+    fn test_closed_link_cannot_be_resumed() {
+        let (mut sender, mut receiver) = resumable_pair();
+        let outcome = send_unsettled(&mut sender, &mut receiver, 1);
+        sender.send_detach(true, None).unwrap();
+        deliver(&mut sender, &mut receiver);
+        receiver.send_detach(true, None).unwrap();
+        deliver(&mut receiver, &mut sender);
+        assert!(sender.closed());
+        assert!(sender.unsettled().is_empty() && receiver.unsettled().is_empty());
+        assert!(matches!(
+            sender.resume(2),
+            Err(AppError::Amqp(AmqpError::IllegalState))
+        ));
+        assert!(matches!(
+            outcome.now_or_never(),
+            Some(Err(AppError::Link(LinkError::DetachForced)))
+        ));
+    }
+
+    #[test]
+    fn test_aborted_delivery_is_discarded() {
+        let (mut sender, mut receiver) = attached_pair();
+        receiver.grant_credit(2).unwrap();
+        deliver(&mut receiver, &mut sender);
+        let outcome = sender
+            .send_message(tagged(1).with_more(true), vec![1, 2])
+            .unwrap();
+        deliver(&mut sender, &mut receiver);
+        sender.abort().unwrap();
+        deliver(&mut sender, &mut receiver);
+        assert_eq!(
+            events(&mut receiver),
+            vec![LinkEvent::Aborted { tag: tag(1) }]
+        );
+        assert!(sender.unsettled().is_empty() && receiver.unsettled().is_empty());
+        assert_eq!(outcome.now_or_never().unwrap().unwrap(), None);
+
+        // The aborted delivery used up one credit on both ends.
+        assert_eq!(sender.link_credit(), 1);
+        assert_eq!(receiver.link_credit(), 1);
+        assert_eq!(sender.delivery_count(), receiver.delivery_count());
+
+        sender.send_message(tagged(2), vec![3]).unwrap();
+        deliver(&mut sender, &mut receiver);
+        let events = events(&mut receiver);
+        assert!(matches!(&events[..], [LinkEvent::Delivery(_, payload)] if payload == &vec![3]));
+    }
+
+    #[test]
+    fn test_abort_requires_delivery_in_progress() {
+        let (mut sender, mut receiver) = attached_pair();
+        receiver.grant_credit(1).unwrap();
+        deliver(&mut receiver, &mut sender);
+        assert!(matches!(
+            sender.abort(),
+            Err(AppError::Amqp(AmqpError::IllegalState))
+        ));
+        sender.send_transfer(tagged(1), vec![1]).unwrap();
+        assert!(matches!(
+            sender.abort(),
+            Err(AppError::Amqp(AmqpError::IllegalState))
+        ));
+        assert!(matches!(
+            receiver.abort(),
+            Err(AppError::Amqp(AmqpError::NotAllowed))
+        ));
+    }
+
+    #[test]
+    fn test_payload_of_aborting_frame_is_ignored() {
+        let (mut sender, mut receiver) = attached_with(
+            Attach::new("link".to_string(), 0, Role::Sender),
+            Attach::new("link".to_string(), 1, Role::Receiver).with_max_message_size(4),
+        );
+        receiver.grant_credit(1).unwrap();
+        deliver(&mut receiver, &mut sender);
+        sender
+            .send_transfer(tagged(1).with_more(true), vec![0; 3])
+            .unwrap();
+        sender
+            .send_transfer(Transfer::new(0).with_aborted(true), vec![0; 10])
+            .unwrap();
+        deliver(&mut sender, &mut receiver);
+        assert_eq!(
+            events(&mut receiver),
+            vec![LinkEvent::Aborted { tag: tag(1) }]
+        );
+        assert_eq!(receiver.link_credit(), 0);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::client::{Connection, ConnectionOptions};
+use crate::error::TransportError;
+use crate::server::{serve, Handler, ServerConnection};
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+
+/// How many bytes may be in flight in each direction unless configured otherwise.
+pub const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// # Memory Transport
+/// Connects two endpoints within one process through a [`tokio::io::duplex`] pipe, without
+/// any network access.
+///
+/// With a chunk size, every read and write moves at most that many bytes, so that frames
+/// arrive in pieces the way they may over a real socket.
+///
+/// ```
+///# use amqp_transport::client::ConnectionOptions;
+///# use amqp_transport::memory::MemoryTransport;
+///# use amqp_transport::server::Handler;
+///# struct Accepting;
+///# impl Handler for Accepting {}
+///# async fn example() -> Result<(), amqp_transport::error::TransportError> {
+/// let transport = MemoryTransport::new().with_chunk_size(7);
+/// let (connection, served) = transport
+///     .connect(ConnectionOptions::default(), ConnectionOptions::default(), Accepting)
+///     .await?;
+///# Ok(())
+///# }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryTransport {
+    buffer_size: usize,
+    chunk_size: Option<usize>,
+}
+
+impl Default for MemoryTransport {
+    fn default() -> Self {
+        MemoryTransport::new()
+    }
+}
+
+impl MemoryTransport {
+    pub fn new() -> Self {
+        MemoryTransport {
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            chunk_size: None,
+        }
+    }
+
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Limits every read and write to `chunk_size` bytes, which must not be zero.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = Some(chunk_size.max(1));
+        self
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    pub fn chunk_size(&self) -> Option<usize> {
+        self.chunk_size
+    }
+
+    /// Two streams connected to each other.
+    pub fn pair(&self) -> (MemoryStream, MemoryStream) {
+        let (a, b) = tokio::io::duplex(self.buffer_size);
+        (
+            MemoryStream::new(a, self.chunk_size),
+            MemoryStream::new(b, self.chunk_size),
+        )
+    }
+
+    /// Opens a client connection to a connection served by `handler` in the same process.
+    pub async fn connect(
+        &self,
+        client: ConnectionOptions,
+        server: ConnectionOptions,
+        handler: impl Handler,
+    ) -> Result<(Connection, ServerConnection), TransportError> {
+        let (client_stream, server_stream) = self.pair();
+        let served = serve(server_stream, handler, server);
+        let connection = Connection::open_stream(client_stream, client).await?;
+        Ok((connection, served))
+    }
+}
+
+/// # Memory Stream
+/// One end of a [`MemoryTransport`].
+#[derive(Debug)]
+pub struct MemoryStream {
+    inner: DuplexStream,
+    chunk_size: Option<usize>,
+}
+
+impl MemoryStream {
+    fn new(inner: DuplexStream, chunk_size: Option<usize>) -> Self {
+        MemoryStream { inner, chunk_size }
+    }
+
+    fn limit(&self, len: usize) -> usize {
+        self.chunk_size
+            .map_or(len, |chunk_size| chunk_size.min(len))
+    }
+}
+
+impl AsyncRead for MemoryStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let limit = this.limit(buf.remaining());
+        let mut chunk = ReadBuf::new(buf.initialize_unfilled_to(limit));
+        ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk))?;
+        let read = chunk.filled().len();
+        buf.advance(read);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for MemoryStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let limit = this.limit(buf.len());
+        Pin::new(&mut this.inner).poll_write(cx, &buf[..limit])
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amqp_messaging::message::Message;
+    use amqp_type::composite::messaging::delivery_state::accepted::Accepted;
+    use amqp_type::composite::messaging::delivery_state::DeliveryState;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    struct Accepting;
+
+    impl Handler for Accepting {}
+
+    #[tokio::test]
+    async fn test_chunk_size_splits_reads_and_writes() {
+        let (mut a, mut b) = MemoryTransport::new().with_chunk_size(3).pair();
+        assert_eq!(a.write(b"abcdefgh").await.unwrap(), 3);
+        a.write_all(b"defgh").await.unwrap();
+        let mut buffer = [0; 8];
+        assert_eq!(b.read(&mut buffer).await.unwrap(), 3);
+        b.read_exact(&mut buffer[3..]).await.unwrap();
+        assert_eq!(&buffer, b"abcdefgh");
+    }
+
+    #[tokio::test]
+    async fn test_streams_close_together() {
+        let (mut a, mut b) = MemoryTransport::new().pair();
+        a.shutdown().await.unwrap();
+        let mut buffer = Vec::new();
+        assert_eq!(b.read_to_end(&mut buffer).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_connection_survives_byte_by_byte_frames() {
+        let transport = MemoryTransport::new().with_chunk_size(1);
+        let (connection, served) = transport
+            .connect(
+                ConnectionOptions::default(),
+                ConnectionOptions::default(),
+                Accepting,
+            )
+            .await
+            .unwrap();
+        let session = connection.begin_session().await.unwrap();
+        let sender = session.attach_sender("queue").await.unwrap();
+        let outcome = sender
+            .send(Message::from_data(vec![7; 2000]))
+            .await
+            .unwrap();
+        assert_eq!(outcome, Some(DeliveryState::Accepted(Accepted {})));
+        connection.close().await.unwrap();
+        served.closed().await.unwrap();
+    }
+}
@@ -0,0 +1,456 @@
+use crate::constants::{MAJOR, MINOR, REVISION};
+use std::fmt::{Display, Formatter};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const PROTOCOL_NAME: &[u8; 4] = b"AMQP";
+
+/// # Protocol ID
+/// Identifies the layer a protocol header opens.
+///
+/// The layers are negotiated in a fixed order: TLS, then SASL, then AMQP. Every layer
+/// except AMQP is optional.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ProtocolId {
+    Tls,
+    Sasl,
+    Amqp,
+}
+
+impl ProtocolId {
+    pub fn id(&self) -> u8 {
+        match self {
+            ProtocolId::Amqp => 0,
+            ProtocolId::Tls => 2,
+            ProtocolId::Sasl => 3,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(ProtocolId::Amqp),
+            2 => Some(ProtocolId::Tls),
+            3 => Some(ProtocolId::Sasl),
+            _ => None,
+        }
+    }
+}
+
+/// # Protocol Header
+/// The 8 byte header each peer sends before the frames of a layer.
+/// ```text
+///   4 OCTETS   1 OCTET   1 OCTET   1 OCTET   1 OCTET
+/// +----------+---------+---------+---------+----------+
+/// |  "AMQP"  |   %d0   |  major  |  minor  | revision |
+/// +----------+---------+---------+---------+----------+
+/// ```
+/// ```
+///# use amqp_transport::protocol_header::{ProtocolHeader, ProtocolId};
+/// let header = ProtocolHeader::new(ProtocolId::Sasl);
+/// assert_eq!(header.encode(), [b'A', b'M', b'Q', b'P', 3, 1, 0, 0]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProtocolHeader {
+    protocol_id: ProtocolId,
+    major: u8,
+    minor: u8,
+    revision: u8,
+}
+
+impl ProtocolHeader {
+    pub const SIZE: usize = 8;
+
+    /// A header for the given layer with the protocol version implemented by this library.
+    pub fn new(protocol_id: ProtocolId) -> Self {
+        ProtocolHeader {
+            protocol_id,
+            major: MAJOR,
+            minor: MINOR,
+            revision: REVISION,
+        }
+    }
+
+    pub fn protocol_id(&self) -> ProtocolId {
+        self.protocol_id
+    }
+
+    pub fn version(&self) -> (u8, u8, u8) {
+        (self.major, self.minor, self.revision)
+    }
+
+    pub fn is_supported(&self) -> bool {
+        self.version() == (MAJOR, MINOR, REVISION)
+    }
+
+    pub fn encode(self) -> [u8; Self::SIZE] {
+        let [a, m, q, p] = *PROTOCOL_NAME;
+        [
+            a,
+            m,
+            q,
+            p,
+            self.protocol_id.id(),
+            self.major,
+            self.minor,
+            self.revision,
+        ]
+    }
+
+    /// Decodes a header of any version. Fails if the bytes are not a protocol header at all.
+    pub fn try_decode(bytes: [u8; Self::SIZE]) -> Result<Self, NegotiationError> {
+        if &bytes[..4] != PROTOCOL_NAME {
+            Err(NegotiationError::Malformed(bytes))?
+        }
+        let protocol_id =
+            ProtocolId::from_id(bytes[4]).ok_or(NegotiationError::Malformed(bytes))?;
+        Ok(ProtocolHeader {
+            protocol_id,
+            major: bytes[5],
+            minor: bytes[6],
+            revision: bytes[7],
+        })
+    }
+}
+
+impl Display for ProtocolHeader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "AMQP {} {}.{}.{}",
+            self.protocol_id.id(),
+            self.major,
+            self.minor,
+            self.revision
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum NegotiationError {
+    /// The peer sent bytes that are not a protocol header.
+    Malformed([u8; ProtocolHeader::SIZE]),
+    /// The peer does not accept the requested layer or version and replied with the header it supports.
+    Rejected {
+        requested: ProtocolHeader,
+        supported: ProtocolHeader,
+    },
+    /// The layers to negotiate are not in the order TLS, SASL, AMQP or do not end with AMQP.
+    InvalidLayers,
+    /// All layers have already been negotiated.
+    Complete,
+    Io(std::io::Error),
+}
+
+impl Display for NegotiationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NegotiationError::Malformed(bytes) => {
+                write!(f, "malformed protocol header: {:?}", bytes)
+            }
+            NegotiationError::Rejected {
+                requested,
+                supported,
+            } => {
+                write!(
+                    f,
+                    "peer rejected protocol header {}, supports {}",
+                    requested, supported
+                )
+            }
+            NegotiationError::InvalidLayers => {
+                write!(f, "protocol layers must be ordered TLS, SASL, AMQP")
+            }
+            NegotiationError::Complete => write!(f, "protocol negotiation is already complete"),
+            NegotiationError::Io(e) => write!(f, "protocol negotiation failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for NegotiationError {}
+
+impl From<std::io::Error> for NegotiationError {
+    fn from(error: std::io::Error) -> Self {
+        NegotiationError::Io(error)
+    }
+}
+
+/// The answer of a server to a protocol header received from a client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reply {
+    /// The layer is accepted. The server echoes the header and continues with the layer.
+    Accept(ProtocolHeader),
+    /// The layer or version is not acceptable. The server sends the header it expects and closes the socket.
+    Reject(ProtocolHeader),
+}
+
+/// # Protocol Negotiation
+/// Sequences the protocol headers of the TLS, SASL and AMQP layers (spec section 2.2).
+///
+/// Both peers are configured with the layers they use, in order. After a layer has been
+/// accepted, the caller runs it (the TLS handshake or the SASL exchange) and then negotiates
+/// the next one on the resulting stream. The negotiation itself does no I/O.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolNegotiation {
+    layers: Vec<ProtocolId>,
+    next: usize,
+}
+
+impl ProtocolNegotiation {
+    pub fn new(layers: Vec<ProtocolId>) -> Result<Self, NegotiationError> {
+        let ordered = layers.windows(2).all(|w| w[0] < w[1]);
+        if !ordered || layers.last() != Some(&ProtocolId::Amqp) {
+            Err(NegotiationError::InvalidLayers)?
+        }
+        Ok(ProtocolNegotiation { layers, next: 0 })
+    }
+
+    /// Negotiates the AMQP layer only.
+    pub fn amqp() -> Self {
+        ProtocolNegotiation {
+            layers: vec![ProtocolId::Amqp],
+            next: 0,
+        }
+    }
+
+    /// The layer to negotiate next, if any.
+    pub fn current(&self) -> Option<ProtocolId> {
+        self.layers.get(self.next).copied()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.next == self.layers.len()
+    }
+
+    /// The header a client sends to open the next layer.
+    pub fn request(&self) -> Result<ProtocolHeader, NegotiationError> {
+        self.current()
+            .map(ProtocolHeader::new)
+            .ok_or(NegotiationError::Complete)
+    }
+
+    /// Handles the header the server answered the request with.
+    /// On success the layer is established and the negotiation moves to the next layer.
+    pub fn on_reply(
+        &mut self,
+        bytes: [u8; ProtocolHeader::SIZE],
+    ) -> Result<ProtocolId, NegotiationError> {
+        let requested = self.request()?;
+        let supported = ProtocolHeader::try_decode(bytes)?;
+        if supported != requested {
+            Err(NegotiationError::Rejected {
+                requested,
+                supported,
+            })?
+        }
+        self.next += 1;
+        Ok(requested.protocol_id())
+    }
+
+    /// Handles a header received by a server.
+    /// A header for a different layer than expected, for a different version or a malformed
+    /// header is answered with the header the server expects next, after which the server
+    /// must close the socket.
+    pub fn on_request(
+        &mut self,
+        bytes: [u8; ProtocolHeader::SIZE],
+    ) -> Result<Reply, NegotiationError> {
+        let expected = self.request()?;
+        match ProtocolHeader::try_decode(bytes) {
+            Ok(requested) if requested == expected => {
+                self.next += 1;
+                Ok(Reply::Accept(expected))
+            }
+            _ => Ok(Reply::Reject(expected)),
+        }
+    }
+
+    /// Runs the client side of the next layer's header exchange on the stream.
+    pub async fn client<S: AsyncRead + AsyncWrite + Unpin>(
+        &mut self,
+        stream: &mut S,
+    ) -> Result<ProtocolId, NegotiationError> {
+        stream.write_all(&self.request()?.encode()).await?;
+        stream.flush().await?;
+        let mut reply = [0; ProtocolHeader::SIZE];
+        stream.read_exact(&mut reply).await?;
+        self.on_reply(reply)
+    }
+
+    /// Runs the server side of the next layer's header exchange on the stream.
+    /// If the client's header is rejected, the supported header is sent and the stream shut down.
+    pub async fn server<S: AsyncRead + AsyncWrite + Unpin>(
+        &mut self,
+        stream: &mut S,
+    ) -> Result<ProtocolId, NegotiationError> {
+        let mut request = [0; ProtocolHeader::SIZE];
+        stream.read_exact(&mut request).await?;
+        match self.on_request(request)? {
+            Reply::Accept(header) => {
+                stream.write_all(&header.encode()).await?;
+                stream.flush().await?;
+                Ok(header.protocol_id())
+            }
+            Reply::Reject(supported) => {
+                stream.write_all(&supported.encode()).await?;
+                stream.shutdown().await?;
+                match ProtocolHeader::try_decode(request) {
+                    Ok(requested) => Err(NegotiationError::Rejected {
+                        requested,
+                        supported,
+                    }),
+                    Err(e) => Err(e),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AMQP: [u8; 8] = [b'A', b'M', b'Q', b'P', 0, 1, 0, 0];
+    const TLS: [u8; 8] = [b'A', b'M', b'Q', b'P', 2, 1, 0, 0];
+    const SASL: [u8; 8] = [b'A', b'M', b'Q', b'P', 3, 1, 0, 0];
+
+    #[test]
+    fn test_encode_protocol_headers() {
+        assert_eq!(ProtocolHeader::new(ProtocolId::Amqp).encode(), AMQP);
+        assert_eq!(ProtocolHeader::new(ProtocolId::Tls).encode(), TLS);
+        assert_eq!(ProtocolHeader::new(ProtocolId::Sasl).encode(), SASL);
+    }
+
+    #[test]
+    fn test_decode_protocol_header() {
+        let header = ProtocolHeader::try_decode([b'A', b'M', b'Q', b'P', 0, 0, 9, 10]).unwrap();
+        assert_eq!(header.protocol_id(), ProtocolId::Amqp);
+        assert_eq!(header.version(), (0, 9, 10));
+        assert!(!header.is_supported());
+        assert!(ProtocolHeader::try_decode(SASL).unwrap().is_supported());
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed_header() {
+        assert!(matches!(
+            ProtocolHeader::try_decode(*b"GET / HT"),
+            Err(NegotiationError::Malformed(_))
+        ));
+        assert!(matches!(
+            ProtocolHeader::try_decode([b'A', b'M', b'Q', b'P', 1, 1, 0, 0]),
+            Err(NegotiationError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_layers_must_be_ordered() {
+        use ProtocolId::*;
+        assert!(ProtocolNegotiation::new(vec![Tls, Sasl, Amqp]).is_ok());
+        assert!(ProtocolNegotiation::new(vec![Sasl, Amqp]).is_ok());
+        assert!(ProtocolNegotiation::new(vec![Sasl, Tls, Amqp]).is_err());
+        assert!(ProtocolNegotiation::new(vec![Tls, Sasl]).is_err());
+        assert!(ProtocolNegotiation::new(vec![Sasl, Sasl, Amqp]).is_err());
+        assert!(ProtocolNegotiation::new(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_client_sequences_layers() {
+        use ProtocolId::*;
+        let mut client = ProtocolNegotiation::new(vec![Tls, Sasl, Amqp]).unwrap();
+        for (header, id) in [(TLS, Tls), (SASL, Sasl), (AMQP, Amqp)] {
+            assert_eq!(client.request().unwrap().encode(), header);
+            assert_eq!(client.on_reply(header).unwrap(), id);
+        }
+        assert!(client.is_complete());
+        assert!(matches!(client.request(), Err(NegotiationError::Complete)));
+    }
+
+    #[test]
+    fn test_client_detects_rejection() {
+        let mut client = ProtocolNegotiation::amqp();
+        match client.on_reply(SASL) {
+            Err(NegotiationError::Rejected {
+                requested,
+                supported,
+            }) => {
+                assert_eq!(requested.protocol_id(), ProtocolId::Amqp);
+                assert_eq!(supported.protocol_id(), ProtocolId::Sasl);
+            }
+            other => panic!("expected rejection, got {:?}", other),
+        }
+        assert!(!client.is_complete());
+    }
+
+    #[test]
+    fn test_server_rejects_unsupported_version() {
+        let mut server = ProtocolNegotiation::amqp();
+        let old = [b'A', b'M', b'Q', b'P', 0, 0, 10, 0];
+        assert_eq!(
+            server.on_request(old).unwrap(),
+            Reply::Reject(ProtocolHeader::new(ProtocolId::Amqp))
+        );
+    }
+
+    #[test]
+    fn test_server_requires_sasl_before_amqp() {
+        use ProtocolId::*;
+        let mut server = ProtocolNegotiation::new(vec![Sasl, Amqp]).unwrap();
+        assert_eq!(
+            server.on_request(AMQP).unwrap(),
+            Reply::Reject(ProtocolHeader::new(Sasl))
+        );
+        assert_eq!(
+            server.on_request(SASL).unwrap(),
+            Reply::Accept(ProtocolHeader::new(Sasl))
+        );
+        assert_eq!(
+            server.on_request(AMQP).unwrap(),
+            Reply::Accept(ProtocolHeader::new(Amqp))
+        );
+        assert!(server.is_complete());
+    }
+
+    #[test]
+    fn test_server_rejects_garbage() {
+        let mut server = ProtocolNegotiation::amqp();
+        assert_eq!(
+            server.on_request(*b"HTTP/1.1").unwrap(),
+            Reply::Reject(ProtocolHeader::new(ProtocolId::Amqp))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exchange_over_stream() {
+        use ProtocolId::*;
+        let (mut client_io, mut server_io) = tokio::io::duplex(64);
+        let server = tokio::spawn(async move {
+            let mut server = ProtocolNegotiation::new(vec![Sasl, Amqp]).unwrap();
+            let first = server.server(&mut server_io).await.unwrap();
+            let second = server.server(&mut server_io).await.unwrap();
+            (first, second)
+        });
+        let mut client = ProtocolNegotiation::new(vec![Sasl, Amqp]).unwrap();
+        assert_eq!(client.client(&mut client_io).await.unwrap(), Sasl);
+        assert_eq!(client.client(&mut client_io).await.unwrap(), Amqp);
+        assert_eq!(server.await.unwrap(), (Sasl, Amqp));
+    }
+
+    #[tokio::test]
+    async fn test_mismatch_over_stream_closes() {
+        let (mut client_io, mut server_io) = tokio::io::duplex(64);
+        let server = tokio::spawn(async move {
+            let mut server =
+                ProtocolNegotiation::new(vec![ProtocolId::Sasl, ProtocolId::Amqp]).unwrap();
+            server.server(&mut server_io).await
+        });
+        let mut client = ProtocolNegotiation::amqp();
+        assert!(matches!(
+            client.client(&mut client_io).await,
+            Err(NegotiationError::Rejected { .. })
+        ));
+        assert!(matches!(
+            server.await.unwrap(),
+            Err(NegotiationError::Rejected { .. })
+        ));
+        let mut rest = Vec::new();
+        assert_eq!(client_io.read_to_end(&mut rest).await.unwrap(), 0);
+    }
+}
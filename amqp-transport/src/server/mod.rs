@@ -0,0 +1,583 @@
+use crate::client::ConnectionOptions;
+use crate::connection::ConnectionEndpoint;
+use crate::driver::Driver;
+use crate::error::TransportError;
+use crate::tls::ServerTls;
+use crate::websocket;
+use amqp_messaging::interceptor::Interceptors;
+use amqp_messaging::message::Message;
+use amqp_type::composite::messaging::delivery_state::accepted::Accepted;
+use amqp_type::composite::messaging::delivery_state::DeliveryState;
+use amqp_type::composite::transport::frame::performatives::attach::Attach;
+use amqp_type::composite::transport::frame::performatives::begin::Begin;
+use amqp_type::composite::transport::frame::performatives::open::Open;
+use amqp_type::composite::transport::transport::error::Error;
+use amqp_type::composite::transport::transport::source::Source;
+use amqp_type::composite::transport::transport::target::Target;
+use amqp_type::restricted::delivery_tag::DeliveryTag;
+use amqp_type::restricted::handle::Handle;
+use amqp_type::restricted::role::Role;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// # Link
+/// A link the peer attached to a served connection, as the [`Handler`] sees it.
+/// The role is ours: a `Sender` link sends messages to the peer, a `Receiver` link receives them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    channel: u16,
+    handle: Handle,
+    name: String,
+    role: Role,
+    source: Option<Source>,
+    target: Option<Target>,
+}
+
+impl Link {
+    pub(crate) fn new(channel: u16, attach: &Attach) -> Self {
+        Link {
+            channel,
+            handle: attach.handle(),
+            name: attach.name().to_string(),
+            role: attach.role(),
+            source: attach.source().cloned(),
+            target: attach.target().cloned(),
+        }
+    }
+
+    /// The local channel of the session the link is attached to.
+    pub fn channel(&self) -> u16 {
+        self.channel
+    }
+
+    pub fn handle(&self) -> Handle {
+        self.handle
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn source(&self) -> Option<&Source> {
+        self.source.as_ref()
+    }
+
+    pub fn target(&self) -> Option<&Target> {
+        self.target.as_ref()
+    }
+}
+
+/// # Handler
+/// Decides what a served connection does with what the peer starts.
+///
+/// The `on_open`, `on_begin` and `on_attach` hooks accept by default; returning an error
+/// refuses the connection, session or link, and the peer is told the error. Messages arriving
+/// on a receiving link are handed to [`Handler::on_message`], whose outcome settles the
+/// delivery. A sending link asks [`Handler::on_credit`] for messages whenever the peer grants
+/// it credit.
+///
+/// Handlers run on the task of the connection and should not block.
+pub trait Handler: Send + 'static {
+    fn on_open(&mut self, _open: &Open) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn on_begin(&mut self, _channel: u16, _begin: &Begin) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn on_attach(&mut self, _link: &Link) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// A message arrived on a receiving link. The returned state is the outcome of the
+    /// delivery, unless the peer sent it settled.
+    fn on_message(&mut self, _link: &Link, _message: Message) -> DeliveryState {
+        DeliveryState::Accepted(Accepted {})
+    }
+
+    /// The peer can take `credit` more messages on a sending link. At most that many of the
+    /// returned messages are sent.
+    fn on_credit(&mut self, _link: &Link, _credit: u32) -> Vec<Message> {
+        Vec::new()
+    }
+
+    /// The peer settled a message sent on a sending link.
+    fn on_outcome(&mut self, _link: &Link, _tag: &DeliveryTag, _state: Option<&DeliveryState>) {}
+
+    fn on_detach(&mut self, _link: &Link, _error: Option<&Error>) {}
+
+    fn on_close(&mut self, _error: Option<&Error>) {}
+}
+
+/// # Listener
+/// Accepts AMQP connections over TCP and serves each on its own task.
+///
+/// ```no_run
+///# use amqp_transport::server::{Handler, Listener};
+///# struct Accepting;
+///# impl Handler for Accepting {}
+///# async fn example() -> Result<(), amqp_transport::error::TransportError> {
+/// let listener = Listener::bind("0.0.0.0:5672").await?;
+/// loop {
+///     listener.accept(Accepting).await?;
+/// }
+///# }
+/// ```
+pub struct Listener {
+    listener: TcpListener,
+    open: Open,
+    interceptors: Arc<Interceptors>,
+    tls: Option<ServerTls>,
+    websocket: bool,
+}
+
+impl Listener {
+    pub async fn bind(addr: impl ToSocketAddrs) -> Result<Self, TransportError> {
+        Ok(Listener::new(
+            TcpListener::bind(addr).await?,
+            ConnectionOptions::default(),
+        ))
+    }
+
+    /// Serves connections accepted by `listener`, opening them with the fields of `options`.
+    pub fn new(listener: TcpListener, options: ConnectionOptions) -> Self {
+        let (open, interceptors) = options.into_parts();
+        Listener {
+            listener,
+            open,
+            interceptors: Arc::new(interceptors),
+            tls: None,
+            websocket: false,
+        }
+    }
+
+    /// Opens accepted connections with the fields of `options` and runs its interceptors
+    /// on their links.
+    pub fn with_options(self, options: ConnectionOptions) -> Self {
+        let (tls, websocket) = (self.tls, self.websocket);
+        Listener {
+            tls,
+            websocket,
+            ..Listener::new(self.listener, options)
+        }
+    }
+
+    /// Starts TLS on accepted connections before serving them. The handshake runs on the
+    /// connection's task, so a slow client does not hold up [`Listener::accept`].
+    pub fn with_tls(mut self, tls: ServerTls) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Serves accepted connections over a WebSocket, after TLS if that is configured too.
+    /// Clients must ask for the [`SUBPROTOCOL`](crate::websocket::SUBPROTOCOL).
+    pub fn with_websocket(mut self) -> Self {
+        self.websocket = true;
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, TransportError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Waits for the next connection and serves it with `handler`.
+    pub async fn accept(&self, handler: impl Handler) -> Result<ServerConnection, TransportError> {
+        let (stream, peer_addr) = self.listener.accept().await?;
+        stream.set_nodelay(true)?;
+        let (open, interceptors) = (self.open.clone(), self.interceptors.clone());
+        let task = match (&self.tls, self.websocket) {
+            (None, false) => spawn(stream, handler, open, interceptors),
+            (tls, websocket) => {
+                spawn_layered(stream, tls.clone(), websocket, handler, open, interceptors)
+            }
+        };
+        Ok(ServerConnection {
+            peer_addr: Some(peer_addr),
+            task,
+        })
+    }
+}
+
+/// Serves a connection over a stream that is already connected to the peer.
+pub fn serve<S>(stream: S, handler: impl Handler, options: ConnectionOptions) -> ServerConnection
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (open, interceptors) = options.into_parts();
+    ServerConnection {
+        peer_addr: None,
+        task: spawn(stream, handler, open, Arc::new(interceptors)),
+    }
+}
+
+/// Starts TLS on a stream that is already connected to the peer and serves the connection
+/// over it.
+pub fn serve_tls<S>(
+    stream: S,
+    tls: ServerTls,
+    handler: impl Handler,
+    options: ConnectionOptions,
+) -> ServerConnection
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (open, interceptors) = options.into_parts();
+    ServerConnection {
+        peer_addr: None,
+        task: spawn_layered(
+            stream,
+            Some(tls),
+            false,
+            handler,
+            open,
+            Arc::new(interceptors),
+        ),
+    }
+}
+
+/// Accepts a WebSocket on a stream that is already connected to the peer and serves the
+/// connection over it.
+pub fn serve_websocket<S>(
+    stream: S,
+    handler: impl Handler,
+    options: ConnectionOptions,
+) -> ServerConnection
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (open, interceptors) = options.into_parts();
+    ServerConnection {
+        peer_addr: None,
+        task: spawn_layered(stream, None, true, handler, open, Arc::new(interceptors)),
+    }
+}
+
+fn spawn<S>(
+    stream: S,
+    handler: impl Handler,
+    open: Open,
+    interceptors: Arc<Interceptors>,
+) -> JoinHandle<Result<(), TransportError>>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    tokio::spawn(driver(stream, handler, open, interceptors).run_server())
+}
+
+/// Runs the TLS and WebSocket handshakes, as far as they are wanted, on the connection's task
+/// before serving it.
+fn spawn_layered<S>(
+    stream: S,
+    tls: Option<ServerTls>,
+    websocket: bool,
+    handler: impl Handler,
+    open: Open,
+    interceptors: Arc<Interceptors>,
+) -> JoinHandle<Result<(), TransportError>>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        match (tls, websocket) {
+            (Some(tls), true) => {
+                let stream = websocket::accept(tls.accept(stream).await?).await?;
+                driver(stream, handler, open, interceptors)
+                    .run_server()
+                    .await
+            }
+            (Some(tls), false) => {
+                let stream = tls.accept(stream).await?;
+                driver(stream, handler, open, interceptors)
+                    .run_server()
+                    .await
+            }
+            (None, true) => {
+                let stream = websocket::accept(stream).await?;
+                driver(stream, handler, open, interceptors)
+                    .run_server()
+                    .await
+            }
+            (None, false) => {
+                driver(stream, handler, open, interceptors)
+                    .run_server()
+                    .await
+            }
+        }
+    })
+}
+
+fn driver<S>(
+    stream: S,
+    handler: impl Handler,
+    open: Open,
+    interceptors: Arc<Interceptors>,
+) -> Driver<S>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    // A served connection takes no commands, so nobody holds on to the sending end.
+    let (_, commands) = mpsc::unbounded_channel();
+    Driver::new(stream, ConnectionEndpoint::new(open), commands)
+        .with_handler(Box::new(handler), interceptors)
+}
+
+/// # Server Connection
+/// A connection being served. It runs on its own task until either end closes it.
+pub struct ServerConnection {
+    peer_addr: Option<SocketAddr>,
+    task: JoinHandle<Result<(), TransportError>>,
+}
+
+impl ServerConnection {
+    /// The address of the peer, if the connection runs over TCP.
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
+    /// Waits for the connection to end. Fails if it broke off rather than being closed.
+    pub async fn closed(self) -> Result<(), TransportError> {
+        self.task.await.map_err(std::io::Error::other)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Connection;
+    use crate::memory::MemoryTransport;
+    use amqp_type::composite::messaging::delivery_state::rejected::Rejected;
+    use amqp_type::composite::messaging::delivery_state::released::Released;
+    use amqp_type::primitive::variable_width::symbol::Symbol;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// What the handler saw, shared with the test.
+    #[derive(Debug, Default)]
+    struct Seen {
+        container_id: Option<String>,
+        attached: Vec<(Role, Option<String>)>,
+        received: Vec<Message>,
+        outcomes: Vec<Option<DeliveryState>>,
+        detached: usize,
+    }
+
+    /// Takes messages for any node except `missing`, rejecting those with the value "bad",
+    /// and hands out the messages of its queue.
+    #[derive(Clone, Default)]
+    struct Broker {
+        seen: Arc<Mutex<Seen>>,
+        queue: Arc<Mutex<VecDeque<Message>>>,
+    }
+
+    impl Handler for Broker {
+        fn on_open(&mut self, open: &Open) -> Result<(), Error> {
+            self.seen.lock().unwrap().container_id = Some(open.container_id().to_string());
+            Ok(())
+        }
+
+        fn on_attach(&mut self, link: &Link) -> Result<(), Error> {
+            let address = match link.role() {
+                Role::Sender => link.source().and_then(Source::address),
+                Role::Receiver => link.target().and_then(Target::address),
+            };
+            if address == Some("missing") {
+                return Err(not_found());
+            }
+            self.seen
+                .lock()
+                .unwrap()
+                .attached
+                .push((link.role(), address.map(str::to_string)));
+            Ok(())
+        }
+
+        fn on_message(&mut self, _link: &Link, message: Message) -> DeliveryState {
+            let bad = message == Message::from_value("bad");
+            self.seen.lock().unwrap().received.push(message);
+            match bad {
+                true => DeliveryState::Rejected(Rejected {}),
+                false => DeliveryState::Accepted(Accepted {}),
+            }
+        }
+
+        fn on_credit(&mut self, _link: &Link, credit: u32) -> Vec<Message> {
+            let mut queue = self.queue.lock().unwrap();
+            let count = queue.len().min(credit as usize);
+            queue.drain(..count).collect()
+        }
+
+        fn on_outcome(&mut self, _link: &Link, _tag: &DeliveryTag, state: Option<&DeliveryState>) {
+            self.seen.lock().unwrap().outcomes.push(state.cloned());
+        }
+
+        fn on_detach(&mut self, _link: &Link, _error: Option<&Error>) {
+            self.seen.lock().unwrap().detached += 1;
+        }
+    }
+
+    fn not_found() -> Error {
+        Error::new(Symbol::with_ascii("amqp:not-found"))
+    }
+
+    async fn connect(broker: &Broker) -> (Connection, ServerConnection) {
+        let transport = MemoryTransport::new().with_chunk_size(16);
+        let options = ConnectionOptions::new("server");
+        transport
+            .connect(ConnectionOptions::default(), options, broker.clone())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_listener_accepts_client_over_loopback() {
+        let listener = Listener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (connection, served) = tokio::join!(
+            Connection::open(addr, ConnectionOptions::new("client")),
+            listener.accept(Broker::default()),
+        );
+        let (connection, served) = (connection.unwrap(), served.unwrap());
+        assert!(served.peer_addr().is_some());
+        connection.close().await.unwrap();
+        served.closed().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handler_settles_messages_from_client() {
+        let broker = Broker::default();
+        let (connection, served) = MemoryTransport::new()
+            .connect(
+                ConnectionOptions::new("client"),
+                ConnectionOptions::new("server"),
+                broker.clone(),
+            )
+            .await
+            .unwrap();
+
+        let session = connection.begin_session().await.unwrap();
+        let sender = session.attach_sender("queue").await.unwrap();
+        let outcome = sender.send(Message::from_value("good")).await.unwrap();
+        assert_eq!(outcome, Some(DeliveryState::Accepted(Accepted {})));
+        let outcome = sender.send(Message::from_value("bad")).await.unwrap();
+        assert_eq!(outcome, Some(DeliveryState::Rejected(Rejected {})));
+        sender.close().await.unwrap();
+        connection.close().await.unwrap();
+        served.closed().await.unwrap();
+
+        let seen = broker.seen.lock().unwrap();
+        assert_eq!(seen.container_id.as_deref(), Some("client"));
+        assert_eq!(
+            seen.attached,
+            vec![(Role::Receiver, Some("queue".to_string()))]
+        );
+        assert_eq!(
+            seen.received,
+            vec![Message::from_value("good"), Message::from_value("bad")]
+        );
+        assert_eq!(seen.detached, 1);
+    }
+
+    #[tokio::test]
+    async fn test_handler_supplies_messages_for_credit() {
+        let broker = Broker::default();
+        broker
+            .queue
+            .lock()
+            .unwrap()
+            .extend([Message::from_value(1), Message::from_value(2)]);
+        let (connection, served) = connect(&broker).await;
+        assert_eq!(connection.remote_open().container_id(), "server");
+
+        let session = connection.begin_session().await.unwrap();
+        let mut receiver = session.attach_receiver("queue").await.unwrap();
+        let first = receiver.recv().await.unwrap();
+        assert_eq!(first.message(), &Message::from_value(1));
+        first.accept().await.unwrap();
+        let second = receiver.recv().await.unwrap();
+        assert_eq!(second.message(), &Message::from_value(2));
+        second.release().await.unwrap();
+        receiver.close().await.unwrap();
+        connection.close().await.unwrap();
+        served.closed().await.unwrap();
+
+        let seen = broker.seen.lock().unwrap();
+        assert_eq!(
+            seen.attached,
+            vec![(Role::Sender, Some("queue".to_string()))]
+        );
+        assert_eq!(
+            seen.outcomes,
+            vec![
+                Some(DeliveryState::Accepted(Accepted {})),
+                Some(DeliveryState::Released(Released {})),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refused_attach_fails_with_handler_error() {
+        let (connection, served) = connect(&Broker::default()).await;
+
+        let session = connection.begin_session().await.unwrap();
+        match session.attach_sender("missing").await {
+            Err(TransportError::Closed(Some(error))) => assert_eq!(error, not_found()),
+            _ => panic!("the attach must fail with the handler's error"),
+        }
+        let sender = session.attach_sender("queue").await.unwrap();
+        assert!(sender.send(Message::from_value("good")).await.is_ok());
+        connection.close().await.unwrap();
+        served.closed().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_header_is_answered_and_closed() {
+        use crate::protocol_header::{NegotiationError, ProtocolHeader, ProtocolId};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (mut client, server) = MemoryTransport::new().pair();
+        let served = serve(server, Broker::default(), ConnectionOptions::default());
+        let sasl = ProtocolHeader::new(ProtocolId::Sasl);
+        client.write_all(&sasl.encode()).await.unwrap();
+        let mut reply = Vec::new();
+        client.read_to_end(&mut reply).await.unwrap();
+        assert_eq!(reply, ProtocolHeader::new(ProtocolId::Amqp).encode());
+        assert!(matches!(
+            served.closed().await,
+            Err(TransportError::Negotiation(
+                NegotiationError::Rejected { .. }
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_served_session_outlasts_its_window() {
+        let broker = Broker::default();
+        let (connection, served) = MemoryTransport::new()
+            .connect(
+                ConnectionOptions::default(),
+                ConnectionOptions::new("server"),
+                broker.clone(),
+            )
+            .await
+            .unwrap();
+        let count = 2 * crate::session::DEFAULT_SESSION_WINDOW + 100;
+        let session = connection.begin_session().await.unwrap();
+        let sender = session.attach_sender("queue").await.unwrap();
+        for i in 0..count {
+            let outcome = sender.send(Message::from_value(i)).await.unwrap();
+            assert_eq!(outcome, Some(DeliveryState::Accepted(Accepted {})));
+        }
+        connection.close().await.unwrap();
+        served.closed().await.unwrap();
+        assert_eq!(broker.seen.lock().unwrap().received.len(), count as usize);
+    }
+}
@@ -0,0 +1,259 @@
+use amqp_type::composite::transport::frame::performatives::disposition::Disposition;
+use std::time::{Duration, Instant};
+
+/// How many deliveries a batch of dispositions covers at most before it is sent.
+pub const DEFAULT_BATCH_DELIVERIES: u32 = 64;
+
+/// How long a disposition waits in a batch at most before it is sent.
+pub const DEFAULT_BATCH_DELAY: Duration = Duration::from_millis(10);
+
+/// # Disposition Batcher
+/// Collects the dispositions the links of a session send and merges those of neighbouring
+/// deliveries into ranges, so that accepting many messages takes a few disposition frames
+/// instead of one per message.
+///
+/// Two dispositions merge if their delivery-id ranges touch and they agree on role,
+/// settlement and state:
+/// ```text
+/// first=4 last=6 settled accepted  +  first=7 settled accepted  =  first=4 last=7 settled accepted
+/// ```
+/// Only dispositions marked `batchable` wait in the batch. The batch is sent as a whole once
+/// a disposition that is not batchable arrives, once it covers `max_deliveries` deliveries, or
+/// once its oldest disposition has waited `max_delay`.
+///
+/// Ranges are compared with serial number arithmetic, so a range may wrap around from
+/// `u32::MAX` to `0`.
+#[derive(Debug, Clone)]
+pub struct DispositionBatcher {
+    max_deliveries: u32,
+    max_delay: Duration,
+    pending: Vec<Disposition>,
+    deliveries: u32,
+    since: Option<Instant>,
+}
+
+impl DispositionBatcher {
+    pub fn new(max_deliveries: u32, max_delay: Duration) -> Self {
+        DispositionBatcher {
+            max_deliveries,
+            max_delay,
+            pending: Vec::new(),
+            deliveries: 0,
+            since: None,
+        }
+    }
+
+    pub fn max_deliveries(&self) -> u32 {
+        self.max_deliveries
+    }
+
+    pub fn max_delay(&self) -> Duration {
+        self.max_delay
+    }
+
+    /// The number of dispositions waiting to be sent.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// The instant the batch must be sent by, if anything is waiting.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.since.map(|since| since + self.max_delay)
+    }
+
+    /// Adds a disposition to the batch, merging it into a pending range where possible.
+    /// Returns whether the batch must be sent now.
+    pub fn push(&mut self, disposition: Disposition, now: Instant) -> bool {
+        let batchable = disposition.batchable();
+        self.deliveries = self.deliveries.saturating_add(range_len(&disposition));
+        self.since = self.since.or(Some(now));
+        match self
+            .pending
+            .iter_mut()
+            .position(|pending| merge(pending, &disposition).is_some())
+        {
+            Some(index) => self.coalesce(index),
+            None => self.pending.push(disposition),
+        }
+        !batchable
+            || self.deliveries >= self.max_deliveries
+            || self.deadline().is_some_and(|deadline| now >= deadline)
+    }
+
+    /// Merges the pending ranges that the grown range at `index` now touches into one, which
+    /// keeps the place of the earliest of them. Pushing 1, 3 and 2 leaves a single `1..=3`.
+    fn coalesce(&mut self, mut index: usize) {
+        let mut other = 0;
+        while other < self.pending.len() {
+            let (keep, drop) = (index.min(other), index.max(other));
+            let dropped = self.pending[drop].clone();
+            if other != index && merge(&mut self.pending[keep], &dropped).is_some() {
+                self.pending.remove(drop);
+                index = keep;
+                other = 0;
+            } else {
+                other += 1;
+            }
+        }
+    }
+
+    /// Takes all pending dispositions, in the order they were first added.
+    pub fn flush(&mut self) -> Vec<Disposition> {
+        self.deliveries = 0;
+        self.since = None;
+        std::mem::take(&mut self.pending)
+    }
+}
+
+impl Default for DispositionBatcher {
+    fn default() -> Self {
+        DispositionBatcher::new(DEFAULT_BATCH_DELIVERIES, DEFAULT_BATCH_DELAY)
+    }
+}
+
+/// The number of deliveries `first..=last` covers.
+fn range_len(disposition: &Disposition) -> u32 {
+    disposition
+        .first()
+        .distance(disposition.last())
+        .saturating_add(1)
+}
+
+/// Extends `pending` by the range of `disposition` if both agree and their ranges touch.
+fn merge(pending: &mut Disposition, disposition: &Disposition) -> Option<()> {
+    let agree = pending.role() == disposition.role()
+        && pending.settled() == disposition.settled()
+        && pending.state() == disposition.state();
+    if !agree {
+        return None;
+    }
+    let (first, last) = if disposition.first() == pending.last() + 1.into() {
+        (pending.first(), disposition.last())
+    } else if disposition.last() + 1.into() == pending.first() {
+        (disposition.first(), pending.last())
+    } else {
+        return None;
+    };
+    let mut merged = Disposition::new(pending.role(), first)
+        .with_last(last)
+        .with_settled(pending.settled())
+        .with_batchable(pending.batchable() && disposition.batchable());
+    if let Some(state) = pending.state() {
+        merged = merged.with_state(state.clone());
+    }
+    *pending = merged;
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use amqp_type::composite::messaging::delivery_state::accepted::Accepted;
+    use amqp_type::composite::messaging::delivery_state::released::Released;
+    use amqp_type::composite::messaging::delivery_state::DeliveryState;
+    use amqp_type::restricted::role::Role;
+
+    fn accept(delivery_id: u32) -> Disposition {
+        Disposition::new(Role::Receiver, delivery_id.into())
+            .with_settled(true)
+            .with_state(DeliveryState::Accepted(Accepted {}))
+            .with_batchable(true)
+    }
+
+    #[test]
+    fn test_merges_contiguous_deliveries() {
+        let mut batcher = DispositionBatcher::default();
+        let now = Instant::now();
+        for delivery_id in [4, 5, 3, 6] {
+            assert!(!batcher.push(accept(delivery_id), now));
+        }
+        let batch = batcher.flush();
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch[0].first(), 3.into());
+        assert_eq!(batch[0].last(), 6.into());
+        assert!(batch[0].settled());
+        assert!(batcher.is_empty());
+        assert_eq!(batcher.deadline(), None);
+    }
+
+    #[test]
+    fn test_coalesces_ranges_a_delivery_joins() {
+        let mut batcher = DispositionBatcher::default();
+        let now = Instant::now();
+        for delivery_id in [1, 3, 2] {
+            batcher.push(accept(delivery_id), now);
+        }
+        let batch = batcher.flush();
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch[0].first(), 1.into());
+        assert_eq!(batch[0].last(), 3.into());
+
+        for delivery_id in [7, 3, 9, 5, 4, 6] {
+            batcher.push(accept(delivery_id), now);
+        }
+        let batch = batcher.flush();
+        let ranges: Vec<_> = batch.iter().map(|d| (d.first(), d.last())).collect();
+        assert_eq!(ranges, vec![(3.into(), 7.into()), (9.into(), 9.into())]);
+    }
+
+    #[test]
+    fn test_keeps_gaps_and_differing_states_apart() {
+        let mut batcher = DispositionBatcher::default();
+        let now = Instant::now();
+        batcher.push(accept(1), now);
+        batcher.push(accept(3), now);
+        let released = Disposition::new(Role::Receiver, 2.into())
+            .with_settled(true)
+            .with_state(DeliveryState::Released(Released {}))
+            .with_batchable(true);
+        batcher.push(released, now);
+        batcher.push(accept(2).with_settled(false), now);
+        assert_eq!(batcher.len(), 4);
+    }
+
+    #[test]
+    fn test_merges_across_wrap_around() {
+        let mut batcher = DispositionBatcher::default();
+        let now = Instant::now();
+        for delivery_id in [u32::MAX - 1, u32::MAX, 0, 1] {
+            batcher.push(accept(delivery_id), now);
+        }
+        let batch = batcher.flush();
+        assert_eq!(batch.len(), 1);
+        assert_eq!(batch[0].first(), (u32::MAX - 1).into());
+        assert_eq!(batch[0].last(), 1.into());
+    }
+
+    #[test]
+    fn test_flushes_by_size() {
+        let mut batcher = DispositionBatcher::new(3, Duration::from_secs(1));
+        let now = Instant::now();
+        assert!(!batcher.push(accept(0), now));
+        assert!(!batcher.push(accept(5), now));
+        assert!(batcher.push(accept(1), now));
+    }
+
+    #[test]
+    fn test_flushes_by_time() {
+        let mut batcher = DispositionBatcher::new(100, Duration::from_millis(10));
+        let now = Instant::now();
+        batcher.push(accept(0), now);
+        assert_eq!(batcher.deadline(), Some(now + Duration::from_millis(10)));
+        assert!(batcher.push(accept(1), now + Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn test_not_batchable_flushes_at_once() {
+        let mut batcher = DispositionBatcher::default();
+        let now = Instant::now();
+        batcher.push(accept(0), now);
+        assert!(batcher.push(accept(1).with_batchable(false), now));
+        let batch = batcher.flush();
+        assert_eq!(batch.len(), 1);
+        assert!(!batch[0].batchable());
+    }
+}
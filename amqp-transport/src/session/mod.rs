@@ -1 +1,1462 @@
+pub mod batcher;
 
+use crate::allocator::Allocator;
+use crate::connection::clock::{Clock, SystemClock};
+use crate::constants::MIN_MAX_FRAME_SIZE;
+use crate::frame::amqp_frame::AmqpFrame;
+use crate::link::{LinkEndpoint, LinkEvent, LinkState};
+use crate::session::batcher::DispositionBatcher;
+use amqp_type::composite::transport::frame::performative::Performative;
+use amqp_type::composite::transport::frame::performatives::attach::Attach;
+use amqp_type::composite::transport::frame::performatives::begin::Begin;
+use amqp_type::composite::transport::frame::performatives::disposition::Disposition;
+use amqp_type::composite::transport::frame::performatives::end::End;
+use amqp_type::composite::transport::frame::performatives::flow::Flow;
+use amqp_type::composite::transport::frame::performatives::transfer::Transfer;
+use amqp_type::composite::transport::transport::error::Error;
+use amqp_type::error::amqp_error::AmqpError;
+use amqp_type::error::connection_error::ConnectionError;
+use amqp_type::error::session_error::SessionError;
+use amqp_type::error::AppError;
+use amqp_type::restricted::delivery_number::DeliveryNumber;
+use amqp_type::restricted::handle::Handle;
+use amqp_type::restricted::role::Role;
+use amqp_type::restricted::transfer_number::TransferNumber;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The incoming and outgoing window of a session the peer began.
+pub const DEFAULT_SESSION_WINDOW: u32 = 2048;
+
+/// # Session State
+/// The states of a session endpoint as defined in spec section 2.5.5.
+/// ```text
+/// UNMAPPED    --S:BEGIN--> BEGIN_SENT   UNMAPPED    --R:BEGIN--> BEGIN_RCVD
+/// BEGIN_SENT  --R:BEGIN--> MAPPED       BEGIN_RCVD  --S:BEGIN--> MAPPED
+/// MAPPED      --S:END-->   END_SENT*    MAPPED      --R:END-->   END_RCVD
+/// END_SENT*   --R:END-->   UNMAPPED     END_RCVD    --S:END-->   UNMAPPED
+/// ```
+/// `END_SENT*` is `DISCARDING` if the end was sent because of an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionState {
+    Unmapped,
+    BeginSent,
+    BeginRcvd,
+    Mapped,
+    EndSent,
+    EndRcvd,
+    Discarding,
+}
+
+/// Something that happened on the session that the links need to know about.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionEvent {
+    /// The peer began its side of the session.
+    Begun(Begin),
+    /// The peer ended its side of the session, possibly because of an error.
+    Ended(Option<Error>),
+    /// Something happened on the link with the given local handle.
+    Link(Handle, LinkEvent),
+}
+
+/// # Session Endpoint
+/// The state machine of one end of a session, without any I/O.
+///
+/// Besides the begin and end handshakes, the endpoint does the transfer bookkeeping of spec
+/// section 2.5.6:
+/// ```text
+/// next-incoming-id     the transfer-id expected next from the peer
+/// incoming-window      how many more transfers the peer may send us
+/// next-outgoing-id     the transfer-id of the next transfer we send
+/// outgoing-window      how many more transfers we allow ourselves to send
+/// remote-incoming-window   how many more transfers the peer accepts from us
+/// remote-outgoing-window   how many more transfers the peer intends to send
+/// ```
+/// A transfer that would exceed the remote incoming window (or our outgoing window) is held
+/// back until a flow from the peer (or [`SessionEndpoint::set_outgoing_window`]) opens the window
+/// again. A transfer the peer sends beyond our incoming window is answered with
+/// `amqp:session:window-violation`.
+///
+/// Our windows are not used up for good: once half of the incoming or outgoing window has been
+/// used, it is restored to the size it was set to and a session flow tells the peer.
+///
+/// The frames to send are queued with the local channel of the session and polled with
+/// [`SessionEndpoint::poll_transmit`].
+///
+/// The session owns the links attached to it. Frames from the peer are routed to the link
+/// mapped to their handle, and an attach for a new link creates a [`LinkEndpoint`] that is
+/// reported with [`LinkEvent::Attached`] and must be answered with [`LinkEndpoint::send_attach`].
+/// Links are dropped once both ends have detached them. A link detached without closing it
+/// while it still has unsettled deliveries is kept aside instead, to be resumed with
+/// [`SessionEndpoint::resume_link`] or by an attach from the peer with the same link name.
+///
+/// Local handles are the lowest free ones up to the smaller `handle-max` of both begins, and
+/// are only reused once the link is dropped. An attach on a handle beyond our `handle-max`
+/// is a `amqp:connection:framing-error`, on a handle already in use a
+/// `amqp:session:handle-in-use`; any other frame on an unknown handle is a
+/// `amqp:session:unattached-handle`.
+///
+/// Delivery-ids are numbered by the session across all its links. A disposition from the peer
+/// is handed to every link of the opposite role, each of which settles its deliveries within
+/// the range. The dispositions the links send are merged into ranges by a
+/// [`DispositionBatcher`]; batched dispositions are sent by [`SessionEndpoint::handle_timeout`]
+/// once they have waited long enough.
+#[derive(Debug)]
+pub struct SessionEndpoint {
+    state: SessionState,
+    channel: u16,
+    remote_channel: Option<u16>,
+    local_begin: Begin,
+    remote_begin: Option<Begin>,
+    next_incoming_id: TransferNumber,
+    incoming_window: u32,
+    /// The incoming window is restored to this size once half of it is used.
+    max_incoming_window: u32,
+    next_outgoing_id: TransferNumber,
+    outgoing_window: u32,
+    /// The outgoing window is restored to this size once half of it is used.
+    max_outgoing_window: u32,
+    next_delivery_id: DeliveryNumber,
+    remote_incoming_window: u32,
+    remote_outgoing_window: u32,
+    links: BTreeMap<Handle, LinkEndpoint>,
+    suspended: BTreeMap<String, LinkEndpoint>,
+    handles: Allocator,
+    max_frame_size: u32,
+    remote_handles: HashMap<Handle, Handle>,
+    blocked: VecDeque<AmqpFrame>,
+    batcher: DispositionBatcher,
+    clock: Arc<dyn Clock>,
+    transmit: VecDeque<AmqpFrame>,
+    events: VecDeque<SessionEvent>,
+}
+
+impl SessionEndpoint {
+    /// Creates an unmapped session on the local `channel`. The transfer-id and windows of
+    /// `local_begin` are the initial values of the session.
+    pub fn new(channel: u16, local_begin: Begin) -> Self {
+        SessionEndpoint {
+            state: SessionState::Unmapped,
+            channel,
+            remote_channel: None,
+            next_incoming_id: TransferNumber::default(),
+            incoming_window: local_begin.incoming_window(),
+            max_incoming_window: local_begin.incoming_window(),
+            next_outgoing_id: local_begin.next_outgoing_id(),
+            outgoing_window: local_begin.outgoing_window(),
+            max_outgoing_window: local_begin.outgoing_window(),
+            next_delivery_id: DeliveryNumber::default(),
+            handles: Allocator::new(local_begin.handle_max()),
+            max_frame_size: MIN_MAX_FRAME_SIZE,
+            remote_incoming_window: 0,
+            remote_outgoing_window: 0,
+            local_begin,
+            remote_begin: None,
+            links: BTreeMap::new(),
+            suspended: BTreeMap::new(),
+            remote_handles: HashMap::new(),
+            blocked: VecDeque::new(),
+            batcher: DispositionBatcher::default(),
+            clock: Arc::new(SystemClock),
+            transmit: VecDeque::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Replaces the system clock, e.g. with one a test advances by hand.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    pub(crate) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn state(&self) -> SessionState {
+        self.state
+    }
+
+    pub fn channel(&self) -> u16 {
+        self.channel
+    }
+
+    pub fn remote_channel(&self) -> Option<u16> {
+        self.remote_channel
+    }
+
+    pub fn local_begin(&self) -> &Begin {
+        &self.local_begin
+    }
+
+    pub fn remote_begin(&self) -> Option<&Begin> {
+        self.remote_begin.as_ref()
+    }
+
+    pub fn next_incoming_id(&self) -> TransferNumber {
+        self.next_incoming_id
+    }
+
+    pub fn incoming_window(&self) -> u32 {
+        self.incoming_window
+    }
+
+    pub fn next_outgoing_id(&self) -> TransferNumber {
+        self.next_outgoing_id
+    }
+
+    pub fn outgoing_window(&self) -> u32 {
+        self.outgoing_window
+    }
+
+    pub fn remote_incoming_window(&self) -> u32 {
+        self.remote_incoming_window
+    }
+
+    pub fn remote_outgoing_window(&self) -> u32 {
+        self.remote_outgoing_window
+    }
+
+    /// Whether transfers are held back because a window is closed.
+    pub fn is_blocked(&self) -> bool {
+        !self.blocked.is_empty()
+    }
+
+    pub fn max_frame_size(&self) -> u32 {
+        self.max_frame_size
+    }
+
+    /// Sets the largest frame the peer accepts, which limits the transfer frames of the links.
+    pub fn set_max_frame_size(&mut self, max_frame_size: u32) -> Result<(), AppError> {
+        for link in self.links.values_mut() {
+            link.set_max_frame_size(max_frame_size)?;
+        }
+        self.max_frame_size = max_frame_size;
+        Ok(())
+    }
+
+    pub fn disposition_batcher(&self) -> &DispositionBatcher {
+        &self.batcher
+    }
+
+    /// Changes when batched dispositions are sent: once they cover `max_deliveries`
+    /// deliveries or the oldest has waited `max_delay`. Waiting dispositions are sent first.
+    pub fn set_disposition_batching(&mut self, max_deliveries: u32, max_delay: Duration) {
+        self.flush_dispositions();
+        self.batcher = DispositionBatcher::new(max_deliveries, max_delay);
+    }
+
+    /// The instant [`SessionEndpoint::handle_timeout`] must be called next, if any.
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.batcher.deadline()
+    }
+
+    /// Sends the batched dispositions once they have waited long enough.
+    pub fn handle_timeout(&mut self) {
+        if self
+            .batcher
+            .deadline()
+            .is_some_and(|deadline| self.clock.now() >= deadline)
+        {
+            self.flush_dispositions();
+        }
+    }
+
+    pub fn link(&self, handle: Handle) -> Option<&LinkEndpoint> {
+        self.links.get(&handle)
+    }
+
+    pub fn link_mut(&mut self, handle: Handle) -> Option<&mut LinkEndpoint> {
+        self.links.get_mut(&handle)
+    }
+
+    /// The detached link with the given name that is kept for its unsettled deliveries.
+    pub fn suspended_link(&self, name: &str) -> Option<&LinkEndpoint> {
+        self.suspended.get(name)
+    }
+
+    /// Takes a suspended link, e.g. to resume it on another session.
+    pub fn take_suspended_link(&mut self, name: &str) -> Option<LinkEndpoint> {
+        self.suspended.remove(name)
+    }
+
+    /// Polls the frames to send, the session's own before those of its links.
+    pub fn poll_transmit(&mut self) -> Option<AmqpFrame> {
+        loop {
+            if let Some(frame) = self.transmit.pop_front() {
+                return Some(frame);
+            }
+            if !matches!(
+                self.state,
+                SessionState::BeginSent | SessionState::Mapped | SessionState::EndRcvd
+            ) {
+                return None;
+            }
+            let next_delivery_id = &mut self.next_delivery_id;
+            let (performative, payload) = self
+                .links
+                .values_mut()
+                .find_map(|link| link.poll_transmit(next_delivery_id))?;
+            self.remove_detached_links();
+            match performative {
+                Performative::Transfer(_) => {
+                    let frame = AmqpFrame::new(self.channel, performative).with_payload(payload);
+                    self.blocked.push_back(frame);
+                    self.release_blocked();
+                }
+                Performative::Flow(flow) => {
+                    self.push(Performative::Flow(self.stamp(flow)), payload)
+                }
+                Performative::Disposition(disposition) => {
+                    if self.batcher.push(disposition, self.clock.now()) {
+                        self.flush_dispositions();
+                    }
+                }
+                performative => self.push(performative, payload),
+            }
+        }
+    }
+
+    pub fn poll_event(&mut self) -> Option<SessionEvent> {
+        self.events.pop_front()
+    }
+
+    pub(crate) fn has_transmit(&self) -> bool {
+        !self.transmit.is_empty() || self.links.values().any(LinkEndpoint::has_transmit)
+    }
+
+    /// The flow carrying the current state of the session, without any link state.
+    pub fn flow(&self) -> Flow {
+        Flow::new(
+            self.next_incoming_id,
+            self.incoming_window,
+            self.next_outgoing_id,
+            self.outgoing_window,
+        )
+    }
+
+    /// Attaches a link on the lowest free handle, which is returned. The handle of `attach`
+    /// is replaced by it.
+    pub fn attach_link(&mut self, attach: Attach) -> Result<Handle, AppError> {
+        if !matches!(self.state, SessionState::BeginSent | SessionState::Mapped) {
+            Err(AmqpError::IllegalState)?
+        }
+        let handle = self.free_handle()?;
+        let mut link = LinkEndpoint::new(attach.with_handle(handle));
+        link.set_max_frame_size(self.max_frame_size)?;
+        link.send_attach()?;
+        self.links.insert(handle, link);
+        Ok(handle)
+    }
+
+    /// Attaches a link that was detached without closing it again on the lowest free handle,
+    /// which is returned. The link sends its unsettled map so that the peer can recover the
+    /// deliveries that were in flight.
+    pub fn resume_link(&mut self, mut link: LinkEndpoint) -> Result<Handle, AppError> {
+        if !matches!(self.state, SessionState::BeginSent | SessionState::Mapped) {
+            Err(AmqpError::IllegalState)?
+        }
+        let handle = self.free_handle()?;
+        if let Err(error) = link.resume(handle) {
+            self.handles.release(handle);
+            Err(error)?
+        }
+        link.set_max_frame_size(self.max_frame_size)?;
+        link.send_attach()?;
+        self.links.insert(handle, link);
+        Ok(handle)
+    }
+
+    /// Sends the begin frame, answering the peer's begin if it began the session.
+    pub fn send_begin(&mut self) -> Result<(), AppError> {
+        self.state = match self.state {
+            SessionState::Unmapped => SessionState::BeginSent,
+            SessionState::BeginRcvd => SessionState::Mapped,
+            _ => Err(AmqpError::IllegalState)?,
+        };
+        let mut begin = Begin::new(
+            self.next_outgoing_id,
+            self.incoming_window,
+            self.outgoing_window,
+        )
+        .with_handle_max(self.local_begin.handle_max())
+        .with_offered_capabilities(self.local_begin.offered_capabilities().to_vec())
+        .with_desired_capabilities(self.local_begin.desired_capabilities().to_vec());
+        if let Some(properties) = self.local_begin.properties() {
+            begin = begin.with_properties(properties.clone());
+        }
+        if let Some(remote_channel) = self.remote_channel {
+            begin = begin.with_remote_channel(remote_channel);
+        }
+        self.push(Performative::Begin(begin), Vec::new());
+        Ok(())
+    }
+
+    /// Sends the end frame. Ending because of an error moves the session to `DISCARDING`,
+    /// in which frames from the peer are ignored until its end arrives. A session whose begin
+    /// is not answered yet may be ended too; it then waits for the peer's begin and end.
+    pub fn send_end(&mut self, error: Option<Error>) -> Result<(), AppError> {
+        self.state = match (self.state, &error) {
+            (SessionState::BeginSent, _) => SessionState::EndSent,
+            (SessionState::Mapped, None) => SessionState::EndSent,
+            (SessionState::Mapped, Some(_)) => SessionState::Discarding,
+            (SessionState::EndRcvd, _) => SessionState::Unmapped,
+            _ => Err(AmqpError::IllegalState)?,
+        };
+        self.blocked.clear();
+        self.flush_dispositions();
+        self.push(Performative::End(End::new(error)), Vec::new());
+        Ok(())
+    }
+
+    /// Sends a session flow, e.g. to tell the peer about a changed window.
+    pub fn send_flow(&mut self) -> Result<(), AppError> {
+        self.send_performative(Performative::Flow(self.flow()))
+    }
+
+    /// Sends a frame for a link (attach, detach, disposition or a link flow).
+    /// Transfers go through [`SessionEndpoint::send_transfer`].
+    pub fn send_performative(&mut self, performative: Performative) -> Result<(), AppError> {
+        if matches!(performative, Performative::Transfer(_)) || !self.is_mapped() {
+            Err(AmqpError::IllegalState)?
+        }
+        self.push(performative, Vec::new());
+        Ok(())
+    }
+
+    /// Sends a transfer frame. While a window is closed, the transfer is held back and sent,
+    /// in order, as soon as the window opens.
+    pub fn send_transfer(&mut self, transfer: Transfer, payload: Vec<u8>) -> Result<(), AppError> {
+        if self.state != SessionState::Mapped {
+            Err(AmqpError::IllegalState)?
+        }
+        self.blocked.push_back(
+            AmqpFrame::new(self.channel, Performative::Transfer(transfer)).with_payload(payload),
+        );
+        self.release_blocked();
+        Ok(())
+    }
+
+    /// Changes how many transfers the peer may send, and tells the peer once the session is mapped.
+    /// The window is restored to this size whenever half of it is used.
+    pub fn set_incoming_window(&mut self, incoming_window: u32) -> Result<(), AppError> {
+        self.incoming_window = incoming_window;
+        self.max_incoming_window = incoming_window;
+        match self.state {
+            SessionState::Mapped => self.send_flow(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Changes how many transfers we allow ourselves to send, and tells the peer once the
+    /// session is mapped. Transfers held back by a closed outgoing window are released, and the
+    /// window is restored to this size whenever half of it is used.
+    pub fn set_outgoing_window(&mut self, outgoing_window: u32) -> Result<(), AppError> {
+        self.outgoing_window = outgoing_window;
+        self.max_outgoing_window = outgoing_window;
+        match self.state {
+            SessionState::Mapped => {
+                self.send_flow()?;
+                self.release_blocked();
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Handles a frame the peer sent on this session.
+    pub fn on_frame(&mut self, frame: AmqpFrame) -> Result<(), AppError> {
+        let channel = frame.channel();
+        match frame.performative() {
+            Performative::Begin(begin) => return self.on_begin(channel, begin.clone()),
+            Performative::End(end) => return self.on_end(end.error().cloned()),
+            _ => {}
+        }
+        match self.state {
+            SessionState::Discarding => return Ok(()),
+            // The peer may pipeline frames right after its begin.
+            SessionState::BeginRcvd | SessionState::Mapped | SessionState::EndSent => {}
+            _ => Err(AmqpError::IllegalState)?,
+        }
+        match frame.performative() {
+            Performative::Flow(flow) => self.on_flow(flow.clone(), frame),
+            Performative::Transfer(_) => self.on_transfer(frame),
+            Performative::Attach(attach) => {
+                let handle = self.map_remote_handle(attach)?;
+                self.on_link_frame(handle, frame)
+            }
+            Performative::Detach(detach) => {
+                let handle = self.local_handle(detach.handle())?;
+                self.on_link_frame(handle, frame)
+            }
+            Performative::Open(_)
+            | Performative::Close(_)
+            | Performative::Begin(_)
+            | Performative::End(_) => Err(AmqpError::IllegalState)?,
+            Performative::Disposition(disposition) => self.on_disposition(disposition.clone()),
+        }
+    }
+
+    fn on_disposition(&mut self, disposition: Disposition) -> Result<(), AppError> {
+        let handles: Vec<Handle> = self
+            .links
+            .iter()
+            .filter(|(_, link)| link.role() != disposition.role())
+            .map(|(handle, _)| *handle)
+            .collect();
+        for handle in handles {
+            self.on_link_performative(
+                handle,
+                Performative::Disposition(disposition.clone()),
+                Vec::new(),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn on_link_frame(&mut self, handle: Handle, frame: AmqpFrame) -> Result<(), AppError> {
+        let (_, performative, payload) = frame.into_parts();
+        self.on_link_performative(handle, performative, payload)
+    }
+
+    fn on_link_performative(
+        &mut self,
+        handle: Handle,
+        performative: Performative,
+        payload: Vec<u8>,
+    ) -> Result<(), AppError> {
+        let link = self
+            .links
+            .get_mut(&handle)
+            .ok_or(AmqpError::InternalError)?;
+        link.on_performative(performative, payload)?;
+        while let Some(event) = link.poll_event() {
+            self.events.push_back(SessionEvent::Link(handle, event));
+        }
+        self.remove_detached_links();
+        Ok(())
+    }
+
+    /// Finds the local handle of the link an attach is meant for. An attach answering ours
+    /// carries the same link name and the opposite role. Any other attach resumes the suspended
+    /// link of that name, or else starts a new link.
+    fn map_remote_handle(&mut self, attach: &Attach) -> Result<Handle, AppError> {
+        if attach.handle() > self.local_begin.handle_max() {
+            Err(ConnectionError::FramingError)?
+        }
+        if self.remote_handles.contains_key(&attach.handle()) {
+            Err(SessionError::HandleInUse)?
+        }
+        let answered = self.links.values().find(|link| {
+            link.state() == LinkState::AttachSent
+                && link.name() == attach.name()
+                && link.role() != attach.role()
+        });
+        let suspended = self
+            .suspended
+            .get(attach.name())
+            .is_some_and(|link| link.role() != attach.role());
+        let handle = match answered {
+            Some(link) => link.handle(),
+            None if suspended => {
+                let handle = self.free_handle()?;
+                let mut link = self
+                    .suspended
+                    .remove(attach.name())
+                    .ok_or(AmqpError::InternalError)?;
+                link.resume(handle)?;
+                link.set_max_frame_size(self.max_frame_size)?;
+                self.links.insert(handle, link);
+                handle
+            }
+            None => {
+                let handle = self.free_handle()?;
+                let role = match attach.role() {
+                    Role::Sender => Role::Receiver,
+                    Role::Receiver => Role::Sender,
+                };
+                let mut local_attach = Attach::new(attach.name().to_string(), handle, role);
+                if let Some(source) = attach.source() {
+                    local_attach = local_attach.with_source(source.clone());
+                }
+                if let Some(target) = attach.target() {
+                    local_attach = local_attach.with_target(target.clone());
+                }
+                let mut link = LinkEndpoint::new(local_attach);
+                link.set_max_frame_size(self.max_frame_size)?;
+                self.links.insert(handle, link);
+                handle
+            }
+        };
+        self.remote_handles.insert(attach.handle(), handle);
+        Ok(handle)
+    }
+
+    fn local_handle(&self, remote_handle: Handle) -> Result<Handle, AppError> {
+        match self.remote_handles.get(&remote_handle) {
+            Some(handle) => Ok(*handle),
+            None => Err(SessionError::UnattachedHandle)?,
+        }
+    }
+
+    fn free_handle(&mut self) -> Result<Handle, AppError> {
+        match self.handles.allocate() {
+            Some(handle) => Ok(handle),
+            None => Err(AmqpError::ResourceLimitExceeded)?,
+        }
+    }
+
+    fn remove_detached_links(&mut self) {
+        let detached: Vec<Handle> = self
+            .links
+            .iter()
+            .filter(|(_, link)| link.state() == LinkState::Detached && !link.has_transmit())
+            .map(|(handle, _)| *handle)
+            .collect();
+        for handle in detached {
+            if let Some(link) = self.links.remove(&handle) {
+                if !link.closed() && !link.unsettled().is_empty() {
+                    self.suspended.insert(link.name().to_string(), link);
+                }
+            }
+            self.handles.release(handle);
+            self.remote_handles.retain(|_, local| *local != handle);
+        }
+    }
+
+    /// Fills in the current session fields of a flow sent by a link.
+    fn stamp(&self, link_flow: Flow) -> Flow {
+        let mut flow = self.flow();
+        if let Some(handle) = link_flow.handle() {
+            flow = flow.with_handle(handle);
+        }
+        if let Some(delivery_count) = link_flow.delivery_count() {
+            flow = flow.with_delivery_count(delivery_count);
+        }
+        if let Some(link_credit) = link_flow.link_credit() {
+            flow = flow.with_link_credit(link_credit);
+        }
+        if let Some(available) = link_flow.available() {
+            flow = flow.with_available(available);
+        }
+        if link_flow.drain() {
+            flow = flow.with_drain(true);
+        }
+        if link_flow.echo() {
+            flow = flow.with_echo(true);
+        }
+        if let Some(properties) = link_flow.properties() {
+            flow = flow.with_properties(properties.clone());
+        }
+        flow
+    }
+
+    fn on_begin(&mut self, channel: u16, begin: Begin) -> Result<(), AppError> {
+        self.state = match self.state {
+            SessionState::Unmapped => SessionState::BeginRcvd,
+            SessionState::BeginSent => SessionState::Mapped,
+            // We ended the session before the peer answered our begin.
+            SessionState::EndSent if self.remote_channel.is_none() => SessionState::EndSent,
+            _ => Err(AmqpError::IllegalState)?,
+        };
+        self.remote_channel = Some(channel);
+        self.next_incoming_id = begin.next_outgoing_id();
+        self.remote_incoming_window = begin.incoming_window();
+        self.remote_outgoing_window = begin.outgoing_window();
+        self.handles
+            .set_max(begin.handle_max().min(self.local_begin.handle_max()));
+        self.remote_begin = Some(begin.clone());
+        self.events.push_back(SessionEvent::Begun(begin));
+        Ok(())
+    }
+
+    fn on_end(&mut self, error: Option<Error>) -> Result<(), AppError> {
+        self.state = match self.state {
+            SessionState::Mapped => SessionState::EndRcvd,
+            SessionState::EndSent | SessionState::Discarding => SessionState::Unmapped,
+            _ => Err(AmqpError::IllegalState)?,
+        };
+        self.blocked.clear();
+        self.events.push_back(SessionEvent::Ended(error));
+        Ok(())
+    }
+
+    fn on_flow(&mut self, flow: Flow, frame: AmqpFrame) -> Result<(), AppError> {
+        self.next_incoming_id = flow.next_outgoing_id();
+        self.remote_outgoing_window = flow.outgoing_window();
+        // remote-incoming-window = next-incoming-id(flow) + incoming-window(flow) - next-outgoing-id
+        self.remote_incoming_window = self
+            .next_outgoing_id
+            .distance(flow.next_incoming_id() + flow.incoming_window().into());
+        if self.state == SessionState::Mapped {
+            self.release_blocked();
+        }
+        match flow.handle() {
+            Some(handle) => {
+                let handle = self.local_handle(handle)?;
+                self.on_link_frame(handle, frame)
+            }
+            None if flow.echo() && self.state == SessionState::Mapped => self.send_flow(),
+            None => Ok(()),
+        }
+    }
+
+    fn on_transfer(&mut self, frame: AmqpFrame) -> Result<(), AppError> {
+        if self.incoming_window == 0 {
+            Err(SessionError::WindowViolation)?
+        }
+        self.next_incoming_id += 1.into();
+        self.incoming_window -= 1;
+        self.remote_outgoing_window = self.remote_outgoing_window.saturating_sub(1);
+        let handle = match frame.performative() {
+            Performative::Transfer(transfer) => self.local_handle(transfer.handle())?,
+            _ => Err(AmqpError::InternalError)?,
+        };
+        self.on_link_frame(handle, frame)?;
+        if self.incoming_window <= self.max_incoming_window / 2 {
+            self.incoming_window = self.max_incoming_window;
+            self.send_window_flow();
+        }
+        Ok(())
+    }
+
+    fn flush_dispositions(&mut self) {
+        for disposition in self.batcher.flush() {
+            self.push(Performative::Disposition(disposition), Vec::new());
+        }
+    }
+
+    fn release_blocked(&mut self) {
+        while self.can_transfer() {
+            let Some(frame) = self.blocked.pop_front() else {
+                break;
+            };
+            self.next_outgoing_id += 1.into();
+            self.outgoing_window -= 1;
+            self.remote_incoming_window -= 1;
+            self.transmit.push_back(frame);
+            if self.outgoing_window <= self.max_outgoing_window / 2 {
+                self.outgoing_window = self.max_outgoing_window;
+                self.send_window_flow();
+            }
+        }
+    }
+
+    /// Tells the peer about a restored window. Only a mapped session that has not sent its end
+    /// needs to; a session the peer began announces its windows in its begin.
+    fn send_window_flow(&mut self) {
+        if self.state == SessionState::Mapped {
+            self.push(Performative::Flow(self.flow()), Vec::new());
+        }
+    }
+
+    fn can_transfer(&self) -> bool {
+        self.remote_incoming_window > 0 && self.outgoing_window > 0
+    }
+
+    fn is_mapped(&self) -> bool {
+        matches!(self.state, SessionState::Mapped | SessionState::EndRcvd)
+    }
+
+    fn push(&mut self, performative: Performative, payload: Vec<u8>) {
+        self.transmit
+            .push_back(AmqpFrame::new(self.channel, performative).with_payload(payload));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connection::clock::ManualClock;
+    use amqp_type::composite::messaging::delivery_state::accepted::Accepted;
+    use amqp_type::composite::messaging::delivery_state::DeliveryState;
+    use amqp_type::composite::transport::frame::performatives::detach::Detach;
+    use amqp_type::primitive::variable_width::symbol::Symbol;
+    use amqp_type::restricted::delivery_tag::DeliveryTag;
+    use SessionState::*;
+
+    #[derive(Debug, Clone, Copy)]
+    enum Step {
+        SendBegin,
+        SendEnd,
+        SendEndWithError,
+        ReceiveBegin,
+        ReceiveEnd,
+    }
+    use Step::*;
+
+    const REMOTE_CHANNEL: u16 = 5;
+
+    fn remote(performative: Performative) -> AmqpFrame {
+        AmqpFrame::new(REMOTE_CHANNEL, performative)
+    }
+
+    fn remote_begin(incoming_window: u32) -> AmqpFrame {
+        remote(Performative::Begin(Begin::new(
+            100.into(),
+            incoming_window,
+            10,
+        )))
+    }
+
+    fn transfer() -> Transfer {
+        Transfer::new(0)
+    }
+
+    fn apply(endpoint: &mut SessionEndpoint, step: Step) -> Result<(), AppError> {
+        match step {
+            SendBegin => endpoint.send_begin(),
+            SendEnd => endpoint.send_end(None),
+            SendEndWithError => {
+                endpoint.send_end(Some(Error::new(Symbol::with_ascii("amqp:internal-error"))))
+            }
+            ReceiveBegin => endpoint.on_frame(remote_begin(10)),
+            ReceiveEnd => endpoint.on_frame(remote(Performative::End(End::default()))),
+        }
+    }
+
+    fn run(steps: &[Step]) -> SessionEndpoint {
+        let mut endpoint = SessionEndpoint::new(1, Begin::new(0.into(), 10, 10));
+        for step in steps {
+            apply(&mut endpoint, *step)
+                .unwrap_or_else(|e| panic!("{:?} failed in {:?}: {}", step, endpoint.state(), e));
+        }
+        endpoint
+    }
+
+    fn mapped(remote_incoming_window: u32) -> SessionEndpoint {
+        let mut endpoint = SessionEndpoint::new(1, Begin::new(0.into(), 10, 10));
+        endpoint.send_begin().unwrap();
+        endpoint
+            .on_frame(remote_begin(remote_incoming_window))
+            .unwrap();
+        while endpoint.poll_transmit().is_some() {}
+        while endpoint.poll_event().is_some() {}
+        endpoint
+    }
+
+    const REMOTE_HANDLE: Handle = 3;
+
+    /// Lets the peer attach a sending link, which we answer with `credit`.
+    fn with_receiver(mut endpoint: SessionEndpoint, credit: u32) -> SessionEndpoint {
+        let attach = Attach::new("link".to_string(), REMOTE_HANDLE, Role::Sender);
+        endpoint
+            .on_frame(remote(Performative::Attach(attach)))
+            .unwrap();
+        let link = endpoint.link_mut(0).unwrap();
+        link.send_attach().unwrap();
+        link.grant_credit(credit).unwrap();
+        while endpoint.poll_transmit().is_some() {}
+        while endpoint.poll_event().is_some() {}
+        endpoint
+    }
+
+    /// A settled delivery from the peer's sending link.
+    fn remote_transfer() -> AmqpFrame {
+        let transfer = Transfer::new(REMOTE_HANDLE)
+            .with_delivery_id(0.into())
+            .with_delivery_tag(tag(0))
+            .with_settled(true);
+        remote(Performative::Transfer(transfer))
+    }
+
+    fn tag(tag: u8) -> DeliveryTag {
+        DeliveryTag::new(vec![tag]).unwrap()
+    }
+
+    fn sent_transfers(endpoint: &mut SessionEndpoint) -> usize {
+        std::iter::from_fn(|| endpoint.poll_transmit())
+            .filter(|frame| matches!(frame.performative(), Performative::Transfer(_)))
+            .count()
+    }
+
+    #[test]
+    fn test_legal_transitions() {
+        let paths: &[(&[Step], SessionState)] = &[
+            (&[], Unmapped),
+            (&[SendBegin], BeginSent),
+            (&[ReceiveBegin], BeginRcvd),
+            (&[SendBegin, ReceiveBegin], Mapped),
+            (&[ReceiveBegin, SendBegin], Mapped),
+            (&[SendBegin, ReceiveBegin, SendEnd], EndSent),
+            (&[SendBegin, ReceiveBegin, SendEndWithError], Discarding),
+            (&[SendBegin, ReceiveBegin, ReceiveEnd], EndRcvd),
+            (&[SendBegin, ReceiveBegin, SendEnd, ReceiveEnd], Unmapped),
+            (
+                &[SendBegin, ReceiveBegin, SendEndWithError, ReceiveEnd],
+                Unmapped,
+            ),
+            (&[SendBegin, ReceiveBegin, ReceiveEnd, SendEnd], Unmapped),
+            (&[SendBegin, SendEnd], EndSent),
+            (&[SendBegin, SendEndWithError], EndSent),
+            (&[SendBegin, SendEnd, ReceiveBegin], EndSent),
+            (&[SendBegin, SendEnd, ReceiveBegin, ReceiveEnd], Unmapped),
+        ];
+        for (steps, expected) in paths {
+            assert_eq!(run(steps).state(), *expected, "{:?}", steps);
+        }
+    }
+
+    #[test]
+    fn test_illegal_transitions() {
+        let paths: &[(&[Step], Step)] = &[
+            (&[], SendEnd),
+            (&[], ReceiveEnd),
+            (&[SendBegin], SendBegin),
+            (&[SendBegin, SendEnd], SendEnd),
+            (&[SendBegin, SendEnd, ReceiveBegin], ReceiveBegin),
+            (&[ReceiveBegin], ReceiveBegin),
+            (&[SendBegin, ReceiveBegin], SendBegin),
+            (&[SendBegin, ReceiveBegin], ReceiveBegin),
+            (&[SendBegin, ReceiveBegin, SendEnd], SendEnd),
+            (&[SendBegin, ReceiveBegin, ReceiveEnd], ReceiveEnd),
+        ];
+        for (steps, step) in paths {
+            let mut endpoint = run(steps);
+            let before = endpoint.state();
+            assert!(
+                matches!(
+                    apply(&mut endpoint, *step),
+                    Err(AppError::Amqp(AmqpError::IllegalState))
+                ),
+                "{:?} then {:?}",
+                steps,
+                step
+            );
+            assert_eq!(endpoint.state(), before);
+        }
+    }
+
+    #[test]
+    fn test_answering_begin_carries_remote_channel() {
+        let mut endpoint = run(&[ReceiveBegin]);
+        assert_eq!(
+            endpoint.poll_event(),
+            Some(SessionEvent::Begun(Begin::new(100.into(), 10, 10)))
+        );
+        endpoint.send_begin().unwrap();
+        let frame = endpoint.poll_transmit().unwrap();
+        assert_eq!(frame.channel(), 1);
+        match frame.performative() {
+            Performative::Begin(begin) => assert_eq!(begin.remote_channel(), Some(REMOTE_CHANNEL)),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(endpoint.remote_channel(), Some(REMOTE_CHANNEL));
+        assert_eq!(endpoint.next_incoming_id(), 100.into());
+    }
+
+    #[test]
+    fn test_transfers_update_outgoing_state() {
+        let mut endpoint = mapped(5);
+        endpoint.send_transfer(transfer(), vec![1, 2, 3]).unwrap();
+        endpoint.send_transfer(transfer(), vec![4]).unwrap();
+        assert_eq!(sent_transfers(&mut endpoint), 2);
+        assert_eq!(endpoint.next_outgoing_id(), 2.into());
+        assert_eq!(endpoint.outgoing_window(), 8);
+        assert_eq!(endpoint.remote_incoming_window(), 3);
+    }
+
+    #[test]
+    fn test_transfers_blocked_until_flow_opens_window() {
+        let mut endpoint = mapped(1);
+        endpoint.send_transfer(transfer(), vec![1]).unwrap();
+        endpoint.send_transfer(transfer(), vec![2]).unwrap();
+        endpoint.send_transfer(transfer(), vec![3]).unwrap();
+        assert_eq!(sent_transfers(&mut endpoint), 1);
+        assert!(endpoint.is_blocked());
+
+        // The peer received transfer 0 and allows one more.
+        endpoint
+            .on_frame(remote(Performative::Flow(Flow::new(
+                1.into(),
+                1,
+                100.into(),
+                10,
+            ))))
+            .unwrap();
+        let payloads: Vec<Vec<u8>> = std::iter::from_fn(|| endpoint.poll_transmit())
+            .map(|frame| frame.payload().to_vec())
+            .collect();
+        assert_eq!(payloads, vec![vec![2]]);
+        assert!(endpoint.is_blocked());
+
+        endpoint
+            .on_frame(remote(Performative::Flow(Flow::new(
+                2.into(),
+                10,
+                100.into(),
+                10,
+            ))))
+            .unwrap();
+        assert_eq!(sent_transfers(&mut endpoint), 1);
+        assert!(!endpoint.is_blocked());
+        assert_eq!(endpoint.remote_incoming_window(), 9);
+    }
+
+    #[test]
+    fn test_outgoing_window_holds_back_transfers() {
+        let mut endpoint = mapped(100);
+        endpoint.set_outgoing_window(0).unwrap();
+        endpoint.poll_transmit();
+        endpoint.send_transfer(transfer(), vec![1]).unwrap();
+        assert_eq!(sent_transfers(&mut endpoint), 0);
+        endpoint.set_outgoing_window(1).unwrap();
+        let frames: Vec<AmqpFrame> = std::iter::from_fn(|| endpoint.poll_transmit()).collect();
+        assert!(matches!(frames[0].performative(), Performative::Flow(_)));
+        assert!(matches!(
+            frames[1].performative(),
+            Performative::Transfer(_)
+        ));
+    }
+
+    #[test]
+    fn test_incoming_transfers_consume_window() {
+        let mut endpoint = with_receiver(mapped(10), 100);
+        endpoint.set_incoming_window(4).unwrap();
+        endpoint.poll_transmit();
+        endpoint.on_frame(remote_transfer()).unwrap();
+        assert_eq!(endpoint.next_incoming_id(), 101.into());
+        assert_eq!(endpoint.incoming_window(), 3);
+        assert_eq!(endpoint.remote_outgoing_window(), 9);
+        assert!(matches!(
+            endpoint.poll_event(),
+            Some(SessionEvent::Link(0, LinkEvent::Delivery(..)))
+        ));
+
+        endpoint.set_incoming_window(0).unwrap();
+        assert!(matches!(
+            endpoint.on_frame(remote_transfer()),
+            Err(AppError::Session(SessionError::WindowViolation))
+        ));
+    }
+
+    #[test]
+    fn test_incoming_window_is_restored_at_half() {
+        let mut endpoint = with_receiver(mapped(10), 100);
+        endpoint.set_incoming_window(4).unwrap();
+        endpoint.poll_transmit();
+        endpoint.on_frame(remote_transfer()).unwrap();
+        assert_eq!(endpoint.poll_transmit(), None);
+        endpoint.on_frame(remote_transfer()).unwrap();
+        assert_eq!(endpoint.incoming_window(), 4);
+        assert_eq!(
+            endpoint.poll_transmit().unwrap().performative(),
+            &Performative::Flow(Flow::new(102.into(), 4, 0.into(), 10))
+        );
+    }
+
+    #[test]
+    fn test_more_transfers_than_the_windows_flow_between_sessions() {
+        let mut sender = SessionEndpoint::new(1, Begin::new(0.into(), 8, 8));
+        let mut receiver = SessionEndpoint::new(REMOTE_CHANNEL, Begin::new(100.into(), 8, 8));
+        sender.send_begin().unwrap();
+        receiver.on_frame(sender.poll_transmit().unwrap()).unwrap();
+        receiver.send_begin().unwrap();
+        sender.on_frame(receiver.poll_transmit().unwrap()).unwrap();
+        let handle = sender
+            .attach_link(Attach::new("link".to_string(), 0, Role::Sender))
+            .unwrap();
+        exchange(&mut sender, &mut receiver);
+        let link = receiver.link_mut(0).unwrap();
+        link.send_attach().unwrap();
+        link.grant_credit(100).unwrap();
+        exchange(&mut sender, &mut receiver);
+
+        let link = sender.link_mut(handle).unwrap();
+        for i in 0..100_u8 {
+            link.send_transfer(transfer().with_delivery_tag(tag(i)), vec![i])
+                .unwrap();
+        }
+        exchange(&mut sender, &mut receiver);
+        let deliveries = std::iter::from_fn(|| receiver.poll_event())
+            .filter(|event| matches!(event, SessionEvent::Link(_, LinkEvent::Delivery(..))))
+            .count();
+        assert_eq!(deliveries, 100);
+        assert!(!sender.is_blocked());
+        assert!(sender.outgoing_window() > 0);
+        assert!(receiver.incoming_window() > 0);
+    }
+
+    /// Delivers everything both sessions want to send to the other one until both are quiet.
+    fn exchange(a: &mut SessionEndpoint, b: &mut SessionEndpoint) {
+        fn deliver(from: &mut SessionEndpoint, to: &mut SessionEndpoint) -> bool {
+            let mut delivered = false;
+            while let Some(frame) = from.poll_transmit() {
+                delivered = true;
+                to.on_frame(frame).unwrap();
+            }
+            delivered
+        }
+        while deliver(a, b) | deliver(b, a) {}
+    }
+
+    #[test]
+    fn test_set_incoming_window_sends_flow() {
+        let mut endpoint = mapped(10);
+        endpoint.set_incoming_window(50).unwrap();
+        let frame = endpoint.poll_transmit().unwrap();
+        assert_eq!(
+            frame.performative(),
+            &Performative::Flow(Flow::new(100.into(), 50, 0.into(), 10))
+        );
+    }
+
+    #[test]
+    fn test_echo_is_answered_with_flow() {
+        let mut endpoint = mapped(10);
+        endpoint
+            .on_frame(remote(Performative::Flow(
+                Flow::new(0.into(), 10, 100.into(), 10).with_echo(true),
+            )))
+            .unwrap();
+        assert!(matches!(
+            endpoint.poll_transmit().unwrap().performative(),
+            Performative::Flow(_)
+        ));
+        assert_eq!(endpoint.poll_event(), None);
+    }
+
+    #[test]
+    fn test_remote_attach_creates_link() {
+        let mut endpoint = mapped(10);
+        let attach = Attach::new("link".to_string(), REMOTE_HANDLE, Role::Sender);
+        endpoint
+            .on_frame(remote(Performative::Attach(attach.clone())))
+            .unwrap();
+        assert_eq!(
+            endpoint.poll_event(),
+            Some(SessionEvent::Link(0, LinkEvent::Attached(attach)))
+        );
+        let link = endpoint.link(0).unwrap();
+        assert_eq!(link.role(), Role::Receiver);
+        assert_eq!(link.state(), LinkState::AttachRcvd);
+        assert_eq!(link.remote_handle(), Some(REMOTE_HANDLE));
+    }
+
+    #[test]
+    fn test_link_flow_carries_session_state() {
+        let mut endpoint = with_receiver(mapped(10), 0);
+        endpoint.link_mut(0).unwrap().grant_credit(7).unwrap();
+        let frame = endpoint.poll_transmit().unwrap();
+        let expected = Flow::new(100.into(), 10, 0.into(), 10)
+            .with_handle(0)
+            .with_delivery_count(0.into())
+            .with_link_credit(7);
+        assert_eq!(frame.performative(), &Performative::Flow(expected));
+    }
+
+    #[test]
+    fn test_link_transfers_wait_for_session_window() {
+        let mut endpoint = mapped(1);
+        let handle = endpoint
+            .attach_link(Attach::new("out".to_string(), 9, Role::Sender))
+            .unwrap();
+        assert_eq!(handle, 0);
+        let answer = Attach::new("out".to_string(), REMOTE_HANDLE, Role::Receiver);
+        endpoint
+            .on_frame(remote(Performative::Attach(answer)))
+            .unwrap();
+        let credit = Flow::new(0.into(), 1, 100.into(), 10)
+            .with_handle(REMOTE_HANDLE)
+            .with_delivery_count(0.into())
+            .with_link_credit(5);
+        endpoint
+            .on_frame(remote(Performative::Flow(credit)))
+            .unwrap();
+        assert_eq!(endpoint.link(0).unwrap().link_credit(), 5);
+
+        let link = endpoint.link_mut(0).unwrap();
+        link.send_transfer(Transfer::new(0).with_delivery_tag(tag(1)), vec![1])
+            .unwrap();
+        link.send_transfer(Transfer::new(0).with_delivery_tag(tag(2)), vec![2])
+            .unwrap();
+        assert_eq!(sent_transfers(&mut endpoint), 1);
+        assert!(endpoint.is_blocked());
+    }
+
+    #[test]
+    fn test_handle_errors() {
+        let mut endpoint = with_receiver(mapped(10), 1);
+        let attach = Attach::new("other".to_string(), REMOTE_HANDLE, Role::Sender);
+        assert!(matches!(
+            endpoint.on_frame(remote(Performative::Attach(attach))),
+            Err(AppError::Session(SessionError::HandleInUse))
+        ));
+        assert!(matches!(
+            endpoint.on_frame(remote(Performative::Transfer(Transfer::new(
+                REMOTE_HANDLE + 1
+            )))),
+            Err(AppError::Session(SessionError::UnattachedHandle))
+        ));
+    }
+
+    #[test]
+    fn test_handle_max_limits_links() {
+        let mut endpoint = SessionEndpoint::new(1, Begin::new(0.into(), 10, 10).with_handle_max(1));
+        endpoint.send_begin().unwrap();
+        endpoint
+            .on_frame(remote(Performative::Begin(
+                Begin::new(0.into(), 10, 10).with_handle_max(0),
+            )))
+            .unwrap();
+        assert_eq!(
+            endpoint
+                .attach_link(Attach::new("a".to_string(), 0, Role::Sender))
+                .unwrap(),
+            0
+        );
+        assert!(matches!(
+            endpoint.attach_link(Attach::new("b".to_string(), 0, Role::Sender)),
+            Err(AppError::Amqp(AmqpError::ResourceLimitExceeded))
+        ));
+    }
+
+    #[test]
+    fn test_attach_beyond_handle_max_is_framing_error() {
+        let mut endpoint = SessionEndpoint::new(1, Begin::new(0.into(), 10, 10).with_handle_max(3));
+        endpoint.send_begin().unwrap();
+        endpoint.on_frame(remote_begin(10)).unwrap();
+        let attach = Attach::new("link".to_string(), 4, Role::Sender);
+        assert!(matches!(
+            endpoint.on_frame(remote(Performative::Attach(attach))),
+            Err(AppError::Connection(ConnectionError::FramingError))
+        ));
+    }
+
+    #[test]
+    fn test_detached_link_is_released() {
+        let mut endpoint = with_receiver(mapped(10), 1);
+        endpoint
+            .on_frame(remote(Performative::Detach(
+                Detach::new(REMOTE_HANDLE).with_closed(true),
+            )))
+            .unwrap();
+        endpoint
+            .link_mut(0)
+            .unwrap()
+            .send_detach(true, None)
+            .unwrap();
+        assert!(matches!(
+            endpoint.poll_transmit().unwrap().performative(),
+            Performative::Detach(_)
+        ));
+        assert!(endpoint.link(0).is_none());
+        assert!(matches!(
+            endpoint.on_frame(remote_transfer()),
+            Err(AppError::Session(SessionError::UnattachedHandle))
+        ));
+        assert_eq!(
+            endpoint
+                .attach_link(Attach::new("next".to_string(), 0, Role::Sender))
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_handle_not_reused_while_detach_pending() {
+        let mut endpoint = with_receiver(mapped(10), 1);
+        endpoint
+            .link_mut(0)
+            .unwrap()
+            .send_detach(true, None)
+            .unwrap();
+        endpoint.poll_transmit();
+        assert_eq!(
+            endpoint
+                .attach_link(Attach::new("next".to_string(), 0, Role::Sender))
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_window_arithmetic_wraps_around() {
+        let mut endpoint = SessionEndpoint::new(1, Begin::new(u32::MAX.into(), 10, 10));
+        endpoint.send_begin().unwrap();
+        endpoint.on_frame(remote_begin(1)).unwrap();
+        endpoint.send_transfer(transfer(), vec![]).unwrap();
+        assert_eq!(endpoint.next_outgoing_id(), 0.into());
+        endpoint
+            .on_frame(remote(Performative::Flow(Flow::new(
+                u32::MAX.into(),
+                3,
+                100.into(),
+                10,
+            ))))
+            .unwrap();
+        assert_eq!(endpoint.remote_incoming_window(), 2);
+    }
+
+    #[test]
+    fn test_discarding_ignores_frames_until_end() {
+        let mut endpoint = run(&[SendBegin, ReceiveBegin, SendEndWithError]);
+        endpoint.poll_event();
+        endpoint.on_frame(remote_transfer()).unwrap();
+        assert_eq!(endpoint.poll_event(), None);
+        endpoint
+            .on_frame(remote(Performative::End(End::default())))
+            .unwrap();
+        assert_eq!(endpoint.state(), Unmapped);
+        assert_eq!(endpoint.poll_event(), Some(SessionEvent::Ended(None)));
+    }
+
+    #[test]
+    fn test_transfers_require_mapped_session() {
+        let mut endpoint = run(&[SendBegin]);
+        assert!(endpoint.send_transfer(transfer(), vec![]).is_err());
+        assert!(endpoint
+            .on_frame(remote(Performative::Transfer(transfer())))
+            .is_err());
+    }
+
+    /// A mapped session on `clock` whose peer attached a sending link and sent `count`
+    /// unsettled, batchable deliveries, tagged with their delivery-id.
+    fn with_batchable_deliveries(clock: &ManualClock, count: u8) -> SessionEndpoint {
+        let mut endpoint =
+            SessionEndpoint::new(1, Begin::new(0.into(), 10, 10)).with_clock(clock.clone());
+        endpoint.send_begin().unwrap();
+        endpoint.on_frame(remote_begin(10)).unwrap();
+        let mut endpoint = with_receiver(endpoint, 100);
+        endpoint.set_disposition_batching(100, Duration::from_millis(10));
+        for delivery_id in 0..count {
+            let transfer = Transfer::new(REMOTE_HANDLE)
+                .with_delivery_id((delivery_id as u32).into())
+                .with_delivery_tag(tag(delivery_id))
+                .with_batchable(true);
+            endpoint
+                .on_frame(remote(Performative::Transfer(transfer)))
+                .unwrap();
+        }
+        endpoint
+    }
+
+    fn accepted() -> DeliveryState {
+        DeliveryState::Accepted(Accepted {})
+    }
+
+    #[test]
+    fn test_batchable_dispositions_are_sent_as_one_range() {
+        let clock = ManualClock::new();
+        let mut endpoint = with_batchable_deliveries(&clock, 3);
+        let link = endpoint.link_mut(0).unwrap();
+        for delivery_id in [1, 0, 2] {
+            link.dispose(&tag(delivery_id), accepted()).unwrap();
+        }
+        assert!(endpoint.poll_transmit().is_none());
+        assert_eq!(
+            endpoint.poll_timeout(),
+            Some(clock.now() + Duration::from_millis(10))
+        );
+
+        clock.advance(9);
+        endpoint.handle_timeout();
+        assert!(endpoint.poll_transmit().is_none());
+        clock.advance(1);
+        endpoint.handle_timeout();
+        let frames: Vec<AmqpFrame> = std::iter::from_fn(|| endpoint.poll_transmit()).collect();
+        assert_eq!(frames.len(), 1);
+        let Performative::Disposition(disposition) = frames[0].performative() else {
+            panic!("expected a disposition");
+        };
+        assert_eq!(disposition.role(), Role::Receiver);
+        assert_eq!(disposition.first(), 0.into());
+        assert_eq!(disposition.last(), 2.into());
+        assert!(disposition.settled());
+        assert_eq!(disposition.state(), Some(&accepted()));
+        assert_eq!(endpoint.poll_timeout(), None);
+    }
+
+    #[test]
+    fn test_batch_is_sent_before_end() {
+        let clock = ManualClock::new();
+        let mut endpoint = with_batchable_deliveries(&clock, 1);
+        endpoint
+            .link_mut(0)
+            .unwrap()
+            .dispose(&tag(0), accepted())
+            .unwrap();
+        assert!(endpoint.poll_transmit().is_none());
+        endpoint.send_end(None).unwrap();
+        let frames: Vec<AmqpFrame> = std::iter::from_fn(|| endpoint.poll_transmit()).collect();
+        assert!(matches!(
+            frames[0].performative(),
+            Performative::Disposition(_)
+        ));
+        assert!(matches!(frames[1].performative(), Performative::End(_)));
+    }
+
+    #[test]
+    fn test_dispositions_without_batchable_are_sent_at_once() {
+        let mut endpoint = with_receiver(mapped(10), 100);
+        let transfer = Transfer::new(REMOTE_HANDLE)
+            .with_delivery_id(0.into())
+            .with_delivery_tag(tag(0));
+        endpoint
+            .on_frame(remote(Performative::Transfer(transfer)))
+            .unwrap();
+        endpoint
+            .link_mut(0)
+            .unwrap()
+            .dispose(&tag(0), accepted())
+            .unwrap();
+        assert!(matches!(
+            endpoint
+                .poll_transmit()
+                .map(|frame| frame.performative().clone()),
+            Some(Performative::Disposition(_))
+        ));
+    }
+
+    #[test]
+    fn test_detached_link_with_unsettled_deliveries_is_resumed_by_peer() {
+        let mut endpoint = with_receiver(mapped(10), 100);
+        let transfer = Transfer::new(REMOTE_HANDLE)
+            .with_delivery_id(0.into())
+            .with_delivery_tag(tag(0));
+        endpoint
+            .on_frame(remote(Performative::Transfer(transfer)))
+            .unwrap();
+        endpoint
+            .on_frame(remote(Performative::Detach(Detach::new(REMOTE_HANDLE))))
+            .unwrap();
+        endpoint
+            .link_mut(0)
+            .unwrap()
+            .send_detach(false, None)
+            .unwrap();
+        while endpoint.poll_transmit().is_some() {}
+        while endpoint.poll_event().is_some() {}
+        assert!(endpoint.link(0).is_none());
+        assert!(endpoint.suspended_link("link").is_some());
+
+        let attach = Attach::new("link".to_string(), 4, Role::Sender);
+        endpoint
+            .on_frame(remote(Performative::Attach(attach)))
+            .unwrap();
+        assert!(endpoint.suspended_link("link").is_none());
+        assert!(matches!(
+            endpoint.poll_event(),
+            Some(SessionEvent::Link(0, LinkEvent::Attached(_)))
+        ));
+        let link = endpoint.link_mut(0).unwrap();
+        link.send_attach().unwrap();
+        // The peer's attach had no unsettled map, so it settled the delivery.
+        assert!(link.unsettled().is_empty());
+        assert_eq!(
+            link.poll_event(),
+            Some(LinkEvent::Settled {
+                tag: tag(0),
+                state: None
+            })
+        );
+        let Some(Performative::Attach(answer)) = endpoint
+            .poll_transmit()
+            .map(|frame| frame.performative().clone())
+        else {
+            panic!("expected the answering attach");
+        };
+        assert_eq!(answer.unsettled().map(|unsettled| unsettled.len()), Some(1));
+    }
+}
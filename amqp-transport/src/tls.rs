@@ -0,0 +1,383 @@
+use crate::error::TransportError;
+use crate::protocol_header::{ProtocolId, ProtocolNegotiation};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::rustls::crypto::{ring, CryptoProvider};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerConfig};
+use tokio_rustls::{client, server, TlsAcceptor, TlsConnector};
+
+pub use tokio_rustls::rustls;
+
+/// # TLS Mode
+/// How the peers start TLS on a connection (spec section 5.2).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsMode {
+    /// The TLS handshake starts with the first byte on the socket, as on the
+    /// [`SECURE_PORT`](crate::constants::SECURE_PORT) (amqps).
+    Direct,
+    /// The peers first exchange the protocol header of the TLS layer, then the TLS handshake
+    /// follows. The AMQP protocol header is sent over the established TLS stream.
+    /// ```text
+    /// TCP Client                 TCP Server
+    /// =========================================
+    /// AMQP%d2.1.0.0 --------->
+    ///               <--------- AMQP%d2.1.0.0
+    ///     <TLS handshake>
+    /// AMQP%d0.1.0.0 --------->                  (over TLS secured connection)
+    /// ```
+    Negotiated,
+}
+
+/// Negotiates the TLS layer only; the AMQP layer is negotiated by the connection itself.
+fn negotiation() -> ProtocolNegotiation {
+    ProtocolNegotiation::new(vec![ProtocolId::Tls, ProtocolId::Amqp]).expect("layers are ordered")
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+/// # Client TLS
+/// Secures client connections. The server's certificate must be issued by one of the
+/// configured roots.
+///
+/// ```no_run
+///# use amqp_transport::client::{Connection, ConnectionOptions};
+///# use amqp_transport::tls::{ClientTls, TlsMode};
+///# use amqp_transport::tls::rustls::RootCertStore;
+///# async fn example(roots: RootCertStore) -> Result<(), amqp_transport::error::TransportError> {
+/// let tls = ClientTls::new(roots)?.with_mode(TlsMode::Direct);
+/// let connection = Connection::open_tls("broker:5671", "broker", &tls, ConnectionOptions::default()).await?;
+///# Ok(())
+///# }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClientTls {
+    config: Arc<ClientConfig>,
+    mode: TlsMode,
+}
+
+impl ClientTls {
+    /// Trusts servers with a certificate issued by one of `roots`, and presents no certificate
+    /// of its own.
+    pub fn new(roots: RootCertStore) -> Result<Self, TransportError> {
+        let config = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Ok(ClientTls::from_config(Arc::new(config)))
+    }
+
+    /// Trusts servers with a certificate issued by one of `roots`, and presents `chain` to
+    /// servers asking for a client certificate.
+    pub fn with_client_auth(
+        roots: RootCertStore,
+        chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+    ) -> Result<Self, TransportError> {
+        let config = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_client_auth_cert(chain, key)?;
+        Ok(ClientTls::from_config(Arc::new(config)))
+    }
+
+    pub fn from_config(config: Arc<ClientConfig>) -> Self {
+        ClientTls {
+            config,
+            mode: TlsMode::Direct,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: TlsMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn mode(&self) -> TlsMode {
+        self.mode
+    }
+
+    pub fn config(&self) -> &Arc<ClientConfig> {
+        &self.config
+    }
+
+    /// Starts TLS on a stream connected to the server, which must present a certificate
+    /// for `server_name`.
+    pub async fn connect<S>(
+        &self,
+        server_name: &str,
+        mut stream: S,
+    ) -> Result<client::TlsStream<S>, TransportError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let server_name = ServerName::try_from(server_name.to_string())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        if self.mode == TlsMode::Negotiated {
+            negotiation().client(&mut stream).await?;
+        }
+        let connector = TlsConnector::from(self.config.clone());
+        Ok(connector.connect(server_name, stream).await?)
+    }
+}
+
+/// # Server TLS
+/// Secures served connections with a certificate chain and, optionally, requires clients to
+/// present a certificate of their own.
+#[derive(Debug, Clone)]
+pub struct ServerTls {
+    config: Arc<ServerConfig>,
+    mode: TlsMode,
+}
+
+impl ServerTls {
+    /// Presents `chain` to clients without asking for their certificates.
+    pub fn new(
+        chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+    ) -> Result<Self, TransportError> {
+        let config = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(chain, key)?;
+        Ok(ServerTls::from_config(Arc::new(config)))
+    }
+
+    /// Presents `chain` to clients and refuses clients without a certificate issued by one
+    /// of `client_roots`.
+    pub fn with_client_auth(
+        chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+        client_roots: RootCertStore,
+    ) -> Result<Self, TransportError> {
+        let verifier =
+            WebPkiClientVerifier::builder_with_provider(Arc::new(client_roots), provider())
+                .build()
+                .map_err(|e| rustls::Error::General(e.to_string()))?;
+        let config = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()?
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(chain, key)?;
+        Ok(ServerTls::from_config(Arc::new(config)))
+    }
+
+    pub fn from_config(config: Arc<ServerConfig>) -> Self {
+        ServerTls {
+            config,
+            mode: TlsMode::Direct,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: TlsMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn mode(&self) -> TlsMode {
+        self.mode
+    }
+
+    pub fn config(&self) -> &Arc<ServerConfig> {
+        &self.config
+    }
+
+    /// Starts TLS on a stream accepted from a client. In negotiated mode a client that does
+    /// not ask for the TLS layer is answered with the TLS protocol header and refused.
+    pub async fn accept<S>(&self, mut stream: S) -> Result<server::TlsStream<S>, TransportError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        if self.mode == TlsMode::Negotiated {
+            negotiation().server(&mut stream).await?;
+        }
+        let acceptor = TlsAcceptor::from(self.config.clone());
+        Ok(acceptor.accept(stream).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Connection, ConnectionOptions};
+    use crate::memory::MemoryTransport;
+    use crate::protocol_header::NegotiationError;
+    use crate::server::{serve_tls, Handler, Listener};
+    use amqp_messaging::message::Message;
+    use amqp_type::composite::messaging::delivery_state::accepted::Accepted;
+    use amqp_type::composite::messaging::delivery_state::DeliveryState;
+    use rcgen::{BasicConstraints, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+    use tokio_rustls::rustls::pki_types::PrivatePkcs8KeyDer;
+
+    struct Accepting;
+
+    impl Handler for Accepting {}
+
+    /// A self-signed certificate authority issuing certificates for the tests.
+    struct Authority {
+        certificate: rcgen::Certificate,
+        key: KeyPair,
+    }
+
+    impl Authority {
+        fn new() -> Self {
+            let mut params = CertificateParams::new(Vec::new()).unwrap();
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            let key = KeyPair::generate().unwrap();
+            let certificate = params.self_signed(&key).unwrap();
+            Authority { certificate, key }
+        }
+
+        fn roots(&self) -> RootCertStore {
+            let mut roots = RootCertStore::empty();
+            roots.add(self.certificate.der().clone()).unwrap();
+            roots
+        }
+
+        fn issue(
+            &self,
+            name: &str,
+            usage: ExtendedKeyUsagePurpose,
+        ) -> (Vec<CertificateDer<'static>>, PrivateKeyDer<'static>) {
+            let mut params = CertificateParams::new(vec![name.to_string()]).unwrap();
+            params.extended_key_usages = vec![usage];
+            let key = KeyPair::generate().unwrap();
+            let certificate = params
+                .signed_by(&key, &self.certificate, &self.key)
+                .unwrap();
+            let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der()));
+            (vec![certificate.der().clone()], key)
+        }
+
+        fn server(&self) -> ServerTls {
+            let (chain, key) = self.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+            ServerTls::new(chain, key).unwrap()
+        }
+    }
+
+    async fn send_one(connection: Connection) {
+        let session = connection.begin_session().await.unwrap();
+        let sender = session.attach_sender("queue").await.unwrap();
+        let outcome = sender.send(Message::from_value("secret")).await.unwrap();
+        assert_eq!(outcome, Some(DeliveryState::Accepted(Accepted {})));
+        connection.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_amqps_over_tcp() {
+        let authority = Authority::new();
+        let listener = Listener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .with_tls(authority.server());
+        let addr = listener.local_addr().unwrap();
+        let server =
+            tokio::spawn(async move { listener.accept(Accepting).await.unwrap().closed().await });
+
+        let tls = ClientTls::new(authority.roots()).unwrap();
+        let connection =
+            Connection::open_tls(addr, "localhost", &tls, ConnectionOptions::default())
+                .await
+                .unwrap();
+        send_one(connection).await;
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_negotiated_tls_with_client_certificate() {
+        let authority = Authority::new();
+        let (chain, key) = authority.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+        let server_tls = ServerTls::with_client_auth(chain, key, authority.roots())
+            .unwrap()
+            .with_mode(TlsMode::Negotiated);
+        let (chain, key) = authority.issue("client", ExtendedKeyUsagePurpose::ClientAuth);
+        let client_tls = ClientTls::with_client_auth(authority.roots(), chain, key)
+            .unwrap()
+            .with_mode(TlsMode::Negotiated);
+
+        let (client, server) = MemoryTransport::new().with_chunk_size(100).pair();
+        let served = serve_tls(server, server_tls, Accepting, ConnectionOptions::default());
+        let stream = client_tls.connect("localhost", client).await.unwrap();
+        send_one(
+            Connection::open_stream(stream, ConnectionOptions::default())
+                .await
+                .unwrap(),
+        )
+        .await;
+        served.closed().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_server_refuses_client_without_certificate() {
+        let authority = Authority::new();
+        let (chain, key) = authority.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+        let server_tls = ServerTls::with_client_auth(chain, key, authority.roots()).unwrap();
+        let client_tls = ClientTls::new(authority.roots()).unwrap();
+
+        let (client, server) = MemoryTransport::new().pair();
+        let served = serve_tls(server, server_tls, Accepting, ConnectionOptions::default());
+        // TLS 1.3 clients finish their handshake before the server checks the certificate.
+        let result = match client_tls.connect("localhost", client).await {
+            Ok(stream) => Connection::open_stream(stream, ConnectionOptions::default())
+                .await
+                .map(drop),
+            Err(e) => Err(e),
+        };
+        assert!(result.is_err());
+        assert!(matches!(served.closed().await, Err(TransportError::Io(_))));
+    }
+
+    #[tokio::test]
+    async fn test_client_refuses_untrusted_server() {
+        let (client, server) = MemoryTransport::new().pair();
+        let served = serve_tls(
+            server,
+            Authority::new().server(),
+            Accepting,
+            ConnectionOptions::default(),
+        );
+        let client_tls = ClientTls::new(Authority::new().roots()).unwrap();
+        assert!(matches!(
+            client_tls.connect("localhost", client).await,
+            Err(TransportError::Io(_))
+        ));
+        assert!(served.closed().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_checks_server_name() {
+        let authority = Authority::new();
+        let (client, server) = MemoryTransport::new().pair();
+        let _served = serve_tls(
+            server,
+            authority.server(),
+            Accepting,
+            ConnectionOptions::default(),
+        );
+        let client_tls = ClientTls::new(authority.roots()).unwrap();
+        assert!(client_tls.connect("elsewhere", client).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_negotiated_server_refuses_plain_client() {
+        let authority = Authority::new();
+        let server_tls = authority.server().with_mode(TlsMode::Negotiated);
+        let (client, server) = MemoryTransport::new().pair();
+        let served = serve_tls(server, server_tls, Accepting, ConnectionOptions::default());
+        // The client sees the TLS header where it expects the AMQP header.
+        assert!(matches!(
+            Connection::open_stream(client, ConnectionOptions::default()).await,
+            Err(TransportError::Negotiation(
+                NegotiationError::Rejected { .. }
+            ))
+        ));
+        assert!(matches!(
+            served.closed().await,
+            Err(TransportError::Negotiation(
+                NegotiationError::Rejected { .. }
+            ))
+        ));
+    }
+}
@@ -0,0 +1,337 @@
+use crate::error::TransportError;
+use bytes::{Buf, Bytes};
+use futures_util::{Sink, Stream};
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::header::SEC_WEBSOCKET_PROTOCOL;
+use tokio_tungstenite::tungstenite::http::{HeaderMap, HeaderValue, StatusCode};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+pub use tokio_tungstenite::tungstenite;
+
+/// The WebSocket subprotocol both peers must agree on.
+/// ```
+///# use amqp_transport::websocket::SUBPROTOCOL;
+/// assert_eq!(SUBPROTOCOL, "amqp");
+/// ```
+pub const SUBPROTOCOL: &str = "amqp";
+
+/// # WebSocket
+/// Carries an AMQP connection over a WebSocket, as defined by the OASIS AMQP WebSocket Binding.
+///
+/// The bytes of the connection, from the protocol header on, travel in binary messages. A
+/// frame may span several messages and a message may hold several frames, so the messages are
+/// read and written as a byte stream. Text messages are a protocol violation; ping and pong
+/// messages are answered by the WebSocket layer and skipped.
+///
+/// ```no_run
+///# use amqp_transport::client::{Connection, ConnectionOptions};
+///# use amqp_transport::tls::ClientTls;
+///# use amqp_transport::websocket;
+///# use tokio::net::TcpStream;
+///# async fn example(tls: ClientTls) -> Result<(), amqp_transport::error::TransportError> {
+/// // AMQP over a secure WebSocket (wss).
+/// let stream = TcpStream::connect("gateway:443").await?;
+/// let stream = tls.connect("gateway", stream).await?;
+/// let stream = websocket::connect("wss://gateway/amqp", stream).await?;
+/// let connection = Connection::open_stream(stream, ConnectionOptions::default()).await?;
+///# Ok(())
+///# }
+/// ```
+#[derive(Debug)]
+pub struct WebSocket<S> {
+    inner: WebSocketStream<S>,
+    /// What is left of the last binary message read.
+    read: Bytes,
+}
+
+impl<S> WebSocket<S> {
+    pub fn new(inner: WebSocketStream<S>) -> Self {
+        WebSocket {
+            inner,
+            read: Bytes::new(),
+        }
+    }
+
+    pub fn get_ref(&self) -> &WebSocketStream<S> {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> WebSocketStream<S> {
+        self.inner
+    }
+}
+
+/// Performs the client side of the WebSocket handshake for `url` on a stream connected to the
+/// server, asking for the [`SUBPROTOCOL`]. Fails if the server does not agree to it.
+pub async fn connect<S>(url: &str, stream: S) -> Result<WebSocket<S>, TransportError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut request = url.into_client_request()?;
+    request.headers_mut().insert(
+        SEC_WEBSOCKET_PROTOCOL,
+        HeaderValue::from_static(SUBPROTOCOL),
+    );
+    let (inner, _) = tokio_tungstenite::client_async(request, stream).await?;
+    Ok(WebSocket::new(inner))
+}
+
+/// Performs the server side of the WebSocket handshake on a stream accepted from a client.
+/// Clients that do not offer the [`SUBPROTOCOL`] are refused with `400 Bad Request`.
+pub async fn accept<S>(stream: S) -> Result<WebSocket<S>, TransportError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let inner = tokio_tungstenite::accept_hdr_async(stream, agree_on_amqp).await?;
+    Ok(WebSocket::new(inner))
+}
+
+/// Answers the handshake request of a client with the [`SUBPROTOCOL`], or refuses it.
+// The signature is the one tungstenite expects of a handshake callback.
+#[allow(clippy::result_large_err)]
+fn agree_on_amqp(request: &Request, mut response: Response) -> Result<Response, ErrorResponse> {
+    if !offers_amqp(request.headers()) {
+        let mut refusal =
+            ErrorResponse::new(Some(format!("the {} subprotocol is required", SUBPROTOCOL)));
+        *refusal.status_mut() = StatusCode::BAD_REQUEST;
+        return Err(refusal);
+    }
+    response.headers_mut().insert(
+        SEC_WEBSOCKET_PROTOCOL,
+        HeaderValue::from_static(SUBPROTOCOL),
+    );
+    Ok(response)
+}
+
+/// The client may offer several subprotocols, in one header or several.
+fn offers_amqp(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|protocol| protocol.trim() == SUBPROTOCOL)
+}
+
+fn io_error(error: tungstenite::Error) -> io::Error {
+    match error {
+        tungstenite::Error::Io(e) => e,
+        tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => {
+            io::ErrorKind::BrokenPipe.into()
+        }
+        e => io::Error::other(e),
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for WebSocket<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        while this.read.is_empty() {
+            match ready!(Pin::new(&mut this.inner).poll_next(cx)) {
+                Some(Ok(Message::Binary(data))) => this.read = data,
+                Some(Ok(Message::Text(_))) => {
+                    let error = io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "AMQP frames must be sent in binary messages",
+                    );
+                    return Poll::Ready(Err(error));
+                }
+                Some(Ok(Message::Ping(_) | Message::Pong(_) | Message::Frame(_))) => {}
+                // After a close message the stream ends.
+                Some(Ok(Message::Close(_))) | None => return Poll::Ready(Ok(())),
+                Some(Err(e)) => return Poll::Ready(Err(io_error(e))),
+            }
+        }
+        let len = this.read.len().min(buf.remaining());
+        buf.put_slice(&this.read[..len]);
+        this.read.advance(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for WebSocket<S> {
+    /// Sends `buf` as one binary message.
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut inner = Pin::new(&mut self.get_mut().inner);
+        ready!(inner.as_mut().poll_ready(cx)).map_err(io_error)?;
+        inner
+            .start_send(Message::Binary(Bytes::copy_from_slice(buf)))
+            .map_err(io_error)?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner)
+            .poll_flush(cx)
+            .map_err(io_error)
+    }
+
+    /// Sends a close message.
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner)
+            .poll_close(cx)
+            .map_err(io_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{Connection, ConnectionOptions};
+    use crate::memory::MemoryTransport;
+    use crate::server::{Handler, Listener};
+    use amqp_messaging::message::Message as AmqpMessage;
+    use amqp_type::composite::messaging::delivery_state::accepted::Accepted;
+    use amqp_type::composite::messaging::delivery_state::DeliveryState;
+    use futures_util::SinkExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_stream::StreamExt;
+
+    struct Accepting;
+
+    impl Handler for Accepting {}
+
+    /// A tokio-tungstenite server that agrees to the subprotocol and then hands over the raw
+    /// WebSocket messages.
+    async fn raw_server(listener: TcpListener) -> WebSocketStream<TcpStream> {
+        let (stream, _) = listener.accept().await.unwrap();
+        tokio_tungstenite::accept_hdr_async(stream, agree_on_amqp)
+            .await
+            .unwrap()
+    }
+
+    /// A listener on a local port, the URL to reach it and a client connected to it.
+    async fn local() -> (TcpListener, String, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stream = TcpStream::connect(addr).await.unwrap();
+        (listener, format!("ws://{}/", addr), stream)
+    }
+
+    #[test]
+    fn test_subprotocol_may_be_one_of_several() {
+        let mut headers = HeaderMap::new();
+        assert!(!offers_amqp(&headers));
+        headers.append(
+            SEC_WEBSOCKET_PROTOCOL,
+            HeaderValue::from_static("mqtt, amqp"),
+        );
+        assert!(offers_amqp(&headers));
+        let mut headers = HeaderMap::new();
+        headers.append(SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static("mqtt"));
+        headers.append(SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static("amqp"));
+        assert!(offers_amqp(&headers));
+    }
+
+    #[tokio::test]
+    async fn test_bytes_travel_in_binary_messages() {
+        let (listener, url, stream) = local().await;
+        let server = tokio::spawn(raw_server(listener));
+        let mut client = connect(&url, stream).await.unwrap();
+        let mut server = server.await.unwrap();
+
+        client.write_all(b"AMQP\x00\x01\x00\x00").await.unwrap();
+        client.flush().await.unwrap();
+        let message = server.next().await.unwrap().unwrap();
+        assert_eq!(
+            message,
+            Message::Binary(Bytes::from_static(b"AMQP\x00\x01\x00\x00"))
+        );
+
+        // A message is read across as many reads as it takes, and messages run together.
+        server
+            .send(Message::Binary(Bytes::from_static(b"abcdef")))
+            .await
+            .unwrap();
+        server.send(Message::Ping(Bytes::new())).await.unwrap();
+        server
+            .send(Message::Binary(Bytes::from_static(b"gh")))
+            .await
+            .unwrap();
+        let mut buffer = [0; 4];
+        client.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"abcd");
+        client.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"efgh");
+
+        server.close(None).await.unwrap();
+        assert_eq!(client.read(&mut buffer).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_text_message_is_refused() {
+        let (listener, url, stream) = local().await;
+        let server = tokio::spawn(async move {
+            let mut server = raw_server(listener).await;
+            server.send(Message::text("AMQP")).await.unwrap();
+            server
+        });
+        let mut client = connect(&url, stream).await.unwrap();
+        let _server = server.await.unwrap();
+        let error = client.read(&mut [0; 8]).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn test_client_refuses_server_without_subprotocol() {
+        let (listener, url, stream) = local().await;
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let _ = tokio_tungstenite::accept_async(stream).await;
+        });
+        assert!(matches!(
+            connect(&url, stream).await,
+            Err(TransportError::WebSocket(tungstenite::Error::Protocol(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_server_refuses_client_without_subprotocol() {
+        let (client, server) = MemoryTransport::new().pair();
+        let served = tokio::spawn(accept(server));
+        let result = tokio_tungstenite::client_async("ws://localhost/", client).await;
+        assert!(
+            matches!(result, Err(tungstenite::Error::Http(response)) if response.status() == StatusCode::BAD_REQUEST)
+        );
+        assert!(served.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_connection_over_websocket() {
+        let listener = Listener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .with_websocket();
+        let url = format!("ws://{}/amqp", listener.local_addr().unwrap());
+        let server =
+            tokio::spawn(async move { listener.accept(Accepting).await.unwrap().closed().await });
+
+        let connection = Connection::open_websocket(&url, ConnectionOptions::default())
+            .await
+            .unwrap();
+        let session = connection.begin_session().await.unwrap();
+        let sender = session.attach_sender("queue").await.unwrap();
+        let outcome = sender
+            .send(AmqpMessage::from_data(vec![1; 5000]))
+            .await
+            .unwrap();
+        assert_eq!(outcome, Some(DeliveryState::Accepted(Accepted {})));
+        connection.close().await.unwrap();
+        server.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,124 @@
+use crate::error::amqp_error::AmqpError;
+use crate::error::AppError;
+use crate::primitive::compound::map::Map;
+use crate::primitive::variable_width::symbol::Symbol;
+use crate::primitive::Primitive;
+
+/// # Annotations
+/// A mapping from annotation keys to values.
+/// ##### AMQP Spec
+/// ```xml
+/// <type name="annotations" class="restricted" source="map"/>
+/// ```
+/// The annotations type is a map where the keys are restricted to be of type symbol or of type ulong.
+/// All ulong keys, and all symbolic keys except those beginning with "x-" are reserved. Keys
+/// beginning with "x-opt-" MUST be ignored if not understood.
+///
+/// Annotations back the delivery-annotations, message-annotations and footer sections.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Annotations(Map);
+
+impl Annotations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Primitive> {
+        self.0.get(Symbol::with_ascii(key))
+    }
+
+    pub fn insert<V: Into<Primitive>>(&mut self, key: Symbol, value: V) -> Option<Primitive> {
+        self.0.insert(key, value)
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Primitive> {
+        self.0.remove(Symbol::with_ascii(key))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn inner(&self) -> &Map {
+        &self.0
+    }
+
+    fn verify_keys(map: &Map) -> Result<(), AppError> {
+        // annotation keys may only be symbols or ulongs, everything else is not compliant
+        match map
+            .inner()
+            .keys()
+            .all(|k| matches!(k, Primitive::Symbol(_) | Primitive::Ulong(_)))
+        {
+            true => Ok(()),
+            false => Err(AmqpError::DecodeError)?,
+        }
+    }
+}
+
+impl From<Annotations> for Primitive {
+    fn from(value: Annotations) -> Self {
+        Primitive::Map(value.0)
+    }
+}
+
+impl TryFrom<Primitive> for Annotations {
+    type Error = AppError;
+
+    fn try_from(value: Primitive) -> Result<Self, Self::Error> {
+        let map: Map = value.try_into()?;
+        Self::verify_keys(&map)?;
+        Ok(Annotations(map))
+    }
+}
+
+impl TryFrom<Primitive> for Option<Annotations> {
+    type Error = AppError;
+
+    fn try_from(value: Primitive) -> Result<Self, Self::Error> {
+        match value {
+            Primitive::Null => Ok(None),
+            x => Ok(Some(Annotations::try_from(x)?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_and_get_annotation() {
+        let mut annotations = Annotations::new();
+        annotations.insert(Symbol::with_ascii("x-opt-partition"), 3);
+        assert_eq!(annotations.get("x-opt-partition"), Some(&Primitive::Int(3)));
+        assert_eq!(annotations.len(), 1);
+    }
+
+    #[test]
+    fn test_remove_annotation() {
+        let mut annotations = Annotations::new();
+        annotations.insert(Symbol::with_ascii("x-opt-partition"), 3);
+        assert_eq!(
+            annotations.remove("x-opt-partition"),
+            Some(Primitive::Int(3))
+        );
+        assert!(annotations.is_empty());
+    }
+
+    #[test]
+    fn test_annotations_reject_string_keys() {
+        let map = Map::from(vec![(
+            Primitive::String("key".to_string()),
+            Primitive::Int(1),
+        )]);
+        assert!(matches!(
+            Annotations::try_from(Primitive::Map(map)),
+            Err(AppError::Amqp(AmqpError::DecodeError))
+        ));
+    }
+}
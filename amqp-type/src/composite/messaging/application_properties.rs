@@ -0,0 +1,124 @@
+use crate::error::amqp_error::AmqpError;
+use crate::error::AppError;
+use crate::primitive::compound::map::Map;
+use crate::primitive::Primitive;
+
+/// # Application Properties
+///
+/// ##### AMQP Specification
+/// ```xml
+/// <type name="application-properties" class="restricted" source="map" provides="section">
+///     <descriptor name="amqp:application-properties:map" code="0x00000000:0x00000074"/>
+/// </type>
+/// ```
+/// The application-properties section is a part of the bare message used for structured application data.
+/// Intermediaries can use the data within this structure for the purposes of filtering or routing.
+/// The keys of this map are restricted to be of type string and the values are restricted to be of
+/// simple types only, that is, excluding map, list, and array types.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ApplicationProperties(Map);
+
+impl ApplicationProperties {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Primitive> {
+        self.0.get(key)
+    }
+
+    pub fn insert<V: Into<Primitive>>(
+        &mut self,
+        key: &str,
+        value: V,
+    ) -> Result<Option<Primitive>, AppError> {
+        let value = value.into();
+        Self::verify_simple_value(&value)?;
+        Ok(self.0.insert(key, value))
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Primitive> {
+        self.0.remove(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn inner(&self) -> &Map {
+        &self.0
+    }
+
+    fn verify_simple_value(value: &Primitive) -> Result<(), AppError> {
+        match value {
+            Primitive::List(_)
+            | Primitive::Map(_)
+            | Primitive::Array(_)
+            | Primitive::Composite(_) => Err(AmqpError::InvalidField)?,
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<ApplicationProperties> for Primitive {
+    fn from(value: ApplicationProperties) -> Self {
+        Primitive::Map(value.0)
+    }
+}
+
+impl TryFrom<Primitive> for ApplicationProperties {
+    type Error = AppError;
+
+    fn try_from(value: Primitive) -> Result<Self, Self::Error> {
+        let map: Map = value.try_into()?;
+        for (key, value) in map.inner() {
+            if !matches!(key, Primitive::String(_)) {
+                Err(AmqpError::DecodeError)?
+            }
+            Self::verify_simple_value(value)?;
+        }
+        Ok(ApplicationProperties(map))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitive::compound::list::List;
+
+    #[test]
+    fn test_insert_and_get_property() {
+        let mut properties = ApplicationProperties::new();
+        properties.insert("tenant", "acme").unwrap();
+        assert_eq!(
+            properties.get("tenant"),
+            Some(&Primitive::String("acme".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_insert_rejects_compound_values() {
+        let mut properties = ApplicationProperties::new();
+        assert!(matches!(
+            properties.insert("list", List::from(vec![1, 2])),
+            Err(AppError::Amqp(AmqpError::InvalidField))
+        ));
+        assert!(properties.is_empty());
+    }
+
+    #[test]
+    fn test_try_from_rejects_symbol_keys() {
+        let map = Map::from(vec![(
+            Primitive::Symbol(crate::primitive::variable_width::symbol::Symbol::with_ascii("key")),
+            Primitive::Int(1),
+        )]);
+        assert!(matches!(
+            ApplicationProperties::try_from(Primitive::Map(map)),
+            Err(AppError::Amqp(AmqpError::DecodeError))
+        ));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::restricted::duration::Milliseconds;
+use amqp_derive::AmqpComposite;
+
+/// # Header
+/// Transport headers for a message.
+/// ##### AMQP Specification
+/// ```xml
+/// <type name="header" class="composite" source="list" provides="section">
+///     <descriptor name="amqp:header:list" code="0x00000000:0x00000070"/>
+///     <field name="durable" type="boolean" default="false"/>
+///     <field name="priority" type="ubyte" default="4"/>
+///     <field name="ttl" type="milliseconds"/>
+///     <field name="first-acquirer" type="boolean" default="false"/>
+///     <field name="delivery-count" type="uint" default="0"/>
+/// </type>
+/// ```
+/// The header section carries standard delivery details about the transfer of a message through
+/// the AMQP network. If the header section is omitted the receiver MUST assume the appropriate
+/// default values for the fields within the header unless other target or node specific defaults
+/// have otherwise been set.
+#[derive(Debug, Clone, PartialEq, Default, AmqpComposite)]
+#[amqp(name = "amqp:header:list", code = 0x70)]
+pub struct Header {
+    durable: Option<bool>, // default: false
+    priority: Option<u8>,  // default: 4
+    ttl: Option<Milliseconds>,
+    first_acquirer: Option<bool>, // default: false
+    delivery_count: Option<u32>,  // default: 0
+}
+
+impl Header {
+    pub fn durable(&self) -> bool {
+        self.durable.unwrap_or(false)
+    }
+
+    pub fn priority(&self) -> u8 {
+        self.priority.unwrap_or(4)
+    }
+
+    pub fn ttl(&self) -> Option<Milliseconds> {
+        self.ttl
+    }
+
+    pub fn first_acquirer(&self) -> bool {
+        self.first_acquirer.unwrap_or(false)
+    }
+
+    pub fn delivery_count(&self) -> u32 {
+        self.delivery_count.unwrap_or(0)
+    }
+
+    pub fn with_durable(mut self, durable: bool) -> Self {
+        self.durable = Some(durable);
+        self
+    }
+
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    pub fn with_ttl(mut self, ttl: Milliseconds) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn with_first_acquirer(mut self, first_acquirer: bool) -> Self {
+        self.first_acquirer = Some(first_acquirer);
+        self
+    }
+
+    pub fn with_delivery_count(mut self, delivery_count: u32) -> Self {
+        self.delivery_count = Some(delivery_count);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitive::Primitive;
+    use crate::serde::encode::Encode;
+
+    #[test]
+    fn test_defaults_apply_to_empty_header() {
+        let header = Header::default();
+        assert!(!header.durable());
+        assert_eq!(header.priority(), 4);
+        assert_eq!(header.ttl(), None);
+        assert!(!header.first_acquirer());
+        assert_eq!(header.delivery_count(), 0);
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let initial = Header::default()
+            .with_durable(true)
+            .with_priority(9)
+            .with_ttl(1000)
+            .with_delivery_count(2);
+        let encoded = Primitive::from(initial.clone()).encode().into_bytes();
+        let decoded =
+            Header::try_from(Primitive::try_decode(&mut encoded.into_iter()).unwrap()).unwrap();
+        assert_eq!(decoded, initial);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::error::amqp_error::AmqpError;
+use crate::error::AppError;
+use crate::primitive::fixed_width::uuid::Uuid;
+use crate::primitive::variable_width::binary::Binary;
+use crate::primitive::Primitive;
+
+/// # Message ID
+/// Application or system assigned identifier of a message.
+///
+/// ##### AMQP Specification
+/// ```xml
+/// <type name="message-id-ulong" class="restricted" source="ulong" provides="message-id"/>
+/// <type name="message-id-uuid" class="restricted" source="uuid" provides="message-id"/>
+/// <type name="message-id-binary" class="restricted" source="binary" provides="message-id"/>
+/// <type name="message-id-string" class="restricted" source="string" provides="message-id"/>
+/// ```
+/// The `message-id` and `correlation-id` fields of the properties section accept any of
+/// these four restricted types, so they share this representation.
+///
+/// ```
+///# use amqp_type::composite::messaging::message_id::MessageId;
+///# use amqp_type::primitive::Primitive;
+/// assert_eq!(Primitive::from(MessageId::from(5_u64)), Primitive::Ulong(5));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessageId {
+    Ulong(u64),
+    Uuid(Uuid),
+    Binary(Binary),
+    String(String),
+}
+
+impl From<u64> for MessageId {
+    fn from(value: u64) -> Self {
+        MessageId::Ulong(value)
+    }
+}
+
+impl From<Uuid> for MessageId {
+    fn from(value: Uuid) -> Self {
+        MessageId::Uuid(value)
+    }
+}
+
+impl From<Binary> for MessageId {
+    fn from(value: Binary) -> Self {
+        MessageId::Binary(value)
+    }
+}
+
+impl From<String> for MessageId {
+    fn from(value: String) -> Self {
+        MessageId::String(value)
+    }
+}
+
+impl From<&str> for MessageId {
+    fn from(value: &str) -> Self {
+        MessageId::String(value.to_string())
+    }
+}
+
+impl From<MessageId> for Primitive {
+    fn from(value: MessageId) -> Self {
+        match value {
+            MessageId::Ulong(x) => Primitive::Ulong(x),
+            MessageId::Uuid(x) => Primitive::Uuid(x),
+            MessageId::Binary(x) => Primitive::Binary(x),
+            MessageId::String(x) => Primitive::String(x),
+        }
+    }
+}
+
+impl TryFrom<Primitive> for MessageId {
+    type Error = AppError;
+
+    fn try_from(value: Primitive) -> Result<Self, Self::Error> {
+        match value {
+            Primitive::Ulong(x) => Ok(MessageId::Ulong(x)),
+            Primitive::Uuid(x) => Ok(MessageId::Uuid(x)),
+            Primitive::Binary(x) => Ok(MessageId::Binary(x)),
+            Primitive::String(x) => Ok(MessageId::String(x)),
+            _ => Err(AmqpError::DecodeError)?,
+        }
+    }
+}
+
+impl TryFrom<Primitive> for Option<MessageId> {
+    type Error = AppError;
+
+    fn try_from(value: Primitive) -> Result<Self, Self::Error> {
+        match value {
+            Primitive::Null => Ok(None),
+            x => Ok(Some(MessageId::try_from(x)?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_id_round_trip_through_primitive() {
+        let ids = vec![
+            MessageId::from(10_u64),
+            MessageId::from(Uuid::from(uuid::Uuid::new_v4())),
+            MessageId::from(Binary::from(vec![1, 2, 3])),
+            MessageId::from("order-15"),
+        ];
+        for id in ids {
+            assert_eq!(
+                MessageId::try_from(Primitive::from(id.clone())).unwrap(),
+                id
+            );
+        }
+    }
+
+    #[test]
+    fn test_message_id_rejects_other_primitives() {
+        assert!(matches!(
+            MessageId::try_from(Primitive::Int(1)),
+            Err(AppError::Amqp(AmqpError::DecodeError))
+        ));
+    }
+
+    #[test]
+    fn test_optional_message_id_from_null() {
+        let id: Option<MessageId> = Primitive::Null.try_into().unwrap();
+        assert_eq!(id, None);
+    }
+}
@@ -1 +1,7 @@
-pub mod delivery_state;
\ No newline at end of file
+pub mod annotations;
+pub mod application_properties;
+pub mod delivery_state;
+pub mod header;
+pub mod message_id;
+pub mod properties;
+pub mod section;
@@ -0,0 +1,205 @@
+use crate::composite::messaging::message_id::MessageId;
+use crate::primitive::fixed_width::timestamp::Timestamp;
+use crate::primitive::variable_width::binary::Binary;
+use crate::primitive::variable_width::symbol::Symbol;
+use crate::restricted::sequence_no::SequenceNumber;
+use amqp_derive::AmqpComposite;
+
+/// # Properties
+/// Immutable properties of the message.
+/// ##### AMQP Specification
+/// ```xml
+/// <type name="properties" class="composite" source="list" provides="section">
+///     <descriptor name="amqp:properties:list" code="0x00000000:0x00000073"/>
+///     <field name="message-id" type="*" requires="message-id"/>
+///     <field name="user-id" type="binary"/>
+///     <field name="to" type="*" requires="address"/>
+///     <field name="subject" type="string"/>
+///     <field name="reply-to" type="*" requires="address"/>
+///     <field name="correlation-id" type="*" requires="message-id"/>
+///     <field name="content-type" type="symbol"/>
+///     <field name="content-encoding" type="symbol"/>
+///     <field name="absolute-expiry-time" type="timestamp"/>
+///     <field name="creation-time" type="timestamp"/>
+///     <field name="group-id" type="string"/>
+///     <field name="group-sequence" type="sequence-no"/>
+///     <field name="reply-to-group-id" type="string"/>
+/// </type>
+/// ```
+/// The properties section is used for a defined set of standard properties of the message. The
+/// properties section is part of the bare message; therefore, if retransmitted by an intermediary,
+/// it MUST remain unaltered.
+#[derive(Debug, Clone, PartialEq, Default, AmqpComposite)]
+#[amqp(name = "amqp:properties:list", code = 0x73)]
+pub struct Properties {
+    message_id: Option<MessageId>,
+    user_id: Option<Binary>,
+    to: Option<String>,
+    subject: Option<String>,
+    reply_to: Option<String>,
+    correlation_id: Option<MessageId>,
+    content_type: Option<Symbol>,
+    content_encoding: Option<Symbol>,
+    absolute_expiry_time: Option<Timestamp>,
+    creation_time: Option<Timestamp>,
+    group_id: Option<String>,
+    group_sequence: Option<SequenceNumber>,
+    reply_to_group_id: Option<String>,
+}
+
+impl Properties {
+    pub fn message_id(&self) -> Option<&MessageId> {
+        self.message_id.as_ref()
+    }
+
+    pub fn user_id(&self) -> Option<&Binary> {
+        self.user_id.as_ref()
+    }
+
+    pub fn to(&self) -> Option<&str> {
+        self.to.as_deref()
+    }
+
+    pub fn subject(&self) -> Option<&str> {
+        self.subject.as_deref()
+    }
+
+    pub fn reply_to(&self) -> Option<&str> {
+        self.reply_to.as_deref()
+    }
+
+    pub fn correlation_id(&self) -> Option<&MessageId> {
+        self.correlation_id.as_ref()
+    }
+
+    pub fn content_type(&self) -> Option<&Symbol> {
+        self.content_type.as_ref()
+    }
+
+    pub fn content_encoding(&self) -> Option<&Symbol> {
+        self.content_encoding.as_ref()
+    }
+
+    pub fn absolute_expiry_time(&self) -> Option<&Timestamp> {
+        self.absolute_expiry_time.as_ref()
+    }
+
+    pub fn creation_time(&self) -> Option<&Timestamp> {
+        self.creation_time.as_ref()
+    }
+
+    pub fn group_id(&self) -> Option<&str> {
+        self.group_id.as_deref()
+    }
+
+    pub fn group_sequence(&self) -> Option<SequenceNumber> {
+        self.group_sequence
+    }
+
+    pub fn reply_to_group_id(&self) -> Option<&str> {
+        self.reply_to_group_id.as_deref()
+    }
+
+    pub fn with_message_id<T: Into<MessageId>>(mut self, message_id: T) -> Self {
+        self.message_id = Some(message_id.into());
+        self
+    }
+
+    pub fn with_user_id(mut self, user_id: Binary) -> Self {
+        self.user_id = Some(user_id);
+        self
+    }
+
+    pub fn with_to(mut self, to: String) -> Self {
+        self.to = Some(to);
+        self
+    }
+
+    pub fn with_subject(mut self, subject: String) -> Self {
+        self.subject = Some(subject);
+        self
+    }
+
+    pub fn with_reply_to(mut self, reply_to: String) -> Self {
+        self.reply_to = Some(reply_to);
+        self
+    }
+
+    pub fn with_correlation_id<T: Into<MessageId>>(mut self, correlation_id: T) -> Self {
+        self.correlation_id = Some(correlation_id.into());
+        self
+    }
+
+    pub fn with_content_type(mut self, content_type: Symbol) -> Self {
+        self.content_type = Some(content_type);
+        self
+    }
+
+    pub fn with_content_encoding(mut self, content_encoding: Symbol) -> Self {
+        self.content_encoding = Some(content_encoding);
+        self
+    }
+
+    pub fn with_absolute_expiry_time(mut self, absolute_expiry_time: Timestamp) -> Self {
+        self.absolute_expiry_time = Some(absolute_expiry_time);
+        self
+    }
+
+    pub fn with_creation_time(mut self, creation_time: Timestamp) -> Self {
+        self.creation_time = Some(creation_time);
+        self
+    }
+
+    pub fn with_group_id(mut self, group_id: String) -> Self {
+        self.group_id = Some(group_id);
+        self
+    }
+
+    pub fn with_group_sequence(mut self, group_sequence: SequenceNumber) -> Self {
+        self.group_sequence = Some(group_sequence);
+        self
+    }
+
+    pub fn with_reply_to_group_id(mut self, reply_to_group_id: String) -> Self {
+        self.reply_to_group_id = Some(reply_to_group_id);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitive::Primitive;
+    use crate::serde::encode::Encode;
+
+    #[test]
+    fn test_encode_decode_round_trip_empty() {
+        let initial = Properties::default();
+        let encoded = Primitive::from(initial.clone()).encode().into_bytes();
+        let decoded =
+            Properties::try_from(Primitive::try_decode(&mut encoded.into_iter()).unwrap()).unwrap();
+        assert_eq!(decoded, initial);
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip_full() {
+        let initial = Properties::default()
+            .with_message_id(42_u64)
+            .with_user_id(Binary::from(b"guest".to_vec()))
+            .with_to("queue://orders".to_string())
+            .with_subject("new-order".to_string())
+            .with_reply_to("queue://replies".to_string())
+            .with_correlation_id("request-1")
+            .with_content_type(Symbol::with_ascii("application/json"))
+            .with_content_encoding(Symbol::with_ascii("gzip"))
+            .with_absolute_expiry_time(Timestamp::from(2000))
+            .with_creation_time(Timestamp::from(1000))
+            .with_group_id("group".to_string())
+            .with_group_sequence(SequenceNumber::new(7))
+            .with_reply_to_group_id("reply-group".to_string());
+        let encoded = Primitive::from(initial.clone()).encode().into_bytes();
+        let decoded =
+            Properties::try_from(Primitive::try_decode(&mut encoded.into_iter()).unwrap()).unwrap();
+        assert_eq!(decoded, initial);
+    }
+}
@@ -0,0 +1,225 @@
+use crate::composite::messaging::annotations::Annotations;
+use crate::composite::messaging::application_properties::ApplicationProperties;
+use crate::composite::messaging::header::Header;
+use crate::composite::messaging::properties::Properties;
+use crate::composite::{Composite, Descriptor};
+use crate::constants::{
+    DESCRIBED_TYPE, SECTION_CODE_AMQP_SEQUENCE, SECTION_CODE_AMQP_VALUE,
+    SECTION_CODE_APPLICATION_PROPERTIES, SECTION_CODE_DATA, SECTION_CODE_DELIVERY_ANNOTATIONS,
+    SECTION_CODE_FOOTER, SECTION_CODE_HEADER, SECTION_CODE_MESSAGE_ANNOTATIONS,
+    SECTION_CODE_PROPERTIES, SECTION_SYMBOL_AMQP_SEQUENCE, SECTION_SYMBOL_AMQP_VALUE,
+    SECTION_SYMBOL_APPLICATION_PROPERTIES, SECTION_SYMBOL_DATA,
+    SECTION_SYMBOL_DELIVERY_ANNOTATIONS, SECTION_SYMBOL_FOOTER, SECTION_SYMBOL_HEADER,
+    SECTION_SYMBOL_MESSAGE_ANNOTATIONS, SECTION_SYMBOL_PROPERTIES,
+};
+use crate::error::amqp_error::AmqpError;
+use crate::error::AppError;
+use crate::primitive::compound::list::List;
+use crate::primitive::variable_width::binary::Binary;
+use crate::primitive::variable_width::symbol::Symbol;
+use crate::primitive::Primitive;
+use crate::serde::decode::Decode;
+use crate::serde::encode::{Encode, Encoded};
+use std::vec::IntoIter;
+
+/// # Section
+/// One section of an AMQP message.
+///
+/// A message on the wire is a sequence of sections, each of which is a described type. Unlike
+/// performatives, most sections are not described lists but described maps, binaries or arbitrary
+/// values, which is why they are encoded here rather than through [`Composite`] alone.
+///
+/// ```text
+///                                                      Bare Message
+///                                                            |
+///                                      .---------------------+--------------------.
+///                                      |                                          |
+/// +--------+-------------+-------------+------------+--------------+--------------+--------+
+/// | header | delivery-   | message-    | properties | application- | application- | footer |
+/// |        | annotations | annotations |            | properties   | data         |        |
+/// +--------+-------------+-------------+------------+--------------+--------------+--------+
+/// |                                                                                        |
+/// '-------------------------------------------+--------------------------------------------'
+///                                             |
+///                                      Annotated Message
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum Section {
+    Header(Header),
+    DeliveryAnnotations(Annotations),
+    MessageAnnotations(Annotations),
+    Properties(Properties),
+    ApplicationProperties(ApplicationProperties),
+    Data(Binary),
+    AmqpSequence(List),
+    AmqpValue(Primitive),
+    Footer(Annotations),
+}
+
+impl Section {
+    pub fn encode(self) -> Vec<u8> {
+        match self {
+            Section::Header(x) => Primitive::from(x).encode().into_bytes(),
+            Section::Properties(x) => Primitive::from(x).encode().into_bytes(),
+            Section::DeliveryAnnotations(x) => {
+                encode_described(SECTION_SYMBOL_DELIVERY_ANNOTATIONS, x.into())
+            }
+            Section::MessageAnnotations(x) => {
+                encode_described(SECTION_SYMBOL_MESSAGE_ANNOTATIONS, x.into())
+            }
+            Section::ApplicationProperties(x) => {
+                encode_described(SECTION_SYMBOL_APPLICATION_PROPERTIES, x.into())
+            }
+            Section::Data(x) => encode_described(SECTION_SYMBOL_DATA, x.into()),
+            Section::AmqpSequence(x) => encode_described(SECTION_SYMBOL_AMQP_SEQUENCE, x.into()),
+            Section::AmqpValue(x) => encode_described(SECTION_SYMBOL_AMQP_VALUE, x),
+            Section::Footer(x) => encode_described(SECTION_SYMBOL_FOOTER, x.into()),
+        }
+    }
+
+    pub fn try_decode(stream: &mut IntoIter<u8>) -> Result<Self, AppError> {
+        let constructor = stream.next().ok_or(AmqpError::DecodeError)?;
+        if constructor != DESCRIBED_TYPE {
+            Err(AmqpError::DecodeError)?
+        }
+        let descriptor_constructor = stream.next().ok_or(AmqpError::DecodeError)?;
+        let descriptor = Descriptor::try_decode(descriptor_constructor, stream)?;
+        let value = Primitive::try_decode(stream)?;
+        match section_code(&descriptor)? {
+            SECTION_CODE_HEADER => Ok(Section::Header(
+                described_list(descriptor, value)?.try_into()?,
+            )),
+            SECTION_CODE_DELIVERY_ANNOTATIONS => {
+                Ok(Section::DeliveryAnnotations(value.try_into()?))
+            }
+            SECTION_CODE_MESSAGE_ANNOTATIONS => Ok(Section::MessageAnnotations(value.try_into()?)),
+            SECTION_CODE_PROPERTIES => Ok(Section::Properties(
+                described_list(descriptor, value)?.try_into()?,
+            )),
+            SECTION_CODE_APPLICATION_PROPERTIES => {
+                Ok(Section::ApplicationProperties(value.try_into()?))
+            }
+            SECTION_CODE_DATA => Ok(Section::Data(value.try_into()?)),
+            SECTION_CODE_AMQP_SEQUENCE => Ok(Section::AmqpSequence(value.try_into()?)),
+            SECTION_CODE_AMQP_VALUE => Ok(Section::AmqpValue(value)),
+            SECTION_CODE_FOOTER => Ok(Section::Footer(value.try_into()?)),
+            _ => Err(AmqpError::DecodeError)?,
+        }
+    }
+
+    /// The descriptor code of the section, which also defines the order sections must appear in.
+    pub fn code(&self) -> u64 {
+        match self {
+            Section::Header(_) => SECTION_CODE_HEADER,
+            Section::DeliveryAnnotations(_) => SECTION_CODE_DELIVERY_ANNOTATIONS,
+            Section::MessageAnnotations(_) => SECTION_CODE_MESSAGE_ANNOTATIONS,
+            Section::Properties(_) => SECTION_CODE_PROPERTIES,
+            Section::ApplicationProperties(_) => SECTION_CODE_APPLICATION_PROPERTIES,
+            Section::Data(_) => SECTION_CODE_DATA,
+            Section::AmqpSequence(_) => SECTION_CODE_AMQP_SEQUENCE,
+            Section::AmqpValue(_) => SECTION_CODE_AMQP_VALUE,
+            Section::Footer(_) => SECTION_CODE_FOOTER,
+        }
+    }
+}
+
+fn encode_described(symbol: &str, value: Primitive) -> Vec<u8> {
+    let descriptor = Descriptor::Symbol(Symbol::with_ascii(symbol))
+        .encode()
+        .into_bytes();
+    Encoded::new_composite(DESCRIBED_TYPE, descriptor, value.encode().into_bytes()).into_bytes()
+}
+
+fn described_list(descriptor: Descriptor, value: Primitive) -> Result<Primitive, AppError> {
+    match value {
+        Primitive::List(list) => Ok(Primitive::Composite(Composite::new(descriptor, list))),
+        _ => Err(AmqpError::DecodeError)?,
+    }
+}
+
+fn section_code(descriptor: &Descriptor) -> Result<u64, AppError> {
+    match descriptor {
+        Descriptor::Code(code) => Ok(*code),
+        Descriptor::Symbol(symbol) => match symbol.inner() {
+            SECTION_SYMBOL_HEADER => Ok(SECTION_CODE_HEADER),
+            SECTION_SYMBOL_DELIVERY_ANNOTATIONS => Ok(SECTION_CODE_DELIVERY_ANNOTATIONS),
+            SECTION_SYMBOL_MESSAGE_ANNOTATIONS => Ok(SECTION_CODE_MESSAGE_ANNOTATIONS),
+            SECTION_SYMBOL_PROPERTIES => Ok(SECTION_CODE_PROPERTIES),
+            SECTION_SYMBOL_APPLICATION_PROPERTIES => Ok(SECTION_CODE_APPLICATION_PROPERTIES),
+            SECTION_SYMBOL_DATA => Ok(SECTION_CODE_DATA),
+            SECTION_SYMBOL_AMQP_SEQUENCE => Ok(SECTION_CODE_AMQP_SEQUENCE),
+            SECTION_SYMBOL_AMQP_VALUE => Ok(SECTION_CODE_AMQP_VALUE),
+            SECTION_SYMBOL_FOOTER => Ok(SECTION_CODE_FOOTER),
+            _ => Err(AmqpError::DecodeError)?,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(section: Section) {
+        let encoded = section.clone().encode();
+        let decoded = Section::try_decode(&mut encoded.into_iter()).unwrap();
+        assert_eq!(decoded, section);
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip_header() {
+        round_trip(Section::Header(Header::default().with_durable(true)));
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip_properties() {
+        round_trip(Section::Properties(
+            Properties::default().with_message_id("id-1"),
+        ));
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip_annotations() {
+        let mut annotations = Annotations::new();
+        annotations.insert(Symbol::with_ascii("x-opt-key"), "value");
+        round_trip(Section::DeliveryAnnotations(annotations.clone()));
+        round_trip(Section::MessageAnnotations(annotations.clone()));
+        round_trip(Section::Footer(annotations));
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip_application_properties() {
+        let mut properties = ApplicationProperties::new();
+        properties.insert("count", 10_u32).unwrap();
+        round_trip(Section::ApplicationProperties(properties));
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip_body_sections() {
+        round_trip(Section::Data(Binary::from(vec![1, 2, 3])));
+        round_trip(Section::AmqpSequence(List::from(vec![1, 2, 3])));
+        round_trip(Section::AmqpValue(Primitive::String("hello".to_string())));
+    }
+
+    #[test]
+    fn test_decode_accepts_numeric_descriptor() {
+        let descriptor = SECTION_CODE_DATA.encode().into_bytes();
+        let value = Primitive::Binary(Binary::from(vec![9]))
+            .encode()
+            .into_bytes();
+        let encoded = Encoded::new_composite(DESCRIBED_TYPE, descriptor, value).into_bytes();
+        assert_eq!(
+            Section::try_decode(&mut encoded.into_iter()).unwrap(),
+            Section::Data(Binary::from(vec![9]))
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_unknown_descriptor() {
+        let encoded = encode_described("amqp:unknown:map", Primitive::Null);
+        assert!(matches!(
+            Section::try_decode(&mut encoded.into_iter()),
+            Err(AppError::Amqp(AmqpError::DecodeError))
+        ));
+    }
+}
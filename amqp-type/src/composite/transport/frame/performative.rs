@@ -98,22 +98,6 @@ impl Performative {
             _ => Err(AmqpError::DecodeError)?
         }
     }
-
-    // performatives always contain the payload,
-    // which is the rest of the frame body, after the performative
-    pub fn payload(&self) -> Vec<u8> {
-        match self {
-            Performative::Open(_) => todo!(),
-            Performative::Begin(_) => todo!(),
-            Performative::Attach(_) => todo!(),
-            Performative::Flow(_) => todo!(),
-            Performative::Transfer(_) => todo!(),
-            Performative::Disposition(_) => todo!(),
-            Performative::Detach(_) => todo!(),
-            Performative::End(_) => todo!(),
-            Performative::Close(_) => todo!(),
-        }
-    }
 }
 
 impl From<Open> for Performative {
@@ -171,4 +155,18 @@ impl From<Close> for Performative {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_leaves_payload_in_stream() {
+        let mut encoded = Transfer::new(1).encode();
+        encoded.extend_from_slice(&[1, 2, 3]);
+        let mut stream = encoded.into_iter();
+        assert_eq!(
+            Performative::try_decode(&mut stream).unwrap(),
+            Performative::Transfer(Transfer::new(1))
+        );
+        assert_eq!(stream.collect::<Vec<u8>>(), vec![1, 2, 3]);
+    }
+}
@@ -53,6 +53,122 @@ impl Attach {
             properties: None,
         }
     }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn handle(&self) -> Handle {
+        self.handle
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn snd_settle_mode(&self) -> SenderSettleMode {
+        self.snd_settle_mode.unwrap_or(SenderSettleMode::Mixed)
+    }
+
+    pub fn rcv_settle_mode(&self) -> ReceiverSettleMode {
+        self.rcv_settle_mode.unwrap_or(ReceiverSettleMode::First)
+    }
+
+    pub fn source(&self) -> Option<&Source> {
+        self.source.as_ref()
+    }
+
+    pub fn target(&self) -> Option<&Target> {
+        self.target.as_ref()
+    }
+
+    pub fn unsettled(&self) -> Option<&Map> {
+        self.unsettled.as_ref()
+    }
+
+    pub fn incomplete_unsettled(&self) -> bool {
+        self.incomplete_unsettled.unwrap_or(false)
+    }
+
+    pub fn initial_delivery_count(&self) -> Option<SequenceNumber> {
+        self.initial_delivery_count
+    }
+
+    pub fn max_message_size(&self) -> Option<u64> {
+        self.max_message_size
+    }
+
+    pub fn offered_capabilities(&self) -> &[Symbol] {
+        &self.offered_capabilities
+    }
+
+    pub fn desired_capabilities(&self) -> &[Symbol] {
+        &self.desired_capabilities
+    }
+
+    pub fn properties(&self) -> Option<&Fields> {
+        self.properties.as_ref()
+    }
+
+    pub fn with_handle(mut self, handle: Handle) -> Self {
+        self.handle = handle;
+        self
+    }
+
+    pub fn with_snd_settle_mode(mut self, snd_settle_mode: SenderSettleMode) -> Self {
+        self.snd_settle_mode = Some(snd_settle_mode);
+        self
+    }
+
+    pub fn with_rcv_settle_mode(mut self, rcv_settle_mode: ReceiverSettleMode) -> Self {
+        self.rcv_settle_mode = Some(rcv_settle_mode);
+        self
+    }
+
+    pub fn with_source(mut self, source: Source) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn with_target(mut self, target: Target) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    pub fn with_unsettled(mut self, unsettled: Map) -> Self {
+        self.unsettled = Some(unsettled);
+        self
+    }
+
+    pub fn with_incomplete_unsettled(mut self, incomplete_unsettled: bool) -> Self {
+        self.incomplete_unsettled = Some(incomplete_unsettled);
+        self
+    }
+
+    pub fn with_initial_delivery_count(mut self, initial_delivery_count: SequenceNumber) -> Self {
+        self.initial_delivery_count = Some(initial_delivery_count);
+        self
+    }
+
+    pub fn with_max_message_size(mut self, max_message_size: u64) -> Self {
+        self.max_message_size = Some(max_message_size);
+        self
+    }
+
+    pub fn with_offered_capabilities(mut self, capabilities: Vec<Symbol>) -> Self {
+        self.offered_capabilities = capabilities;
+        self
+    }
+
+    pub fn with_desired_capabilities(mut self, capabilities: Vec<Symbol>) -> Self {
+        self.desired_capabilities = capabilities;
+        self
+    }
+
+    pub fn with_properties(mut self, properties: Fields) -> Self {
+        self.properties = Some(properties);
+        self
+    }
 }
 
 impl Attach {
@@ -89,11 +205,11 @@ mod tests {
             role: Role::Sender,
             snd_settle_mode: Some(SenderSettleMode::Unsettled),
             rcv_settle_mode: Some(ReceiverSettleMode::First),
-            source: Some(Source::default()),
-            target: Some(Target::default()),
+            source: Some(Source::new("queue")),
+            target: Some(Target::new("topic")),
             unsettled: Some(Map::from(
                 vec![(Primitive::Symbol(Symbol::with_ascii("unsettled")), Primitive::String("why though?".to_string()))]
-            ).try_into().unwrap()),
+            )),
             incomplete_unsettled: Some(true),
             initial_delivery_count: Some(SequenceNumber::new(0)),
             max_message_size: Some(1024),
@@ -114,4 +230,13 @@ mod tests {
 
         assert_eq!(Performative::Attach(initial), decoded);
     }
+
+    #[test]
+    fn test_defaults() {
+        let attach = Attach::new("test".to_string(), 0, Role::Sender);
+        assert_eq!(attach.snd_settle_mode(), SenderSettleMode::Mixed);
+        assert_eq!(attach.rcv_settle_mode(), ReceiverSettleMode::First);
+        assert!(!attach.incomplete_unsettled());
+        assert_eq!(attach.initial_delivery_count(), None);
+    }
 }
\ No newline at end of file
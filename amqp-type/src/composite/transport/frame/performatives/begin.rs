@@ -19,7 +19,83 @@ pub struct Begin {
     handle_max: Option<Handle>, // default: 4294967295
     offered_capabilities: Vec<Symbol>,
     desired_capabilities: Vec<Symbol>,
-    properties: Fields,
+    properties: Option<Fields>,
+}
+
+impl Begin {
+    pub fn new(
+        next_outgoing_id: TransferNumber,
+        incoming_window: u32,
+        outgoing_window: u32,
+    ) -> Self {
+        Begin {
+            remote_channel: None,
+            next_outgoing_id,
+            incoming_window,
+            outgoing_window,
+            handle_max: None,
+            offered_capabilities: vec![],
+            desired_capabilities: vec![],
+            properties: None,
+        }
+    }
+
+    pub fn remote_channel(&self) -> Option<u16> {
+        self.remote_channel
+    }
+
+    pub fn next_outgoing_id(&self) -> TransferNumber {
+        self.next_outgoing_id
+    }
+
+    pub fn incoming_window(&self) -> u32 {
+        self.incoming_window
+    }
+
+    pub fn outgoing_window(&self) -> u32 {
+        self.outgoing_window
+    }
+
+    pub fn handle_max(&self) -> Handle {
+        self.handle_max.unwrap_or(u32::MAX)
+    }
+
+    pub fn offered_capabilities(&self) -> &[Symbol] {
+        &self.offered_capabilities
+    }
+
+    pub fn desired_capabilities(&self) -> &[Symbol] {
+        &self.desired_capabilities
+    }
+
+    pub fn properties(&self) -> Option<&Fields> {
+        self.properties.as_ref()
+    }
+
+    pub fn with_remote_channel(mut self, remote_channel: u16) -> Self {
+        self.remote_channel = Some(remote_channel);
+        self
+    }
+
+    pub fn with_handle_max(mut self, handle_max: Handle) -> Self {
+        self.handle_max = Some(handle_max);
+        self
+    }
+
+    pub fn with_offered_capabilities(mut self, capabilities: Vec<Symbol>) -> Self {
+        self.offered_capabilities = capabilities;
+        self
+    }
+
+    pub fn with_desired_capabilities(mut self, capabilities: Vec<Symbol>) -> Self {
+        self.desired_capabilities = capabilities;
+        self
+    }
+
+    pub fn with_properties(mut self, properties: Fields) -> Self {
+        self.properties = Some(properties);
+        self
+    }
 }
 
 impl Begin {
@@ -32,3 +108,34 @@ impl Begin {
         Self::try_from(Primitive::from(composite))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::composite::transport::frame::performative::Performative;
+
+    #[test]
+    fn test_encode_decode_round_trip_empty() {
+        let initial = Begin::new(0.into(), 2048, 2048);
+        let encoded = initial.clone().encode();
+        let decoded = Performative::try_decode(&mut encoded.into_iter()).unwrap();
+        assert_eq!(Performative::Begin(initial), decoded);
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip_all_values() {
+        let initial = Begin::new(u32::MAX.into(), 1, 0)
+            .with_remote_channel(7)
+            .with_handle_max(255)
+            .with_offered_capabilities(vec![Symbol::with_ascii("foo")])
+            .with_desired_capabilities(vec![Symbol::with_ascii("bar")]);
+        let encoded = initial.clone().encode();
+        let decoded = Performative::try_decode(&mut encoded.into_iter()).unwrap();
+        assert_eq!(Performative::Begin(initial), decoded);
+    }
+
+    #[test]
+    fn test_handle_max_defaults_to_max() {
+        assert_eq!(Begin::new(0.into(), 1, 1).handle_max(), u32::MAX);
+    }
+}
@@ -1,16 +1,59 @@
-use crate::error::AppError;
+use crate::composite::transport::transport::error::Error;
 use crate::composite::Composite;
+use crate::error::AppError;
+use crate::primitive::Primitive;
+use crate::serde::encode::Encode;
+use amqp_derive::AmqpComposite;
 use std::vec::IntoIter;
 
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub struct Close {}
+#[derive(Debug, Clone, PartialEq, Default, AmqpComposite)]
+#[amqp(name = "amqp:close:list", code = 0x18)]
+pub struct Close {
+    error: Option<Error>,
+}
+
+impl Close {
+    pub fn new(error: Option<Error>) -> Self {
+        Close { error }
+    }
+
+    pub fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+}
 
 impl Close {
     pub fn encode(self) -> Vec<u8> {
-        todo!()
+        let primitive: Primitive = self.into();
+        primitive.encode().into_bytes()
+    }
+
+    pub fn try_decode(composite: Composite, _body: &mut IntoIter<u8>) -> Result<Self, AppError> {
+        Self::try_from(Primitive::from(composite))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::composite::transport::frame::performative::Performative;
+    use crate::primitive::variable_width::symbol::Symbol;
+
+    #[test]
+    fn test_encode_decode_round_trip_empty() {
+        let initial = Close::default();
+        let encoded = initial.clone().encode();
+        let decoded = Performative::try_decode(&mut encoded.into_iter()).unwrap();
+        assert_eq!(Performative::Close(initial), decoded);
     }
 
-    pub fn try_decode(_composite: Composite, _body: &mut IntoIter<u8>) -> Result<Self, AppError> {
-        todo!()
+    #[test]
+    fn test_encode_decode_round_trip_with_error() {
+        let initial = Close::new(Some(Error::new(Symbol::with_ascii(
+            "amqp:connection:forced",
+        ))));
+        let encoded = initial.clone().encode();
+        let decoded = Performative::try_decode(&mut encoded.into_iter()).unwrap();
+        assert_eq!(Performative::Close(initial), decoded);
     }
 }
@@ -1,16 +1,89 @@
-use crate::error::AppError;
+use crate::composite::transport::transport::error::Error;
 use crate::composite::Composite;
+use crate::error::AppError;
+use crate::primitive::Primitive;
+use crate::restricted::handle::Handle;
+use crate::serde::encode::Encode;
+use amqp_derive::AmqpComposite;
 use std::vec::IntoIter;
 
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub struct Detach {}
+#[derive(Debug, Clone, PartialEq, AmqpComposite)]
+#[amqp(name = "amqp:detach:list", code = 0x16)]
+pub struct Detach {
+    handle: Handle,
+    closed: Option<bool>, // default: false
+    error: Option<Error>,
+}
+
+impl Detach {
+    pub fn new(handle: Handle) -> Self {
+        Detach {
+            handle,
+            closed: None,
+            error: None,
+        }
+    }
+
+    pub fn handle(&self) -> Handle {
+        self.handle
+    }
+
+    pub fn closed(&self) -> bool {
+        self.closed.unwrap_or(false)
+    }
+
+    pub fn error(&self) -> Option<&Error> {
+        self.error.as_ref()
+    }
+
+    pub fn with_closed(mut self, closed: bool) -> Self {
+        self.closed = Some(closed);
+        self
+    }
+
+    pub fn with_error(mut self, error: Error) -> Self {
+        self.error = Some(error);
+        self
+    }
+}
 
 impl Detach {
     pub fn encode(self) -> Vec<u8> {
-        todo!()
+        let primitive: Primitive = self.into();
+        primitive.encode().into_bytes()
+    }
+
+    pub fn try_decode(composite: Composite, _body: &mut IntoIter<u8>) -> Result<Self, AppError> {
+        Self::try_from(Primitive::from(composite))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::composite::transport::frame::performative::Performative;
+    use crate::primitive::variable_width::symbol::Symbol;
+
+    #[test]
+    fn test_encode_decode_round_trip_empty() {
+        let initial = Detach::new(3);
+        let encoded = initial.clone().encode();
+        let decoded = Performative::try_decode(&mut encoded.into_iter()).unwrap();
+        assert_eq!(Performative::Detach(initial), decoded);
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip_closing_with_error() {
+        let initial = Detach::new(3)
+            .with_closed(true)
+            .with_error(Error::new(Symbol::with_ascii("amqp:link:detach-forced")));
+        let encoded = initial.clone().encode();
+        let decoded = Performative::try_decode(&mut encoded.into_iter()).unwrap();
+        assert_eq!(Performative::Detach(initial), decoded);
     }
 
-    pub fn try_decode(_composite: Composite, _body: &mut IntoIter<u8>) -> Result<Self, AppError> {
-        todo!()
+    #[test]
+    fn test_closed_defaults_to_false() {
+        assert!(!Detach::new(0).closed());
     }
 }
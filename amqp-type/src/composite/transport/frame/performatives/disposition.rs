@@ -1,16 +1,146 @@
-use crate::error::AppError;
+use crate::composite::messaging::delivery_state::DeliveryState;
 use crate::composite::Composite;
+use crate::error::AppError;
+use crate::primitive::Primitive;
+use crate::restricted::delivery_number::DeliveryNumber;
+use crate::restricted::role::Role;
+use crate::serde::encode::Encode;
+use amqp_derive::AmqpComposite;
 use std::vec::IntoIter;
 
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub struct Disposition {}
+/// # Disposition
+/// Informs the peer of changes to the state of a range of deliveries, `first` to `last`
+/// (inclusive). `role` is the role of the endpoint sending the disposition, so a receiver
+/// reports outcomes with `role = receiver` for deliveries it received.
+#[derive(Debug, Clone, PartialEq, AmqpComposite)]
+#[amqp(name = "amqp:disposition:list", code = 0x15)]
+pub struct Disposition {
+    role: Role,
+    first: DeliveryNumber,
+    last: Option<DeliveryNumber>,
+    settled: Option<bool>, // default: false
+    state: Option<DeliveryState>,
+    batchable: Option<bool>, // default: false
+}
+
+impl Disposition {
+    pub fn new(role: Role, first: DeliveryNumber) -> Self {
+        Disposition {
+            role,
+            first,
+            last: None,
+            settled: None,
+            state: None,
+            batchable: None,
+        }
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn first(&self) -> DeliveryNumber {
+        self.first
+    }
+
+    /// The last delivery of the range, which is `first` if the field is absent.
+    pub fn last(&self) -> DeliveryNumber {
+        self.last.unwrap_or(self.first)
+    }
+
+    pub fn settled(&self) -> bool {
+        self.settled.unwrap_or(false)
+    }
+
+    pub fn state(&self) -> Option<&DeliveryState> {
+        self.state.as_ref()
+    }
+
+    pub fn batchable(&self) -> bool {
+        self.batchable.unwrap_or(false)
+    }
+
+    /// Whether the delivery with the given id lies within `first` and `last`, taking
+    /// wrap-around of the delivery numbers into account.
+    pub fn contains(&self, delivery_id: DeliveryNumber) -> bool {
+        delivery_id.in_range(self.first, self.last())
+    }
+
+    pub fn with_last(mut self, last: DeliveryNumber) -> Self {
+        self.last = Some(last);
+        self
+    }
+
+    pub fn with_settled(mut self, settled: bool) -> Self {
+        self.settled = Some(settled);
+        self
+    }
+
+    pub fn with_state(mut self, state: DeliveryState) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    pub fn with_batchable(mut self, batchable: bool) -> Self {
+        self.batchable = Some(batchable);
+        self
+    }
+}
 
 impl Disposition {
     pub fn encode(self) -> Vec<u8> {
-        todo!()
+        let primitive: Primitive = self.into();
+        primitive.encode().into_bytes()
+    }
+
+    pub fn try_decode(composite: Composite, _body: &mut IntoIter<u8>) -> Result<Self, AppError> {
+        Self::try_from(Primitive::from(composite))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::composite::messaging::delivery_state::accepted::Accepted;
+    use crate::composite::transport::frame::performative::Performative;
+
+    #[test]
+    fn test_encode_decode_round_trip_minimal() {
+        let initial = Disposition::new(Role::Receiver, 5.into());
+        let encoded = initial.clone().encode();
+        let decoded = Performative::try_decode(&mut encoded.into_iter()).unwrap();
+        assert_eq!(Performative::Disposition(initial), decoded);
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip_all_values() {
+        let initial = Disposition::new(Role::Sender, 5.into())
+            .with_last(9.into())
+            .with_settled(true)
+            .with_state(DeliveryState::Accepted(Accepted {}))
+            .with_batchable(true);
+        let encoded = initial.clone().encode();
+        let decoded = Performative::try_decode(&mut encoded.into_iter()).unwrap();
+        assert_eq!(Performative::Disposition(initial), decoded);
+    }
+
+    #[test]
+    fn test_range_defaults_to_first() {
+        let disposition = Disposition::new(Role::Receiver, 5.into());
+        assert_eq!(disposition.last(), 5.into());
+        assert!(disposition.contains(5.into()));
+        assert!(!disposition.contains(6.into()));
+        assert!(!disposition.settled());
     }
 
-    pub fn try_decode(_composite: Composite, _body: &mut IntoIter<u8>) -> Result<Self, AppError> {
-        todo!()
+    #[test]
+    fn test_range_wraps_around() {
+        let disposition =
+            Disposition::new(Role::Receiver, (u32::MAX - 1).into()).with_last(1.into());
+        assert!(disposition.contains(u32::MAX.into()));
+        assert!(disposition.contains(0.into()));
+        assert!(disposition.contains(1.into()));
+        assert!(!disposition.contains(2.into()));
+        assert!(!disposition.contains((u32::MAX - 2).into()));
     }
 }
@@ -25,6 +25,108 @@ pub struct Flow {
     properties: Option<Fields>
 }
 
+impl Flow {
+    pub fn new(
+        next_incoming_id: TransferNumber,
+        incoming_window: u32,
+        next_outgoing_id: TransferNumber,
+        outgoing_window: u32,
+    ) -> Self {
+        Flow {
+            next_incoming_id,
+            incoming_window,
+            next_outgoing_id,
+            outgoing_window,
+            handle: None,
+            delivery_count: None,
+            link_credit: None,
+            available: None,
+            drain: None,
+            echo: None,
+            properties: None,
+        }
+    }
+
+    pub fn next_incoming_id(&self) -> TransferNumber {
+        self.next_incoming_id
+    }
+
+    pub fn incoming_window(&self) -> u32 {
+        self.incoming_window
+    }
+
+    pub fn next_outgoing_id(&self) -> TransferNumber {
+        self.next_outgoing_id
+    }
+
+    pub fn outgoing_window(&self) -> u32 {
+        self.outgoing_window
+    }
+
+    pub fn handle(&self) -> Option<Handle> {
+        self.handle
+    }
+
+    pub fn delivery_count(&self) -> Option<SequenceNumber> {
+        self.delivery_count
+    }
+
+    pub fn link_credit(&self) -> Option<u32> {
+        self.link_credit
+    }
+
+    pub fn available(&self) -> Option<u32> {
+        self.available
+    }
+
+    pub fn drain(&self) -> bool {
+        self.drain.unwrap_or(false)
+    }
+
+    pub fn echo(&self) -> bool {
+        self.echo.unwrap_or(false)
+    }
+
+    pub fn properties(&self) -> Option<&Fields> {
+        self.properties.as_ref()
+    }
+
+    pub fn with_handle(mut self, handle: Handle) -> Self {
+        self.handle = Some(handle);
+        self
+    }
+
+    pub fn with_delivery_count(mut self, delivery_count: SequenceNumber) -> Self {
+        self.delivery_count = Some(delivery_count);
+        self
+    }
+
+    pub fn with_link_credit(mut self, link_credit: u32) -> Self {
+        self.link_credit = Some(link_credit);
+        self
+    }
+
+    pub fn with_available(mut self, available: u32) -> Self {
+        self.available = Some(available);
+        self
+    }
+
+    pub fn with_drain(mut self, drain: bool) -> Self {
+        self.drain = Some(drain);
+        self
+    }
+
+    pub fn with_echo(mut self, echo: bool) -> Self {
+        self.echo = Some(echo);
+        self
+    }
+
+    pub fn with_properties(mut self, properties: Fields) -> Self {
+        self.properties = Some(properties);
+        self
+    }
+}
+
 impl Flow {
     pub fn encode(self) -> Vec<u8> {
         let enc: Primitive = self.into();
@@ -35,3 +137,38 @@ impl Flow {
         Self::try_from(Primitive::from(composite))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::composite::transport::frame::performative::Performative;
+
+    #[test]
+    fn test_encode_decode_round_trip_session_flow() {
+        let initial = Flow::new(3.into(), 100, 7.into(), 200);
+        let encoded = initial.clone().encode();
+        let decoded = Performative::try_decode(&mut encoded.into_iter()).unwrap();
+        assert_eq!(Performative::Flow(initial), decoded);
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip_link_flow() {
+        let initial = Flow::new(3.into(), 100, 7.into(), 200)
+            .with_handle(1)
+            .with_delivery_count(42.into())
+            .with_link_credit(10)
+            .with_available(5)
+            .with_drain(true)
+            .with_echo(true);
+        let encoded = initial.clone().encode();
+        let decoded = Performative::try_decode(&mut encoded.into_iter()).unwrap();
+        assert_eq!(Performative::Flow(initial), decoded);
+    }
+
+    #[test]
+    fn test_drain_and_echo_default_to_false() {
+        let flow = Flow::new(0.into(), 0, 0.into(), 0);
+        assert!(!flow.drain());
+        assert!(!flow.echo());
+    }
+}
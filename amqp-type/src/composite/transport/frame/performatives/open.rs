@@ -39,6 +39,73 @@ impl Open {
             properties: None,
         }
     }
+
+    pub fn container_id(&self) -> &str {
+        &self.container_id
+    }
+
+    pub fn host_name(&self) -> Option<&str> {
+        self.host_name.as_deref()
+    }
+
+    pub fn max_frame_size(&self) -> u32 {
+        self.max_frame_size.unwrap_or(u32::MAX)
+    }
+
+    pub fn channel_max(&self) -> u16 {
+        self.channel_max.unwrap_or(u16::MAX)
+    }
+
+    pub fn idle_timeout(&self) -> Option<Milliseconds> {
+        self.idle_timeout
+    }
+
+    pub fn offered_capabilities(&self) -> &[Symbol] {
+        &self.offered_capabilities
+    }
+
+    pub fn desired_capabilities(&self) -> &[Symbol] {
+        &self.desired_capabilities
+    }
+
+    pub fn properties(&self) -> Option<&Fields> {
+        self.properties.as_ref()
+    }
+
+    pub fn with_host_name(mut self, host_name: String) -> Self {
+        self.host_name = Some(host_name);
+        self
+    }
+
+    pub fn with_max_frame_size(mut self, max_frame_size: u32) -> Self {
+        self.max_frame_size = Some(max_frame_size);
+        self
+    }
+
+    pub fn with_channel_max(mut self, channel_max: u16) -> Self {
+        self.channel_max = Some(channel_max);
+        self
+    }
+
+    pub fn with_idle_timeout(mut self, idle_timeout: Milliseconds) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    pub fn with_offered_capabilities(mut self, capabilities: Vec<Symbol>) -> Self {
+        self.offered_capabilities = capabilities;
+        self
+    }
+
+    pub fn with_desired_capabilities(mut self, capabilities: Vec<Symbol>) -> Self {
+        self.desired_capabilities = capabilities;
+        self
+    }
+
+    pub fn with_properties(mut self, properties: Fields) -> Self {
+        self.properties = Some(properties);
+        self
+    }
 }
 
 impl Open {
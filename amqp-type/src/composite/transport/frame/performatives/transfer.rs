@@ -25,8 +25,123 @@ pub struct Transfer {
     resume: Option<bool>, // default: false
     aborted: Option<bool>, // default: false
     batchable: Option<bool>, // default: false
+}
+
+impl Transfer {
+    pub fn new(handle: Handle) -> Self {
+        Transfer {
+            handle,
+            delivery_id: None,
+            delivery_tag: None,
+            message_format: None,
+            settled: None,
+            more: None,
+            rcv_settle_mode: None,
+            state: None,
+            resume: None,
+            aborted: None,
+            batchable: None,
+        }
+    }
+
+    pub fn handle(&self) -> Handle {
+        self.handle
+    }
+
+    pub fn delivery_id(&self) -> Option<DeliveryNumber> {
+        self.delivery_id
+    }
+
+    pub fn delivery_tag(&self) -> Option<&DeliveryTag> {
+        self.delivery_tag.as_ref()
+    }
+
+    pub fn message_format(&self) -> Option<MessageFormat> {
+        self.message_format
+    }
+
+    pub fn settled(&self) -> Option<bool> {
+        self.settled
+    }
+
+    pub fn more(&self) -> bool {
+        self.more.unwrap_or(false)
+    }
+
+    pub fn rcv_settle_mode(&self) -> Option<ReceiverSettleMode> {
+        self.rcv_settle_mode
+    }
+
+    pub fn state(&self) -> Option<&DeliveryState> {
+        self.state.as_ref()
+    }
+
+    pub fn resume(&self) -> bool {
+        self.resume.unwrap_or(false)
+    }
+
+    pub fn aborted(&self) -> bool {
+        self.aborted.unwrap_or(false)
+    }
+
+    pub fn batchable(&self) -> bool {
+        self.batchable.unwrap_or(false)
+    }
 
+    pub fn with_handle(mut self, handle: Handle) -> Self {
+        self.handle = handle;
+        self
+    }
+
+    pub fn with_delivery_id(mut self, delivery_id: DeliveryNumber) -> Self {
+        self.delivery_id = Some(delivery_id);
+        self
+    }
+
+    pub fn with_delivery_tag(mut self, delivery_tag: DeliveryTag) -> Self {
+        self.delivery_tag = Some(delivery_tag);
+        self
+    }
+
+    pub fn with_message_format(mut self, message_format: MessageFormat) -> Self {
+        self.message_format = Some(message_format);
+        self
+    }
+
+    pub fn with_settled(mut self, settled: bool) -> Self {
+        self.settled = Some(settled);
+        self
+    }
+
+    pub fn with_more(mut self, more: bool) -> Self {
+        self.more = Some(more);
+        self
+    }
+
+    pub fn with_rcv_settle_mode(mut self, rcv_settle_mode: ReceiverSettleMode) -> Self {
+        self.rcv_settle_mode = Some(rcv_settle_mode);
+        self
+    }
 
+    pub fn with_state(mut self, state: DeliveryState) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = Some(resume);
+        self
+    }
+
+    pub fn with_aborted(mut self, aborted: bool) -> Self {
+        self.aborted = Some(aborted);
+        self
+    }
+
+    pub fn with_batchable(mut self, batchable: bool) -> Self {
+        self.batchable = Some(batchable);
+        self
+    }
 }
 
 impl Transfer {
@@ -39,3 +154,34 @@ impl Transfer {
         Self::try_from(Primitive::from(composite))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::composite::transport::frame::performative::Performative;
+    use crate::restricted::delivery_number::DeliveryNumber;
+
+    #[test]
+    fn test_encode_decode_round_trip_empty() {
+        let initial = Transfer::new(0);
+        let encoded = initial.clone().encode();
+        let decoded = Performative::try_decode(&mut encoded.into_iter()).unwrap();
+        assert_eq!(Performative::Transfer(initial), decoded);
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip_all_values() {
+        let initial = Transfer::new(7)
+            .with_delivery_id(DeliveryNumber::new(3))
+            .with_delivery_tag(DeliveryTag::new(vec![1, 2, 3]).unwrap())
+            .with_message_format(0)
+            .with_settled(false)
+            .with_more(true)
+            .with_resume(false)
+            .with_aborted(false)
+            .with_batchable(true);
+        let encoded = initial.clone().encode();
+        let decoded = Performative::try_decode(&mut encoded.into_iter()).unwrap();
+        assert_eq!(Performative::Transfer(initial), decoded);
+    }
+}
@@ -1,2 +1,3 @@
+#[allow(clippy::module_inception)]
 pub mod transport;
 pub mod frame;
\ No newline at end of file
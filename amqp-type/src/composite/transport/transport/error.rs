@@ -0,0 +1,91 @@
+use crate::error::{AppError, ErrorCondition};
+use crate::primitive::variable_width::symbol::Symbol;
+use crate::restricted::fields::Fields;
+use amqp_derive::AmqpComposite;
+
+/// # Error
+/// Details of an error.
+/// ##### AMQP Specification
+/// ```xml
+/// <type name="error" class="composite" source="list">
+///     <descriptor name="amqp:error:list" code="0x00000000:0x0000001d"/>
+///     <field name="condition" type="symbol" requires="error-condition" mandatory="true"/>
+///     <field name="description" type="string"/>
+///     <field name="info" type="fields"/>
+/// </type>
+/// ```
+/// Carried by the close, end and detach performatives and by the rejected outcome.
+#[derive(Debug, Clone, PartialEq, AmqpComposite)]
+#[amqp(name = "amqp:error:list", code = 0x1d)]
+pub struct Error {
+    condition: Symbol,
+    description: Option<String>,
+    info: Option<Fields>,
+}
+
+impl Error {
+    pub fn new(condition: Symbol) -> Self {
+        Error {
+            condition,
+            description: None,
+            info: None,
+        }
+    }
+
+    pub fn condition(&self) -> &Symbol {
+        &self.condition
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn info(&self) -> Option<&Fields> {
+        self.info.as_ref()
+    }
+
+    pub fn with_description(mut self, description: String) -> Self {
+        self.description = Some(description);
+        self
+    }
+
+    pub fn with_info(mut self, info: Fields) -> Self {
+        self.info = Some(info);
+        self
+    }
+}
+
+impl From<AppError> for Error {
+    fn from(value: AppError) -> Self {
+        Error {
+            condition: value.error_condition(),
+            description: value.amqp_description(),
+            info: value.info(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::amqp_error::AmqpError;
+    use crate::primitive::Primitive;
+    use crate::serde::encode::Encode;
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let initial = Error::new(Symbol::with_ascii("amqp:internal-error"))
+            .with_description("something broke".to_string());
+        let encoded = Primitive::from(initial.clone()).encode().into_bytes();
+        let decoded =
+            Error::try_from(Primitive::try_decode(&mut encoded.into_iter()).unwrap()).unwrap();
+        assert_eq!(decoded, initial);
+    }
+
+    #[test]
+    fn test_from_app_error() {
+        let error = Error::from(AppError::from(AmqpError::IllegalState));
+        assert_eq!(error.condition().inner(), "amqp:illegal-state");
+        assert!(error.description().is_some());
+    }
+}
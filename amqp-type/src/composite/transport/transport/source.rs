@@ -1,5 +1,22 @@
 use amqp_derive::AmqpComposite;
 
+/// # Source
+/// Describes the node at the sending end of the link, where messages come from.
+/// Only the address of the node is supported so far.
 #[derive(Debug, Clone, PartialEq, Default, AmqpComposite)]
 #[amqp(name = "amqp:source:list", code = 0x28)]
-pub struct Source {}
+pub struct Source {
+    address: Option<String>,
+}
+
+impl Source {
+    pub fn new(address: impl Into<String>) -> Self {
+        Source {
+            address: Some(address.into()),
+        }
+    }
+
+    pub fn address(&self) -> Option<&str> {
+        self.address.as_deref()
+    }
+}
@@ -1,5 +1,22 @@
 use amqp_derive::AmqpComposite;
 
+/// # Target
+/// Describes the node at the receiving end of the link, where messages go to.
+/// Only the address of the node is supported so far.
 #[derive(Debug, Clone, PartialEq, Default, AmqpComposite)]
 #[amqp(name = "amqp:target:list", code = 0x29)]
-pub struct Target {}
\ No newline at end of file
+pub struct Target {
+    address: Option<String>,
+}
+
+impl Target {
+    pub fn new(address: impl Into<String>) -> Self {
+        Target {
+            address: Some(address.into()),
+        }
+    }
+
+    pub fn address(&self) -> Option<&str> {
+        self.address.as_deref()
+    }
+}
@@ -61,4 +61,24 @@ pub const PERFORMATIVE_SYMBOL_TRANSFER: &str = "amqp:transfer:list";
 pub const PERFORMATIVE_SYMBOL_DISPOSITION: &str = "amqp:disposition:list";
 pub const PERFORMATIVE_SYMBOL_DETACH: &str = "amqp:detach:list";
 pub const PERFORMATIVE_SYMBOL_END: &str = "amqp:end:list";
-pub const PERFORMATIVE_SYMBOL_CLOSE: &str = "amqp:close:list";
\ No newline at end of file
+pub const PERFORMATIVE_SYMBOL_CLOSE: &str = "amqp:close:list";
+
+pub const SECTION_CODE_HEADER: u64 = 0x70;
+pub const SECTION_CODE_DELIVERY_ANNOTATIONS: u64 = 0x71;
+pub const SECTION_CODE_MESSAGE_ANNOTATIONS: u64 = 0x72;
+pub const SECTION_CODE_PROPERTIES: u64 = 0x73;
+pub const SECTION_CODE_APPLICATION_PROPERTIES: u64 = 0x74;
+pub const SECTION_CODE_DATA: u64 = 0x75;
+pub const SECTION_CODE_AMQP_SEQUENCE: u64 = 0x76;
+pub const SECTION_CODE_AMQP_VALUE: u64 = 0x77;
+pub const SECTION_CODE_FOOTER: u64 = 0x78;
+
+pub const SECTION_SYMBOL_HEADER: &str = "amqp:header:list";
+pub const SECTION_SYMBOL_DELIVERY_ANNOTATIONS: &str = "amqp:delivery-annotations:map";
+pub const SECTION_SYMBOL_MESSAGE_ANNOTATIONS: &str = "amqp:message-annotations:map";
+pub const SECTION_SYMBOL_PROPERTIES: &str = "amqp:properties:list";
+pub const SECTION_SYMBOL_APPLICATION_PROPERTIES: &str = "amqp:application-properties:map";
+pub const SECTION_SYMBOL_DATA: &str = "amqp:data:binary";
+pub const SECTION_SYMBOL_AMQP_SEQUENCE: &str = "amqp:amqp-sequence:list";
+pub const SECTION_SYMBOL_AMQP_VALUE: &str = "amqp:amqp-value:*";
+pub const SECTION_SYMBOL_FOOTER: &str = "amqp:footer:map";
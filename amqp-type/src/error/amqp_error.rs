@@ -8,7 +8,7 @@ const AMQP_INTERNAL_ERROR: &str = "amqp:internal-error";
 const AMQP_NOT_FOUND: &str = "amqp:not-found";
 const AMQP_UNAUTHORIZED_ACCESS: &str = "amqp:unauthorized-access";
 const AMQP_DECODE_ERROR: &str = "amqp:decode-error";
-const AMQP_RESOURCE_LIMITED_EXCEEDED: &str = "amqp:resource-limited-exceeded";
+const AMQP_RESOURCE_LIMIT_EXCEEDED: &str = "amqp:resource-limit-exceeded";
 const AMQP_NOT_ALLOWED: &str = "amqp:not-allowed";
 const AMQP_INVALID_FIELD: &str = "amqp:invalid-field";
 const AMQP_NOT_IMPLEMENTED: &str = "amqp:not-implemented";
@@ -40,7 +40,7 @@ pub(crate) const TAGS: [&str; 13] = [
     AMQP_NOT_FOUND,
     AMQP_UNAUTHORIZED_ACCESS,
     AMQP_DECODE_ERROR,
-    AMQP_RESOURCE_LIMITED_EXCEEDED,
+    AMQP_RESOURCE_LIMIT_EXCEEDED,
     AMQP_NOT_ALLOWED,
     AMQP_INVALID_FIELD,
     AMQP_NOT_IMPLEMENTED,
@@ -58,7 +58,7 @@ impl Display for AmqpError {
             AmqpError::NotFound => write!(f, "{}", AMQP_NOT_FOUND),
             AmqpError::UnauthorizedAccess => write!(f, "{}", AMQP_UNAUTHORIZED_ACCESS),
             AmqpError::DecodeError => write!(f, "{}", AMQP_DECODE_ERROR),
-            AmqpError::ResourceLimitExceeded => write!(f, "{}", AMQP_RESOURCE_LIMITED_EXCEEDED),
+            AmqpError::ResourceLimitExceeded => write!(f, "{}", AMQP_RESOURCE_LIMIT_EXCEEDED),
             AmqpError::NotAllowed => write!(f, "{}", AMQP_NOT_ALLOWED),
             AmqpError::InvalidField => write!(f, "{}", AMQP_INVALID_FIELD),
             AmqpError::NotImplemented => write!(f, "{}", AMQP_NOT_IMPLEMENTED),
@@ -114,7 +114,7 @@ impl From<&str> for AmqpError {
             AMQP_NOT_FOUND => AmqpError::NotFound,
             AMQP_UNAUTHORIZED_ACCESS => AmqpError::UnauthorizedAccess,
             AMQP_DECODE_ERROR => AmqpError::DecodeError,
-            AMQP_RESOURCE_LIMITED_EXCEEDED => AmqpError::ResourceLimitExceeded,
+            AMQP_RESOURCE_LIMIT_EXCEEDED => AmqpError::ResourceLimitExceeded,
             AMQP_NOT_ALLOWED => AmqpError::NotAllowed,
             AMQP_INVALID_FIELD => AmqpError::InvalidField,
             AMQP_NOT_IMPLEMENTED => AmqpError::NotImplemented,
@@ -193,7 +193,7 @@ mod tests {
         assert!(matches!(
             AmqpError::try_from((
                 Some(Primitive::Symbol(
-                    Symbol::new(AMQP_RESOURCE_LIMITED_EXCEEDED.into()).unwrap()
+                    Symbol::new(AMQP_RESOURCE_LIMIT_EXCEEDED.into()).unwrap()
                 )),
                 None,
                 None
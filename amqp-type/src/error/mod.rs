@@ -17,7 +17,7 @@ pub mod connection_error;
 pub mod link_error;
 pub mod session_error;
 
-trait ErrorCondition {
+pub(crate) trait ErrorCondition {
     fn error_condition(&self) -> Symbol;
     fn amqp_description(&self) -> Option<String>;
     fn info(&self) -> Option<Fields>;
@@ -10,7 +10,7 @@ use indexmap::IndexMap;
 use std::hash::Hash;
 use std::vec::IntoIter;
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
 pub struct Map(IndexMap<Primitive, Primitive>);
 
 impl Map {
@@ -42,6 +42,22 @@ impl Map {
         let primitive: Primitive = key.into();
         self.0.remove(&primitive)
     }
+
+    pub fn insert<K, V>(&mut self, key: K, value: V) -> Option<Primitive>
+    where
+        K: Into<Primitive>,
+        V: Into<Primitive>,
+    {
+        self.0.insert(key.into(), value.into())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 impl Encode for Map {
@@ -101,7 +117,7 @@ fn parse_to_index_map(
     size: usize,
     count: usize,
 ) -> Result<IndexMap<Primitive, Primitive>, AppError> {
-    if count % 2 != 0 {
+    if !count.is_multiple_of(2) {
         Err(AmqpError::InvalidField)?;
     }
     let mut buffer = read_bytes(stream, size)?.into_iter();
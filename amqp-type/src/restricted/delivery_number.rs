@@ -1,8 +1,12 @@
-use crate::restricted::sequence_no::SequenceNumber;
+use crate::restricted::sequence_no::serial_number;
 
-/// # Delivery Number
-/// ##### AMQP Spec
-/// ```xml
-/// <type name="delivery-number" class="restricted" source="sequence-no"/>
-/// ```
-pub type DeliveryNumber = SequenceNumber;
+serial_number!(
+    /// # Delivery Number
+    /// A sequence-no numbering the deliveries of a session, which dispositions refer to in
+    /// ranges.
+    /// ##### AMQP Spec
+    /// ```xml
+    /// <type name="delivery-number" class="restricted" source="sequence-no"/>
+    /// ```
+    DeliveryNumber
+);
@@ -11,7 +11,7 @@ use crate::error::AppError;
 /// ```
 /// A delivery-tag may be up to 32 octets of binary data.
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct DeliveryTag(Binary);
 
 impl DeliveryTag {
@@ -15,7 +15,7 @@ use crate::error::AppError;
 /// Valid Values:
 /// - 0: The Receiver will spontaneously settle all incoming transfers.
 /// - 1: The Receiver will only settle after sending the disposition to the Sender and
-///      receiving a disposition indicating settlement of the delivery from the sender.
+///   receiving a disposition indicating settlement of the delivery from the sender.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ReceiverSettleMode {
     First,
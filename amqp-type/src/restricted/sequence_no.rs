@@ -1,126 +1,148 @@
-use crate::error::AppError;
-use crate::serde::decode::Decode;
-use crate::serde::encode::{Encode, Encoded};
 use std::cmp::Ordering;
-use std::num::Wrapping;
-use std::ops::{Add, AddAssign};
-use std::vec::IntoIter;
-use crate::error::amqp_error::AmqpError;
-use crate::primitive::Primitive;
-
-/// # Sequence Number
-/// A 32-bit RFC-1982 serial number.
-/// ```xml
-/// <type name="sequence-no" class="restricted" source="uint"/>
-/// ```
-/// A sequence-no encodes a serial number as defined in RFC-1982. The arithmetic, and operators for
-/// these numbers are defined by RFC-1982.
-#[derive(Debug, Clone, Copy, Default)]
-pub struct SequenceNumber(u32);
-
-const HALF_MAX: u32 = 1 << 30;
-
-impl SequenceNumber {
-    pub fn new(value: u32) -> Self {
-        Self(value)
-    }
 
-    pub fn inner(&self) -> u32 {
-        self.0
+/// Half the number space of a 32-bit serial number, 2^(SERIAL_BITS - 1) in RFC-1982.
+pub const HALF_MAX: u32 = 1 << 31;
+
+/// Compares two 32-bit serial numbers as defined by RFC-1982. Numbers exactly [`HALF_MAX`]
+/// apart are not comparable.
+pub fn compare(i1: u32, i2: u32) -> Option<Ordering> {
+    if i1 == i2 {
+        Some(Ordering::Equal)
+    } else if (i1 < i2 && i2 - i1 < HALF_MAX) || (i1 > i2 && i1 - i2 > HALF_MAX) {
+        Some(Ordering::Less)
+    } else if (i1 < i2 && i2 - i1 > HALF_MAX) || (i1 > i2 && i1 - i2 < HALF_MAX) {
+        Some(Ordering::Greater)
+    } else {
+        // This case is undefined behaviour according to RFC 1982.
+        None
     }
 }
 
-impl From<u32> for SequenceNumber {
-    fn from(value: u32) -> Self {
-        Self(value)
-    }
-}
+/// Defines a newtype over `u32` with the serial number arithmetic of RFC-1982: additions wrap
+/// around, and numbers compare by which one comes first within half the number space.
+macro_rules! serial_number {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+        pub struct $name(u32);
+
+        impl $name {
+            pub fn new(value: u32) -> Self {
+                Self(value)
+            }
+
+            pub fn inner(&self) -> u32 {
+                self.0
+            }
+
+            /// The number of increments that lead from `self` to `to`, wrapping around.
+            pub fn distance(&self, to: Self) -> u32 {
+                to.0.wrapping_sub(self.0)
+            }
+
+            /// Whether the number lies within `first` and `last` (inclusive), where the range
+            /// may wrap around from `u32::MAX` to `0`.
+            pub fn in_range(&self, first: Self, last: Self) -> bool {
+                first.distance(*self) <= first.distance(last)
+            }
+        }
 
-impl Add for SequenceNumber {
-    type Output = Self;
+        impl From<u32> for $name {
+            fn from(value: u32) -> Self {
+                Self(value)
+            }
+        }
 
-    fn add(self, rhs: Self) -> Self::Output {
-        Self((Wrapping(self.0) + Wrapping(rhs.0)).0)
-    }
-}
+        impl From<$name> for u32 {
+            fn from(value: $name) -> Self {
+                value.0
+            }
+        }
 
-impl AddAssign for SequenceNumber {
-    fn add_assign(&mut self, rhs: Self) {
-        self.0 += rhs.0;
-    }
-}
+        impl std::ops::Add for $name {
+            type Output = Self;
 
-impl PartialEq<Self> for SequenceNumber {
-    fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0
-    }
-}
+            fn add(self, rhs: Self) -> Self::Output {
+                Self(self.0.wrapping_add(rhs.0))
+            }
+        }
 
-impl PartialOrd<Self> for SequenceNumber {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let i1 = self.0;
-        let i2 = other.0;
-        if i1 == i2 {
-            Some(Ordering::Equal)
-        } else if (i1 < i2 && i2 - i1 < HALF_MAX) || (i1 > i2 && i1 - i2 > HALF_MAX) {
-            Some(Ordering::Less)
-        } else if (i1 < i2 && i2 - i1 > HALF_MAX) || (i1 > i2 && i1 - i2 < HALF_MAX) {
-            Some(Ordering::Greater)
-        } else {
-            // This case is undefined behaviour according to RFC 1982.
-            None
+        impl std::ops::AddAssign for $name {
+            fn add_assign(&mut self, rhs: Self) {
+                *self = *self + rhs;
+            }
         }
-    }
-}
 
-impl Encode for SequenceNumber {
-    fn encode(self) -> Encoded {
-        self.0.encode()
-    }
-}
+        impl PartialOrd<Self> for $name {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                $crate::restricted::sequence_no::compare(self.0, other.0)
+            }
+        }
 
-impl Decode for SequenceNumber {
-    fn try_decode(constructor: u8, stream: &mut IntoIter<u8>) -> Result<Self, AppError>
-    where
-        Self: Sized,
-    {
-        u32::try_decode(constructor, stream).map(Self)
-    }
-}
+        impl $crate::serde::encode::Encode for $name {
+            fn encode(self) -> $crate::serde::encode::Encoded {
+                self.0.encode()
+            }
+        }
+
+        impl $crate::serde::decode::Decode for $name {
+            fn try_decode(constructor: u8, stream: &mut std::vec::IntoIter<u8>) -> Result<Self, $crate::error::AppError>
+            where
+                Self: Sized,
+            {
+                u32::try_decode(constructor, stream).map(Self)
+            }
+        }
 
-impl TryFrom<Primitive> for SequenceNumber {
-    type Error = AppError;
+        impl TryFrom<$crate::primitive::Primitive> for $name {
+            type Error = $crate::error::AppError;
 
-    fn try_from(value: Primitive) -> Result<Self, Self::Error> {
-        match value {
-            Primitive::Uint(x) => Ok(SequenceNumber::new(x)),
-            _ => Err(AmqpError::DecodeError)?
+            fn try_from(value: $crate::primitive::Primitive) -> Result<Self, Self::Error> {
+                match value {
+                    $crate::primitive::Primitive::Uint(x) => Ok(Self(x)),
+                    _ => Err($crate::error::amqp_error::AmqpError::DecodeError)?,
+                }
+            }
         }
-    }
-}
-impl TryFrom<Primitive> for Option<SequenceNumber> {
-    type Error = AppError;
-
-    fn try_from(value: Primitive) -> Result<Self, Self::Error> {
-        match value {
-            Primitive::Null => Ok(None),
-            Primitive::Uint(x) => Ok(Some(SequenceNumber::new(x))),
-            _ => Err(AmqpError::DecodeError)?
+
+        impl TryFrom<$crate::primitive::Primitive> for Option<$name> {
+            type Error = $crate::error::AppError;
+
+            fn try_from(value: $crate::primitive::Primitive) -> Result<Self, Self::Error> {
+                match value {
+                    $crate::primitive::Primitive::Null => Ok(None),
+                    $crate::primitive::Primitive::Uint(x) => Ok(Some($name(x))),
+                    _ => Err($crate::error::amqp_error::AmqpError::DecodeError)?,
+                }
+            }
         }
-    }
-}
 
-impl From<SequenceNumber> for Primitive {
-    fn from(value: SequenceNumber) -> Self {
-        Primitive::Uint(value.0)
-    }
+        impl From<$name> for $crate::primitive::Primitive {
+            fn from(value: $name) -> Self {
+                $crate::primitive::Primitive::Uint(value.0)
+            }
+        }
+    };
 }
 
+pub(crate) use serial_number;
+
+serial_number!(
+    /// # Sequence Number
+    /// A 32-bit RFC-1982 serial number.
+    /// ```xml
+    /// <type name="sequence-no" class="restricted" source="uint"/>
+    /// ```
+    /// A sequence-no encodes a serial number as defined in RFC-1982. The arithmetic, and operators for
+    /// these numbers are defined by RFC-1982.
+    SequenceNumber
+);
+
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    const MAX: u32 = 1 << 31;
+    use crate::serde::decode::Decode;
+    use crate::serde::encode::Encode;
 
     #[test]
     fn test_default_is_zero() {
@@ -135,6 +157,13 @@ mod tests {
         assert_eq!((max + one).0, 0)
     }
 
+    #[test]
+    fn test_add_assign_wraps_around() {
+        let mut number = SequenceNumber::from(u32::MAX);
+        number += 2.into();
+        assert_eq!(number, 1.into());
+    }
+
     #[test]
     fn test_ordering() {
         assert!(SequenceNumber::from(0) > SequenceNumber::from(u32::MAX));
@@ -142,7 +171,8 @@ mod tests {
         assert!(SequenceNumber::from(200) > SequenceNumber::from(0));
         assert!(SequenceNumber::from(HALF_MAX + 1) < SequenceNumber::from(0));
         assert!(SequenceNumber::from(HALF_MAX - 1) > SequenceNumber::from(0));
-        assert!(SequenceNumber::from(HALF_MAX - 1) > SequenceNumber::from(MAX));
+        assert!(SequenceNumber::from(HALF_MAX - 1) < SequenceNumber::from(HALF_MAX));
+        assert!(SequenceNumber::from(1 << 30) > SequenceNumber::from(u32::MAX - (1 << 29)));
     }
 
     #[test]
@@ -151,4 +181,34 @@ mod tests {
         assert_eq!(SequenceNumber::from(1).partial_cmp(&(HALF_MAX + 1).into()), None);
         assert_eq!(SequenceNumber::from(0).partial_cmp(&HALF_MAX.into()), None);
     }
+
+    #[test]
+    fn test_distance_wraps_around() {
+        assert_eq!(SequenceNumber::from(3).distance(7.into()), 4);
+        assert_eq!(SequenceNumber::from(u32::MAX - 1).distance(2.into()), 4);
+        assert_eq!(SequenceNumber::from(7).distance(7.into()), 0);
+    }
+
+    #[test]
+    fn test_range_wraps_around() {
+        let (first, last) = (SequenceNumber::from(u32::MAX - 1), SequenceNumber::from(1));
+        assert!(SequenceNumber::from(u32::MAX).in_range(first, last));
+        assert!(SequenceNumber::from(0).in_range(first, last));
+        assert!(SequenceNumber::from(1).in_range(first, last));
+        assert!(!SequenceNumber::from(2).in_range(first, last));
+        assert!(!SequenceNumber::from(u32::MAX - 2).in_range(first, last));
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let mut encoded = SequenceNumber::from(u32::MAX)
+            .encode()
+            .into_bytes()
+            .into_iter();
+        let constructor = encoded.next().unwrap();
+        assert_eq!(
+            SequenceNumber::try_decode(constructor, &mut encoded).unwrap(),
+            u32::MAX.into()
+        );
+    }
 }
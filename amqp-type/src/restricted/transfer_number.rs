@@ -1,8 +1,12 @@
-use crate::restricted::sequence_no::SequenceNumber;
+use crate::restricted::sequence_no::serial_number;
 
-/// # Transfer Number
-/// ##### AMQP Spec
-/// ```xml
-/// <type name="transfer-number" class="restricted" source="sequence-no"/>
-/// ```
-pub type TransferNumber = SequenceNumber;
+serial_number!(
+    /// # Transfer Number
+    /// A sequence-no numbering the transfer frames of a session, which the incoming and
+    /// outgoing windows are counted in.
+    /// ##### AMQP Spec
+    /// ```xml
+    /// <type name="transfer-number" class="restricted" source="sequence-no"/>
+    /// ```
+    TransferNumber
+);